@@ -1,24 +1,62 @@
 use std::{
-    os::unix::ffi::OsStrExt,
+    collections::HashMap,
+    fs::File,
+    io::{Read, Write},
+    ops::Deref,
     path::{Path, PathBuf},
     process::ExitCode,
-    sync::Arc,
+    sync::{Arc, mpsc},
+    time::Duration,
 };
 
 use ariadne::{Color, Config, Fmt, Label, Report, ReportKind, Source};
-use deval_format_json::Json;
+use clap::builder::{PossibleValuesParser, TypedValueParser};
 use deval_format_toml::Toml;
-use deval_validator::{AnyValidator, ValidationError, Validator};
+use deval_validator::{AnyValidator, Severity, ValidationError, ValidationErrorKind, Validator};
+use globset::{GlobBuilder, GlobMatcher};
+use memmap2::Mmap;
+use notify::{EventKind, RecursiveMode, Watcher};
 
 use deval_data_model::{Format, ParseError};
 use serde::Deserialize;
 
 #[derive(Debug, Clone, Deserialize)]
 struct DevalRule {
-    filename: String,
+    /// A glob matched against the file name, or against the whole path if it
+    /// has a `/`, like `*.service.toml` or `**/ci/*.yaml`
+    #[serde(deserialize_with = "deserialize_glob")]
+    filename: GlobMatcher,
     schema: PathBuf,
 }
 
+impl DevalRule {
+    fn matches(&self, file: &Path) -> bool {
+        let target = match self.filename.glob().glob().contains('/') {
+            true => file.as_os_str(),
+            false => match file.file_name() {
+                Some(name) => name,
+                None => return false,
+            },
+        };
+        self.filename.is_match(target)
+    }
+}
+
+/// Compiles the glob of a rule. `*` and `?` don't match `/`, so they stay
+/// within a path segment, while `**` matches any number of whole segments.
+fn compile_glob(pattern: &str) -> Result<GlobMatcher, globset::Error> {
+    let glob = GlobBuilder::new(pattern).literal_separator(true).build()?;
+    Ok(glob.compile_matcher())
+}
+
+/// Reads the glob of a rule, so an invalid one is reported with the config file.
+fn deserialize_glob<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<GlobMatcher, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    compile_glob(&pattern).map_err(serde::de::Error::custom)
+}
+
 #[derive(Debug, Default, Deserialize)]
 struct DevalConfig {
     rules: Vec<DevalRule>,
@@ -33,32 +71,52 @@ impl DevalConfig {
         if near.exists() {
             return Some(near);
         }
+        // The first matching rule wins
         Some(
             self.rules
                 .iter()
-                .find(|rule| {
-                    file.file_name()
-                        .is_some_and(|x| x.as_bytes() == rule.filename.as_bytes())
-                })
-                .cloned()?
-                .schema,
+                .find(|rule| rule.matches(file))?
+                .schema
+                .clone(),
         )
     }
 }
 
-fn report_validation_errors(source: &str, errors: &[ValidationError]) {
+/// Prints validation errors, with the path of the value each error is about if
+/// `explain_errors` is set.
+fn report_validation_errors(source: &str, errors: &[ValidationError], explain_errors: bool) {
     for error in errors {
         let source = Source::from(source);
         // Create a simple error report pointing to the beginning of the file
         // In a real implementation, you'd want to map errors to specific positions
         let filename = &*error.span.filename;
         let span = error.span.start..error.span.end;
-        Report::build(ReportKind::Error, (filename, span.clone()))
+        let (kind, label) = match error.severity {
+            Severity::Error => (ReportKind::Error, "error occurred here"),
+            Severity::Warning => (ReportKind::Warning, "warning raised here"),
+        };
+        let label = if explain_errors {
+            format!("{label}, at {:?}", error.pointer())
+        } else {
+            label.to_owned()
+        };
+        let mut report = Report::build(kind, (filename, span.clone()))
             .with_message(&error.text)
-            .with_label(Label::new((filename, span.clone())).with_message("error occurred here"))
-            .finish()
-            .print((filename, source))
-            .unwrap();
+            .with_label(Label::new((filename, span.clone())).with_message(label));
+        // Only the file of the error is in the source cache
+        for other in error
+            .secondary
+            .iter()
+            .filter(|s| s.filename == error.span.filename)
+        {
+            report = report.with_label(
+                Label::new((filename, other.start..other.end)).with_message("also here"),
+            );
+        }
+        if let Some(help) = &error.help {
+            report = report.with_note(help);
+        }
+        report.finish().print((filename, source)).unwrap();
     }
 }
 
@@ -120,94 +178,608 @@ fn display_errors(src: &str, errors: Vec<deval_schema::Error<'_>>) {
     }
 }
 
+/// Parses `--input-format` as the name of one of the formats of `deval_formats`,
+/// which are listed as its possible values.
+fn input_format_parser() -> impl TypedValueParser<Value = Arc<dyn Format>> {
+    let names: Vec<&'static str> = deval_formats::formats()
+        .iter()
+        .map(|format| format.name())
+        .collect();
+    PossibleValuesParser::new(names).map(|name| {
+        deval_formats::format_by_name(&name).expect("the possible values are format names")
+    })
+}
+
 #[derive(clap::Parser)]
 enum Args {
     ConvertJsonSchema {
+        /// A JSON Schema file, `-` to read it from stdin, or an http(s) URL when
+        /// built with the `fetch` feature
         file: PathBuf,
+        /// Inline definitions where they are referenced
+        #[arg(long, overrides_with = "named")]
+        inline: bool,
+        /// Emit definitions as named types. This is the default
+        #[arg(long, overrides_with = "inline")]
+        named: bool,
     },
     Check {
         #[arg(short, long)]
         schema: Option<PathBuf>,
+        /// A file to check, or `-` to read it from stdin. Can be repeated
+        #[arg(short, long)]
+        file: Vec<PathBuf>,
+        /// More files to check
+        files: Vec<PathBuf>,
+        /// Format of the input, inferred from the file extension if omitted
+        #[arg(long, value_parser = input_format_parser())]
+        input_format: Option<Arc<dyn Format>>,
+        /// Print nothing and report the result only through the exit code
+        #[arg(short, long)]
+        quiet: bool,
+        /// Fail on unknown keys. This is the default
+        #[arg(long, overrides_with = "no_strict")]
+        strict: bool,
+        /// Report unknown keys as warnings, which don't fail the check. Missing
+        /// keys still do
+        #[arg(long, overrides_with = "strict")]
+        no_strict: bool,
+        /// Report unknown keys as warnings, even with --strict
+        #[arg(long)]
+        allow_unknown: bool,
+        /// Check again whenever an input file or its schema changes, until Ctrl-C
+        #[arg(short, long)]
+        watch: bool,
+        /// Show the path of the value each error is about, like `/servers/0/port`
+        #[arg(long)]
+        explain_errors: bool,
+    },
+    /// Reformat a JSON or TOML file. Comments are not kept
+    Fmt {
         #[arg(short, long)]
         file: PathBuf,
+        /// Format of the input, inferred from the file extension if omitted
+        #[arg(long, value_parser = input_format_parser())]
+        input_format: Option<Arc<dyn Format>>,
+        /// Rewrite the file instead of printing the result
+        #[arg(short, long)]
+        write: bool,
+    },
+    /// Print the compiled schema in a normalized form
+    Explain {
+        #[arg(short, long)]
+        schema: PathBuf,
     },
     Lsp,
+    /// Print the path of the config file and the rules in it
+    Config,
+}
+
+/// The path of the config file: `$DEVAL_CONFIG` if set, otherwise
+/// `deval/config.toml` in the user's config directory, like `~/.config` on
+/// Linux.
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("DEVAL_CONFIG") {
+        return Some(path.into());
+    }
+    Some(dirs::config_dir()?.join("deval").join("config.toml"))
 }
 
+/// Loads the config file, or the default config if there is none. An invalid
+/// config file is reported on stderr, which is free even when serving the LSP,
+/// and the default config is used instead.
 fn load_config() -> DevalConfig {
-    let Ok(text) = std::fs::read_to_string("/root/.config/deval/config.toml") else {
+    let Some(path) = config_path() else {
         return DevalConfig::default();
     };
-    let spanned = Toml.parse(&text, "config.toml").unwrap_or_else(|e| {
-        report_errors(&text, &e);
-        panic!();
-    });
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return DevalConfig::default();
+    };
+    let filename = path.display().to_string();
+    let spanned = match Toml.parse(&text, &filename) {
+        Ok(spanned) => spanned,
+        Err(errors) => {
+            for error in errors {
+                let (start, _) = error.span.line_col(&text);
+                eprintln!(
+                    "{filename}:{}:{}: {}",
+                    start.line + 1,
+                    start.col + 1,
+                    error.message
+                );
+            }
+            eprintln!("Ignoring the invalid config file {filename}");
+            return DevalConfig::default();
+        }
+    };
     let annotated = AnyValidator.validate(spanned);
-    deval_serde::deserialize_from_annotated(&annotated.result.discard_annotation())
+    match deval_serde::try_deserialize_from_annotated(&annotated.result.discard_annotation()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Ignoring the invalid config file {filename}: {e}");
+            DevalConfig::default()
+        }
+    }
 }
 
-fn main() -> ExitCode {
-    use clap::Parser;
-    let args = Args::parse();
+/// Input files larger than this many bytes are memory mapped instead of read
+/// into a string, and checking any file this large prints a warning,
+/// since parsing still keeps the whole document in memory.
+const LARGE_FILE_SIZE: u64 = 4 * 1024 * 1024;
 
-    match args {
-        Args::ConvertJsonSchema { file } => {
-            let text = std::fs::read_to_string(&file).unwrap();
-            let result = deval_schema_from_json_schema::convert(&text);
-            println!("{result}");
-            ExitCode::SUCCESS
+/// The text of an input file, mapped into memory if the file is large.
+enum InputText {
+    Read(String),
+    /// A mapping that has been checked to be UTF-8. The file is expected not to
+    /// change while it is checked, like other tools mapping their inputs do.
+    Mapped(Mmap),
+}
+
+impl InputText {
+    /// Reads `file`, mapping it if it is larger than `LARGE_FILE_SIZE`, and
+    /// `may_map` is set. Files that can't be mapped are read instead, and files
+    /// that aren't UTF-8 are left to `read_to_string` to report.
+    fn load(file: &Path, may_map: bool) -> std::io::Result<Self> {
+        let handle = File::open(file)?;
+        if may_map && handle.metadata()?.len() > LARGE_FILE_SIZE {
+            // SAFETY: input files aren't expected to be written to while they
+            // are checked, and watched ones, which are, aren't mapped
+            let mmap = unsafe { Mmap::map(&handle) };
+            if let Ok(mmap) = mmap
+                && std::str::from_utf8(&mmap).is_ok()
+            {
+                return Ok(InputText::Mapped(mmap));
+            }
         }
-        Args::Check { schema, file } => {
-            let schema = match schema {
-                Some(path) => path,
-                None => {
-                    let config = load_config();
-                    dbg!(&config);
-                    match config.find_schema_path(&file) {
-                        Some(path) => path,
-                        None => {
-                            eprintln!("Unknown schema for {file:?}");
-                            return ExitCode::FAILURE;
-                        }
+        std::fs::read_to_string(file).map(InputText::Read)
+    }
+}
+
+impl Deref for InputText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            InputText::Read(text) => text,
+            // SAFETY: checked to be UTF-8 when the file was loaded, and it isn't
+            // expected to change after that
+            InputText::Mapped(mmap) => unsafe { std::str::from_utf8_unchecked(mmap) },
+        }
+    }
+}
+
+/// Checks files against their schemas, compiling each schema only once.
+struct Checker {
+    schema: Option<PathBuf>,
+    input_format: Option<Arc<dyn Format>>,
+    quiet: bool,
+    /// Whether lenient errors, like unknown keys, fail the check, rather than
+    /// being warnings
+    strict: bool,
+    /// Whether unknown keys are warnings, whatever the strictness
+    allow_unknown: bool,
+    /// Whether errors are reported with the path of their value
+    explain_errors: bool,
+    /// Whether the success message names the file, when checking several files
+    prefix_filename: bool,
+    /// Whether large files may be memory mapped, which they aren't when watched
+    /// since they are expected to change
+    map_large_files: bool,
+    config: Option<DevalConfig>,
+    /// Compiled schemas by path, or `None` if the schema failed to compile
+    validators: HashMap<PathBuf, Option<Box<dyn Validator>>>,
+    /// The schemas compiled before the last change in watch mode, used while a
+    /// schema can't be read, like in the middle of being saved
+    previous_validators: HashMap<PathBuf, Option<Box<dyn Validator>>>,
+}
+
+impl Checker {
+    /// The schema `file` should be checked against.
+    fn schema_path(&mut self, file: &Path) -> Option<PathBuf> {
+        match &self.schema {
+            Some(path) => Some(path.clone()),
+            None if file.as_os_str() == "-" => None,
+            None => self
+                .config
+                .get_or_insert_with(load_config)
+                .find_schema_path(file),
+        }
+    }
+
+    fn check(&mut self, file: &Path) -> bool {
+        let quiet = self.quiet;
+        let is_stdin = file.as_os_str() == "-";
+        let Some(schema) = self.schema_path(file) else {
+            if !quiet {
+                if is_stdin {
+                    eprintln!("A schema must be given with --schema when reading from stdin");
+                } else {
+                    eprintln!("Unknown schema for {file:?}");
+                }
+            }
+            return false;
+        };
+        let (source, filename) = if is_stdin {
+            let mut source = String::new();
+            if let Err(e) = std::io::stdin().read_to_string(&mut source) {
+                if !quiet {
+                    eprintln!("Failed to read stdin: {e}");
+                }
+                return false;
+            }
+            (InputText::Read(source), "<stdin>".to_owned())
+        } else {
+            // The file may be gone for a moment while an editor replaces it
+            let source = match InputText::load(file, self.map_large_files) {
+                Ok(source) => source,
+                Err(e) => {
+                    if !quiet {
+                        eprintln!("Failed to read {}: {e}", file.display());
                     }
+                    return false;
                 }
             };
-            let schema_source = std::fs::read_to_string(&schema).unwrap();
-            let source = std::fs::read_to_string(&file).unwrap();
-            let format: Box<dyn Format> = match file.extension().and_then(|x| x.to_str()) {
-                Some("json") => Box::new(Json),
-                Some("toml") => Box::new(Toml),
-                Some(f) => panic!("Unknown format {f}"),
-                None => panic!("Unknown format"),
-            };
-            match format.parse(
-                &source,
-                &file
-                    .file_name()
-                    .map(|x| x.to_string_lossy())
-                    .unwrap_or_default(),
-            ) {
-                Ok(data) => {
-                    let validator = match deval_schema::compile(&schema_source) {
-                        Ok(v) => v,
-                        Err(e) => {
+            if source.len() as u64 > LARGE_FILE_SIZE && !quiet {
+                eprintln!(
+                    "Warning: {} is {} MiB, checking it may be slow and use a lot of memory",
+                    file.display(),
+                    source.len() / (1024 * 1024)
+                );
+            }
+            (source, file.display().to_string())
+        };
+        let input_format = self.input_format.clone();
+        let input_format = match input_format.or_else(|| deval_formats::format_for_path(file)) {
+            Some(f) => f,
+            None => {
+                if !quiet {
+                    eprintln!("Unknown format for {filename}, use --input-format to specify it");
+                }
+                return false;
+            }
+        };
+        // Each document of a multi-document file is checked on its own, so the
+        // ones that parse are checked despite syntax errors in the others
+        let mut documents = vec![];
+        let mut parse_errors = vec![];
+        let (parsed, _) = input_format.parse_many(&source, &filename);
+        for document in parsed {
+            match document {
+                Ok(document) => documents.push(document),
+                Err(errors) => parse_errors.extend(errors),
+            }
+        }
+        if !parse_errors.is_empty() && !quiet {
+            report_errors(&source, &parse_errors);
+        }
+        let Some(validator) = self.validator(&schema) else {
+            return false;
+        };
+        let mut errors: Vec<ValidationError> = documents
+            .into_iter()
+            .flat_map(|document| validator.validate(document).errors)
+            .collect();
+        for error in &mut errors {
+            let lenient = !self.strict && error.kind.is_lenient();
+            let allowed = self.allow_unknown && error.kind == ValidationErrorKind::UnknownKey;
+            if lenient || allowed {
+                error.severity = Severity::Warning;
+            }
+        }
+        if !quiet {
+            report_validation_errors(&source, &errors, self.explain_errors);
+        }
+        // Warnings are reported, but don't fail the check
+        if !parse_errors.is_empty() || errors.iter().any(|e| e.severity == Severity::Error) {
+            return false;
+        }
+        if !quiet {
+            if self.prefix_filename {
+                println!("{filename}: Input matches the schema!");
+            } else {
+                println!("Input matches the schema!");
+            }
+        }
+        true
+    }
+
+    /// Returns the compiled schema at `path`, reporting compile errors the first
+    /// time it is loaded. If it can't be read, the one it compiled to before is
+    /// used, if any.
+    fn validator(&mut self, path: &Path) -> Option<&dyn Validator> {
+        let quiet = self.quiet;
+        if !self.validators.contains_key(path) {
+            let validator = match std::fs::read_to_string(path) {
+                Ok(schema_source) => match deval_schema::compile(&schema_source) {
+                    Ok(v) => Some(v),
+                    Err(e) => {
+                        if !quiet {
                             display_errors(&schema_source, e);
-                            return ExitCode::FAILURE;
                         }
-                    };
-                    let r = validator.validate(data);
-                    if !r.errors.is_empty() {
-                        report_validation_errors(&source, &r.errors);
-                        return ExitCode::FAILURE;
+                        None
                     }
+                },
+                Err(e) => {
+                    if !quiet {
+                        eprintln!("Failed to read {}: {e}", path.display());
+                    }
+                    self.previous_validators.remove(path).flatten()
                 }
-                Err(errors) => {
-                    report_errors(&source, &errors);
+            };
+            self.validators.insert(path.to_owned(), validator);
+        }
+        self.validators[path].as_deref()
+    }
+}
+
+/// Reads a JSON Schema from `file`, from stdin if it is `-`, or from an http(s)
+/// URL.
+fn read_json_schema(file: &Path) -> Result<String, String> {
+    if file.as_os_str() == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Failed to read stdin: {e}"))?;
+        return Ok(text);
+    }
+    let url = file
+        .to_str()
+        .filter(|f| f.starts_with("http://") || f.starts_with("https://"));
+    if let Some(url) = url {
+        return fetch(url);
+    }
+    std::fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {e}", file.display()))
+}
+
+#[cfg(feature = "fetch")]
+fn fetch(url: &str) -> Result<String, String> {
+    ureq::get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| format!("Failed to fetch {url}: {e}"))
+}
+
+#[cfg(not(feature = "fetch"))]
+fn fetch(url: &str) -> Result<String, String> {
+    Err(format!(
+        "Can't fetch {url}, deval was built without the `fetch` feature"
+    ))
+}
+
+/// Reformats `file`, printing the result or writing it back to the file.
+fn fmt(file: &Path, input_format: Option<Arc<dyn Format>>, write: bool) -> ExitCode {
+    let source = match std::fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", file.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let Some(input_format) = input_format.or_else(|| deval_formats::format_for_path(file)) else {
+        eprintln!(
+            "Unknown format for {}, use --input-format to specify it",
+            file.display()
+        );
+        return ExitCode::FAILURE;
+    };
+    let data = match input_format.parse(&source, &file.display().to_string()) {
+        Ok(data) => data,
+        Err(errors) => {
+            report_errors(&source, &errors);
+            return ExitCode::FAILURE;
+        }
+    };
+    let formatted = match input_format.name() {
+        "json" => Ok(deval_format_json::emit(&data.value)),
+        "toml" => deval_format_toml::emit(&data.value, &source),
+        name => Err(format!("Formatting {name} files is not supported")),
+    };
+    let formatted = match formatted {
+        Ok(formatted) => formatted,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if !write {
+        print!("{formatted}");
+    } else if let Err(e) = std::fs::write(file, formatted) {
+        eprintln!("Failed to write {}: {e}", file.display());
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Checks `files` each time one of them or their schemas changes, until Ctrl-C
+/// is pressed. Returns the status of the last check.
+fn watch(checker: &mut Checker, files: &[PathBuf]) -> ExitCode {
+    if files.iter().any(|file| file.as_os_str() == "-") {
+        eprintln!("Stdin can not be watched");
+        return ExitCode::FAILURE;
+    }
+    let schemas: Vec<PathBuf> = files
+        .iter()
+        .filter_map(|file| checker.schema_path(file))
+        .collect();
+    // Editors often save by replacing the file, which would end a watch on the
+    // file itself, so the containing directories are watched instead.
+    let mut watched = vec![];
+    for path in files.iter().chain(&schemas) {
+        match path.canonicalize() {
+            Ok(path) => watched.push(path),
+            Err(e) => {
+                eprintln!("Failed to watch {}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    // `None` is sent when Ctrl-C is pressed
+    let (tx, rx) = mpsc::channel::<Option<notify::Result<notify::Event>>>();
+    let interrupt = tx.clone();
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed building the Runtime");
+        if runtime.block_on(tokio::signal::ctrl_c()).is_ok() {
+            let _ = interrupt.send(None);
+        }
+    });
+    let handler = move |event| {
+        let _ = tx.send(Some(event));
+    };
+    let result = notify::recommended_watcher(handler).and_then(|mut watcher| {
+        for path in &watched {
+            if let Some(dir) = path.parent() {
+                watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            }
+        }
+        Ok(watcher)
+    });
+    let _watcher = match result {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("Failed to watch files: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    loop {
+        // Clear the screen and move the cursor to the top left corner
+        print!("\x1B[2J\x1B[1;1H");
+        std::io::stdout().flush().unwrap();
+        checker.previous_validators = std::mem::take(&mut checker.validators);
+        let results: Vec<bool> = files.iter().map(|file| checker.check(file)).collect();
+        let status = if results.iter().all(|&ok| ok) {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
+        let is_relevant = |event: &notify::Result<notify::Event>| {
+            event.as_ref().is_ok_and(|event| {
+                !matches!(event.kind, EventKind::Access(_))
+                    && event.paths.iter().any(|path| watched.contains(path))
+            })
+        };
+        loop {
+            match rx.recv() {
+                Ok(Some(event)) if is_relevant(&event) => break,
+                Ok(Some(_)) => (),
+                Ok(None) => return status,
+                Err(_) => return ExitCode::FAILURE,
+            }
+        }
+        // Saving may touch a file several times in a row, so wait for the
+        // changes to settle before checking again
+        loop {
+            match rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(Some(_)) => (),
+                Ok(None) => return status,
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    use clap::Parser;
+    let args = Args::parse();
+
+    match args {
+        Args::ConvertJsonSchema {
+            file,
+            inline,
+            named: _,
+        } => {
+            let text = match read_json_schema(&file) {
+                Ok(text) => text,
+                Err(e) => {
+                    eprintln!("{e}");
                     return ExitCode::FAILURE;
                 }
+            };
+            // A schema without definitions converts the same either way
+            let result = if inline {
+                deval_schema_from_json_schema::try_convert(&text)
+            } else {
+                deval_schema_from_json_schema::try_convert_named(&text)
+            };
+            match result {
+                Ok(result) => {
+                    println!("{result}");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("Invalid JSON Schema: {e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Args::Check {
+            schema,
+            file,
+            files,
+            input_format,
+            quiet,
+            strict: _,
+            no_strict,
+            allow_unknown,
+            watch: watch_files,
+            explain_errors,
+        } => {
+            let files: Vec<PathBuf> = file.into_iter().chain(files).collect();
+            if files.is_empty() {
+                if !quiet {
+                    eprintln!("No input files given, use --file or pass them as arguments");
+                }
+                return ExitCode::FAILURE;
+            }
+            let mut checker = Checker {
+                schema,
+                input_format,
+                quiet,
+                strict: !no_strict,
+                allow_unknown,
+                explain_errors,
+                prefix_filename: files.len() > 1,
+                map_large_files: !watch_files,
+                config: None,
+                validators: HashMap::new(),
+                previous_validators: HashMap::new(),
+            };
+            if watch_files {
+                return watch(&mut checker, &files);
+            }
+            // Check every file, even after a failure
+            let results: Vec<bool> = files.iter().map(|file| checker.check(file)).collect();
+            if results.iter().all(|&ok| ok) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Args::Fmt {
+            file,
+            input_format,
+            write,
+        } => fmt(&file, input_format, write),
+        Args::Explain { schema } => {
+            let schema_source = match std::fs::read_to_string(&schema) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("Failed to read {}: {e}", schema.display());
+                    return ExitCode::FAILURE;
+                }
+            };
+            match deval_schema::compile(&schema_source) {
+                Ok(v) => {
+                    println!("{}", v.explain());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    display_errors(&schema_source, e);
+                    ExitCode::FAILURE
+                }
             }
-            println!("Input matches the schema!");
-            ExitCode::SUCCESS
         }
         Args::Lsp => {
             let config = load_config();
@@ -217,32 +789,64 @@ fn main() -> ExitCode {
                 .build()
                 .expect("Failed building the Runtime")
                 .block_on(async move {
-                    deval_lsp::start_server(move |path| {
-                        let format: Arc<dyn Format> =
-                            match path.extension().and_then(|x| x.to_str()) {
-                                Some("json") => Arc::new(Json),
-                                Some("toml") => Arc::new(Toml),
-                                Some(_) => return None,
-                                None => return None,
-                            };
-                        let validator: Arc<dyn Validator> = 'b: {
-                            let schema_file = match config.find_schema_path(&path) {
-                                Some(path) => path,
-                                None => {
-                                    break 'b Arc::new(AnyValidator);
-                                }
-                            };
-                            let schema_source = std::fs::read_to_string(&schema_file).unwrap();
-                            match deval_schema::compile(&schema_source) {
-                                Ok(v) => Arc::<dyn Validator>::from(v),
-                                Err(_) => Arc::new(AnyValidator),
-                            }
-                        };
-                        Some((format, validator))
-                    })
-                    .await;
+                    deval_lsp::start_server(move |path| config.find_schema_path(path)).await;
                 });
             ExitCode::SUCCESS
         }
+        Args::Config => {
+            match config_path() {
+                Some(path) if path.exists() => println!("Config file: {}", path.display()),
+                Some(path) => println!("Config file: {} (not found)", path.display()),
+                None => println!("Config file: none, set DEVAL_CONFIG to use one"),
+            }
+            let config = load_config();
+            if config.rules.is_empty() {
+                println!("No rules");
+            }
+            for rule in &config.rules {
+                println!("{} -> {}", rule.filename.glob(), rule.schema.display());
+            }
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_globs() {
+        let config = DevalConfig {
+            rules: [
+                ("*.service.toml", "service.dvl"),
+                ("**/ci/*.yaml", "ci.dvl"),
+                ("Cargo.toml", "cargo.dvl"),
+                ("*.toml", "toml.dvl"),
+            ]
+            .into_iter()
+            .map(|(filename, schema)| DevalRule {
+                filename: compile_glob(filename).unwrap(),
+                schema: schema.into(),
+            })
+            .collect(),
+        };
+        let schema = |path: &str| config.find_schema_path(Path::new(path));
+        assert_eq!(schema("/srv/web.service.toml"), Some("service.dvl".into()));
+        assert_eq!(schema("/repo/ci/build.yaml"), Some("ci.dvl".into()));
+        assert_eq!(schema("/repo/a/b/ci/build.yaml"), Some("ci.dvl".into()));
+        assert_eq!(schema("/repo/ci/nested/build.yaml"), None);
+        assert_eq!(schema("/repo/Cargo.toml"), Some("cargo.dvl".into()));
+        assert_eq!(schema("/repo/other.toml"), Some("toml.dvl".into()));
+        assert_eq!(schema("/repo/other.json"), None);
+
+        let glob = |pattern| compile_glob(pattern).unwrap();
+        assert!(glob("a?c").is_match("abc"));
+        assert!(!glob("a?c").is_match("ac"));
+        assert!(glob("*").is_match(""));
+        assert!(glob("**").is_match("a/b"));
+        assert!(glob("*.{json,yaml}").is_match("a.yaml"));
+        assert!(glob("[ab].toml").is_match("b.toml"));
+        assert!(compile_glob("[a.toml").is_err());
     }
 }
@@ -0,0 +1,420 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+/// A path in the temp directory, unique to this test run so concurrent runs
+/// don't share files.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("deval-test-{}-{name}", std::process::id()))
+}
+
+fn human_schema() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/human/human.dvl")
+}
+
+fn check_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .arg("check")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The CLI may exit without reading its input, e.g. on invalid arguments
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_check_json_from_stdin() {
+    let schema = human_schema();
+    let input = std::fs::read_to_string(schema.with_extension("json")).unwrap();
+    let output = check_stdin(
+        &[
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+            "--input-format",
+            "json",
+        ],
+        &input,
+    );
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Input matches the schema!"));
+}
+
+#[test]
+fn test_check_stdin_without_format_fails() {
+    let schema = human_schema();
+    let output = check_stdin(&["--schema", schema.to_str().unwrap(), "--file", "-"], "{}");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--input-format"));
+}
+
+#[test]
+fn test_check_invalid_input_fails() {
+    let schema = human_schema();
+    let output = check_stdin(
+        &[
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+            "--input-format",
+            "json",
+        ],
+        "{}",
+    );
+    assert!(!output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Input matches the schema!"));
+}
+
+#[test]
+fn test_check_quiet_prints_nothing() {
+    let schema = human_schema();
+    let output = check_stdin(
+        &[
+            "--quiet",
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+        ],
+        "{}",
+    );
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_check_env_file() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/env/.env");
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .args(["check", "--file", file.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_check_env_from_stdin() {
+    let schema = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/env/.env.dvl");
+    let output = check_stdin(
+        &[
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+            "--input-format",
+            "env",
+        ],
+        "DATABASE_URL=mysql://localhost\nPORT=80\n",
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("does not match"));
+}
+
+#[test]
+fn test_check_multiple_files() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/env");
+    let bad = temp_path("multiple-files.env");
+    std::fs::write(&bad, "PORT=eighty\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .arg("check")
+        .arg("--schema")
+        .arg(dir.join(".env.dvl"))
+        .arg(dir.join(".env"))
+        .arg(&bad)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&bad).unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(".env: Input matches the schema!"));
+    assert!(stdout.contains(&bad.display().to_string()), "{stdout}");
+    assert!(stdout.contains("Missing key DATABASE_URL"));
+}
+
+#[test]
+fn test_watch_stdin_fails() {
+    let schema = human_schema();
+    let output = check_stdin(
+        &[
+            "--watch",
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+        ],
+        "{}",
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Stdin can not be watched"));
+}
+
+#[test]
+fn test_check_missing_file_fails() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/env");
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .arg("check")
+        .arg("--schema")
+        .arg(dir.join(".env.dvl"))
+        .arg(dir.join("missing.env"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to read"));
+}
+
+#[test]
+fn test_check_missing_schema_fails() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/env/.env");
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .arg("check")
+        .arg("--schema")
+        .arg("/nonexistent/schema.dvl")
+        .arg(file)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to read /nonexistent/schema.dvl"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("panicked"), "{stderr}");
+}
+
+#[test]
+fn test_check_ini_file() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/ini/config.ini");
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .arg("check")
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Input matches the schema!"));
+}
+
+#[test]
+fn test_check_ron_file() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/ron/config.ron");
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .arg("check")
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Input matches the schema!"));
+}
+
+fn check_env_with_unknown_key(args: &[&str]) -> Output {
+    let schema = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/env/.env.dvl");
+    let args = [
+        args,
+        &[
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+            "--input-format",
+            "env",
+        ],
+    ]
+    .concat();
+    check_stdin(&args, "DATABASE_URL=postgres://db\nPORT=80\nPROT=81\n")
+}
+
+#[test]
+fn test_check_strict_fails_on_unknown_key() {
+    for args in [&[][..], &["--strict"], &["--no-strict", "--strict"]] {
+        let output = check_env_with_unknown_key(args);
+        assert!(!output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Unexpected key PROT"));
+        assert!(stdout.contains("Did you mean `PORT`?"));
+    }
+}
+
+#[test]
+fn test_check_no_strict_warns_on_unknown_key() {
+    for args in [
+        &["--no-strict"][..],
+        &["--allow-unknown"],
+        &["--strict", "--allow-unknown"],
+    ] {
+        let output = check_env_with_unknown_key(args);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Warning"));
+        assert!(stdout.contains("Unexpected key PROT"));
+        assert!(stdout.contains("warning raised here"), "{stdout}");
+        assert!(!stdout.contains("error occurred here"), "{stdout}");
+        assert!(stdout.contains("Input matches the schema!"));
+    }
+}
+
+#[test]
+fn test_check_allow_unknown_still_requires_keys() {
+    let schema = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/env/.env.dvl");
+    let args = [
+        "--allow-unknown",
+        "--schema",
+        schema.to_str().unwrap(),
+        "--file",
+        "-",
+        "--input-format",
+        "env",
+    ];
+    let output = check_stdin(&args, "PORT=80\nPROT=81\n");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Missing key DATABASE_URL"), "{stdout}");
+    assert!(stdout.contains("Unexpected key PROT"), "{stdout}");
+    assert!(stdout.contains("warning raised here"), "{stdout}");
+}
+
+#[test]
+fn test_check_missing_key_fails_without_strict() {
+    let schema = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/env/.env.dvl");
+    let check = |strictness: &str| {
+        let args = [
+            strictness,
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+            "--input-format",
+            "env",
+        ];
+        check_stdin(&args, "PORT=80\n")
+    };
+    // Only unknown keys are lenient, a required key is always required
+    for strictness in ["--strict", "--no-strict"] {
+        let output = check(strictness);
+        assert!(!output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Missing key DATABASE_URL"), "{stdout}");
+        assert!(stdout.contains("error occurred here"), "{stdout}");
+    }
+}
+
+#[test]
+fn test_explain_errors_shows_path() {
+    let schema = temp_path("explain-errors.dvl");
+    std::fs::write(&schema, "{ servers: { port: number }[] }").unwrap();
+    let output = check_stdin(
+        &[
+            "--explain-errors",
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+            "--input-format",
+            "ron",
+        ],
+        r#"(servers: [(port: 80), (port: "x")])"#,
+    );
+    std::fs::remove_file(&schema).unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(r#"at "/servers/1/port""#));
+}
+
+#[test]
+fn test_error_points_at_every_header() {
+    let schema = temp_path("every-header.dvl");
+    std::fs::write(&schema, "{ server: { host: string, tls: {} } }").unwrap();
+    let output = check_stdin(
+        &[
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+            "--input-format",
+            "toml",
+        ],
+        "[server]\n\n[server.tls]\n",
+    );
+    std::fs::remove_file(&schema).unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Missing key host"), "{stdout}");
+    assert!(stdout.contains("also here"), "{stdout}");
+}
+
+#[test]
+fn test_check_json_lines() {
+    let schema = temp_path("json-lines.dvl");
+    std::fs::write(&schema, "{ port: number }").unwrap();
+    let output = check_stdin(
+        &[
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+            "--input-format",
+            "json-lines",
+        ],
+        "{\"port\": 80}\n{\"port\": \"x\"}\n{\"port\": 443}\n",
+    );
+    std::fs::remove_file(&schema).unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Expected Number, found String"));
+    assert!(stdout.contains("<stdin>:2:"), "{stdout}");
+}
+
+#[test]
+fn test_check_json_lines_with_syntax_error() {
+    let schema = temp_path("json-lines-syntax.dvl");
+    std::fs::write(&schema, "{ port: number }").unwrap();
+    let output = check_stdin(
+        &[
+            "--schema",
+            schema.to_str().unwrap(),
+            "--file",
+            "-",
+            "--input-format",
+            "json-lines",
+        ],
+        "{\"port\": 80,}\n{\"port\": \"x\"}\n",
+    );
+    std::fs::remove_file(&schema).unwrap();
+    assert!(!output.status.success());
+    // The line after the syntax error is still checked
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("<stdin>:2:"), "{stdout}");
+    assert!(stdout.contains("Expected Number, found String"), "{stdout}");
+}
+
+#[test]
+fn test_check_large_file() {
+    let schema = temp_path("large-file.dvl");
+    let file = temp_path("large-file.ron");
+    std::fs::write(&schema, "{ items: { id: integer, name: string }[] }").unwrap();
+    let items: Vec<String> = (0..200_000)
+        .map(|i| format!("(id: {i}, name: \"item number {i}\")"))
+        .collect();
+    let input = format!("(items: [{}])", items.join(",\n"));
+    // Larger than the size above which the CLI maps the file
+    assert!(input.len() > 4 * 1024 * 1024);
+    std::fs::write(&file, input).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .args(["check", "--schema"])
+        .arg(&schema)
+        .arg("--file")
+        .arg(&file)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&schema).unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Input matches the schema!"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("checking it may be slow"));
+}
@@ -0,0 +1,46 @@
+use std::process::Command;
+
+#[test]
+fn test_config_from_env() {
+    let path = std::env::temp_dir().join("deval-test-missing-config.toml");
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .arg("config")
+        .env("DEVAL_CONFIG", &path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("Config file: {} (not found)\nNo rules\n", path.display())
+    );
+}
+
+#[test]
+fn test_malformed_config_is_ignored() {
+    let dir = std::env::temp_dir().join(format!("deval-test-config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, text, message) in [
+        ("syntax.toml", "[[rules]\nfilename = ", "syntax.toml:1:"),
+        ("shape.toml", "rules = 3\n", "invalid type"),
+    ] {
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+            .arg("config")
+            .env("DEVAL_CONFIG", &path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).ends_with("No rules\n"));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(message), "{stderr}");
+        assert!(
+            stderr.contains(&format!(
+                "Ignoring the invalid config file {}",
+                path.display()
+            )),
+            "{stderr}"
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,114 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn convert_stdin(input: &str) -> Output {
+    convert_stdin_with(input, &[])
+}
+
+fn convert_stdin_with(input: &str, flags: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .args(["convert-json-schema", "-"])
+        .args(flags)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_convert_from_stdin() {
+    let output = convert_stdin(r#"{"type": "array", "items": {"type": "string"}}"#);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "string[]\n");
+}
+
+const POINT_SCHEMA: &str = r##"{
+    "type": "array",
+    "items": {"$ref": "#/definitions/Point"},
+    "definitions": {
+        "Point": {
+            "type": "object",
+            "properties": {"x": {"type": "number"}},
+            "required": ["x"],
+            "additionalProperties": false
+        }
+    }
+}"##;
+
+const SEGMENT_SCHEMA: &str = r##"{
+    "type": "object",
+    "properties": {
+        "from": {"$ref": "#/definitions/Point"},
+        "color": {"$ref": "#/definitions/Color"}
+    },
+    "required": ["from", "color"],
+    "additionalProperties": false,
+    "definitions": {
+        "Point": {
+            "type": "object",
+            "properties": {"x": {"type": "number"}},
+            "required": ["x"],
+            "additionalProperties": false
+        },
+        "Color": {"enum": ["red", "green"]}
+    }
+}"##;
+
+#[test]
+fn test_convert_names_definitions_by_default() {
+    let output = convert_stdin(SEGMENT_SCHEMA);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let types = stdout.lines().filter(|l| l.starts_with("type ")).count();
+    assert_eq!(types, 2, "{stdout}");
+    assert!(deval_schema::compile(&stdout).is_ok(), "{stdout}");
+}
+
+#[test]
+fn test_convert_inline() {
+    let output = convert_stdin_with(SEGMENT_SCHEMA, &["--inline"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.lines().any(|l| l.starts_with("type ")), "{stdout}");
+    assert!(deval_schema::compile(&stdout).is_ok(), "{stdout}");
+}
+
+#[test]
+fn test_convert_named_compiles() {
+    let output = convert_stdin_with(POINT_SCHEMA, &["--named"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("type Point = "), "{stdout}");
+    assert!(stdout.trim_end().ends_with("Point[]"), "{stdout}");
+    assert!(deval_schema::compile(&stdout).is_ok(), "{stdout}");
+}
+
+#[test]
+fn test_convert_invalid_schema_fails() {
+    let output = convert_stdin("{\n  \"type\": \"string\",\n  oops\n}");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Invalid JSON Schema: "), "{stderr}");
+    assert!(stderr.contains("line 3 column 3"), "{stderr}");
+}
+
+#[test]
+fn test_convert_missing_file_fails() {
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .args(["convert-json-schema", "/nonexistent/schema.json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Failed to read"));
+}
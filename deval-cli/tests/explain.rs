@@ -0,0 +1,40 @@
+use std::{path::PathBuf, process::Command};
+
+#[test]
+fn test_explain_human_schema() {
+    let schema = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/human/human.dvl");
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .args(["explain", "--schema", schema.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{
+    name: string,
+    /// Years since birthday
+    age: number,
+    is_student: bool,
+    address: {
+        street: string,
+        city: string,
+    },
+    hobbies: string[] | string,
+}
+"
+    );
+}
+
+#[test]
+fn test_explain_missing_schema_fails() {
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .args(["explain", "--schema", "/nonexistent/schema.dvl"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("Failed to read /nonexistent/schema.dvl"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,48 @@
+use std::{path::PathBuf, process::Command};
+
+/// A path in the temp directory, unique to this test run so concurrent runs
+/// don't share files.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("deval-test-{}-{name}", std::process::id()))
+}
+
+fn fmt_write(file: &PathBuf) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .args(["fmt", "--write", "--file", file.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    std::fs::read_to_string(file).unwrap()
+}
+
+#[test]
+fn test_fmt_toml_round_trips() {
+    let file = temp_path("round-trip.toml");
+    std::fs::write(
+        &file,
+        "title = 'x'\nowner.name = \"Tom\"\nowner.dob = 1979-05-27T07:32:00-08:00\n\n[db]\nports=[8000,0x1F]\nday = 1979-05-27\n",
+    )
+    .unwrap();
+    let first = fmt_write(&file);
+    let second = fmt_write(&file);
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(
+        first,
+        "title = \"x\"\n\n[owner]\nname = \"Tom\"\ndob = 1979-05-27T07:32:00-08:00\n\n[db]\nports = [8000, 0x1F]\nday = 1979-05-27\n"
+    );
+    assert_eq!(second, first);
+}
+
+#[test]
+fn test_fmt_unsupported_format() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/env/.env");
+    let output = Command::new(env!("CARGO_BIN_EXE_deval-cli"))
+        .args(["fmt", "--file", file.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Formatting env files is not supported\n"
+    );
+}
@@ -1,6 +1,8 @@
 use std::fmt::Debug;
 
-#[derive(Debug, Clone)]
+/// A byte range in a file. The default, an empty range in no file, stands for a
+/// value that isn't in any text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Span {
     pub filename: String,
     /// start offset in bytes
@@ -9,12 +11,70 @@ pub struct Span {
     pub end: usize,
 }
 
+/// A zero based position in a text. `col` is counted in characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl LineCol {
+    /// Converts a byte offset in `source` to a line and column. Offsets past the
+    /// end or inside a character are clamped to the previous character boundary.
+    pub fn from_offset(source: &str, offset: usize) -> Self {
+        let mut offset = offset.min(source.len());
+        while !source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        LineCol {
+            line: before.matches('\n').count(),
+            col: before[line_start..].chars().count(),
+        }
+    }
+}
+
+impl Span {
+    /// Returns the start and end positions of this span in `source`.
+    pub fn line_col(&self, source: &str) -> (LineCol, LineCol) {
+        (
+            LineCol::from_offset(source, self.start),
+            LineCol::from_offset(source, self.end),
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SpanSet(pub Vec<Span>);
 
 impl SpanSet {
-    pub fn primary(&self) -> Span {
-        self.0[0].clone()
+    /// The first span, or `None` for a value that isn't in the text, like a
+    /// default inserted by the schema. Parsed values always have one.
+    pub fn primary(&self) -> Option<Span> {
+        self.0.first().cloned()
+    }
+
+    /// All spans, in the order they were added.
+    pub fn all(&self) -> &[Span] {
+        &self.0
+    }
+
+    /// The spans sorted by filename and position, with overlapping and adjacent
+    /// spans of the same file coalesced.
+    pub fn merged(&self) -> Vec<Span> {
+        let mut spans = self.0.clone();
+        spans.sort_by(|a, b| (&a.filename, a.start).cmp(&(&b.filename, b.start)));
+        let mut result: Vec<Span> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match result.last_mut() {
+                Some(last) if last.filename == span.filename && span.start <= last.end => {
+                    last.end = last.end.max(span.end);
+                }
+                _ => result.push(span),
+            }
+        }
+        result
     }
 }
 
@@ -38,10 +98,35 @@ impl SpannedData {
 pub enum SemanticType {
     String,
     Number,
+    Bool,
+    Null,
+    /// A key of an object
+    Key,
     Variable,
 }
 
-#[derive(Debug, Clone)]
+/// Values which carry a semantic type by themselves, without any annotation of
+/// their own to hold it.
+pub trait ImpliedSemanticType {
+    fn implied_semantic_type(&self) -> Option<SemanticType> {
+        None
+    }
+}
+
+impl ImpliedSemanticType for bool {}
+impl ImpliedSemanticType for Number {}
+impl ImpliedSemanticType for String {}
+
+impl<A> ImpliedSemanticType for AnnotatedData<A> {
+    fn implied_semantic_type(&self) -> Option<SemanticType> {
+        match self {
+            AnnotatedData::Null => Some(SemanticType::Null),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Annotated<T, A = FullAnnotation> {
     pub value: T,
     pub annotation: A,
@@ -52,17 +137,24 @@ pub struct FullAnnotation {
     pub span: SpanSet,
     pub docs: String,
     pub semantic_type: Option<SemanticType>,
+    /// Description of the schema type this value was validated against
+    pub schema_type: Option<String>,
+    /// Whether this is a key that the schema marks as optional or gives a default
+    pub optional: bool,
 }
 
-impl<A, B: From<A>> From<Spanned<A>> for Annotated<B, FullAnnotation> {
+impl<A, B: From<A> + ImpliedSemanticType> From<Spanned<A>> for Annotated<B, FullAnnotation> {
     fn from(spanned: Spanned<A>) -> Self {
+        let value = B::from(spanned.value);
         Annotated {
-            value: spanned.value.into(),
             annotation: FullAnnotation {
                 span: spanned.annotation,
                 docs: String::new(),
-                semantic_type: None,
+                semantic_type: value.implied_semantic_type(),
+                schema_type: None,
+                optional: false,
             },
+            value,
         }
     }
 }
@@ -74,6 +166,19 @@ impl<T> Annotated<T, FullAnnotation> {
     }
 }
 
+impl Annotated<AnnotatedData> {
+    /// Sets the schema type of this value, and of the literal inside it if any.
+    pub fn set_schema_type(&mut self, schema_type: String) {
+        match &mut self.value {
+            AnnotatedData::Bool(x) => x.annotation.schema_type = Some(schema_type.clone()),
+            AnnotatedData::Number(x) => x.annotation.schema_type = Some(schema_type.clone()),
+            AnnotatedData::String(x) => x.annotation.schema_type = Some(schema_type.clone()),
+            AnnotatedData::Null | AnnotatedData::Array(_) | AnnotatedData::Object(_) => (),
+        }
+        self.annotation.schema_type = Some(schema_type);
+    }
+}
+
 impl<A> Annotated<AnnotatedData<A>, A> {
     pub fn discard_annotation(&self) -> Annotated<AnnotatedData<()>, ()> {
         Annotated {
@@ -92,11 +197,103 @@ impl<T: Clone, A> Annotated<T, A> {
     }
 }
 
+/// A step from a value to one of its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A number, along with its text in the source if it was parsed from one.
 #[derive(Debug, Clone)]
+pub struct Number {
+    pub value: f64,
+    /// The number as the author wrote it, like `1e6` or `0x1F`, so serializing
+    /// an unchanged value can keep its representation
+    pub raw: Option<String>,
+}
+
+impl Number {
+    pub fn with_raw(value: f64, raw: &str) -> Self {
+        Number {
+            value,
+            raw: Some(raw.to_owned()),
+        }
+    }
+
+    /// The integer the raw text spells, in decimal or with a `0x`, `0o` or `0b`
+    /// prefix. Unlike `value`, it is exact beyond 2^53.
+    pub fn raw_integer(&self) -> Option<i128> {
+        let raw = self.raw.as_deref()?.replace('_', "");
+        let (negative, digits) = match raw.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, raw.strip_prefix('+').unwrap_or(&raw)),
+        };
+        let (radix, digits) = match digits.get(..2) {
+            Some("0x") => (16, &digits[2..]),
+            Some("0o") => (8, &digits[2..]),
+            Some("0b") => (2, &digits[2..]),
+            _ => (10, digits),
+        };
+        // `from_str_radix` would accept a second sign
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        let value = i128::from_str_radix(digits, radix).ok()?;
+        Some(if negative { -value } else { value })
+    }
+
+    /// The number as an integer of type `T`, if it is one in the range of `T`.
+    /// The raw text is used when it is an integer, so large values are exact.
+    pub fn to_integer<T: TryFrom<i128>>(&self) -> Option<T> {
+        let value = match self.raw_integer() {
+            Some(value) => value,
+            None if self.value.fract() == 0.0 => self.value as i128,
+            None => return None,
+        };
+        T::try_from(value).ok()
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Self {
+        Number { value, raw: None }
+    }
+}
+
+/// Numbers are equal when their values are, however they were written.
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl PartialEq<f64> for Number {
+    fn eq(&self, other: &f64) -> bool {
+        self.value == *other
+    }
+}
+
+/// Writes the raw text of the number if it has one, and the value otherwise.
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.raw {
+            Some(raw) => f.write_str(raw),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+/// A value with an annotation on each node, like its spans in the source.
+///
+/// `==` is derived: it compares annotations too, and objects pair by pair in
+/// order, so `{ a: 1, b: 2 }` differs from `{ b: 2, a: 1 }`. Compare values as
+/// data with [`AnnotatedData::structural_eq`] instead.
+#[derive(Debug, Clone, PartialEq)]
 pub enum AnnotatedData<A = FullAnnotation> {
     Null,
     Bool(Annotated<bool, A>),
-    Number(Annotated<f64, A>),
+    Number(Annotated<Number, A>),
     String(Annotated<String, A>),
     Array(Vec<Annotated<AnnotatedData<A>, A>>),
     Object(Vec<(Annotated<String, A>, Annotated<AnnotatedData<A>, A>)>),
@@ -131,6 +328,69 @@ impl<A> AnnotatedData<A> {
         }
     }
 
+    /// Calls `f` for this value and every value nested inside it, in document
+    /// order, along with the path leading to it from this value.
+    pub fn walk_with_path(&self, f: &mut impl FnMut(&[PathSegment], &AnnotatedData<A>)) {
+        fn go<A>(
+            data: &AnnotatedData<A>,
+            path: &mut Vec<PathSegment>,
+            f: &mut impl FnMut(&[PathSegment], &AnnotatedData<A>),
+        ) {
+            f(path, data);
+            match data {
+                AnnotatedData::Array(items) => {
+                    for (index, item) in items.iter().enumerate() {
+                        path.push(PathSegment::Index(index));
+                        go(&item.value, path, f);
+                        path.pop();
+                    }
+                }
+                AnnotatedData::Object(items) => {
+                    for (key, value) in items {
+                        path.push(PathSegment::Key(key.value.clone()));
+                        go(&value.value, path, f);
+                        path.pop();
+                    }
+                }
+                _ => (),
+            }
+        }
+        go(self, &mut vec![], f);
+    }
+
+    /// Compares the content of two values, ignoring their annotations. Objects are
+    /// equal when they have the same keys with equal values, in any order.
+    pub fn structural_eq<B>(&self, other: &AnnotatedData<B>) -> bool {
+        match (self, other) {
+            (AnnotatedData::Null, AnnotatedData::Null) => true,
+            (AnnotatedData::Bool(a), AnnotatedData::Bool(b)) => a.value == b.value,
+            (AnnotatedData::Number(a), AnnotatedData::Number(b)) => a.value == b.value,
+            (AnnotatedData::String(a), AnnotatedData::String(b)) => a.value == b.value,
+            (AnnotatedData::Array(a), AnnotatedData::Array(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|(a, b)| a.value.structural_eq(&b.value))
+            }
+            // With duplicate keys, each side may have pairs the other lacks even
+            // when the lengths match, so both are checked
+            (AnnotatedData::Object(a), AnnotatedData::Object(b)) => {
+                a.len() == b.len()
+                    && a.iter().all(|(key, value)| {
+                        b.iter().any(|(k, v)| {
+                            k.value == key.value && value.value.structural_eq(&v.value)
+                        })
+                    })
+                    && b.iter().all(|(k, v)| {
+                        a.iter().any(|(key, value)| {
+                            k.value == key.value && value.value.structural_eq(&v.value)
+                        })
+                    })
+            }
+            _ => false,
+        }
+    }
+
     fn discard_annotation(&self) -> AnnotatedData<()> {
         match self {
             AnnotatedData::Null => AnnotatedData::Null,
@@ -160,7 +420,9 @@ impl From<SpannedData> for AnnotatedData<FullAnnotation> {
     fn from(value: SpannedData) -> Self {
         match value {
             SpannedData::Null => AnnotatedData::Null,
-            SpannedData::Bool(spanned) => AnnotatedData::Bool(Annotated::from(spanned)),
+            SpannedData::Bool(spanned) => {
+                AnnotatedData::Bool(Annotated::from(spanned).with_semnatic_type(SemanticType::Bool))
+            }
             SpannedData::Number(spanned) => AnnotatedData::Number(
                 Annotated::from(spanned).with_semnatic_type(SemanticType::Number),
             ),
@@ -175,7 +437,7 @@ impl From<SpannedData> for AnnotatedData<FullAnnotation> {
                     .into_iter()
                     .map(|(key, value)| {
                         (
-                            Annotated::from(key).with_semnatic_type(SemanticType::Variable),
+                            Annotated::from(key).with_semnatic_type(SemanticType::Key),
                             value.into(),
                         )
                     })
@@ -193,4 +455,324 @@ pub struct ParseError {
 
 pub trait Format: Sync + Send {
     fn parse(&self, source: &str, filename: &str) -> Result<Spanned<SpannedData>, Vec<ParseError>>;
+
+    /// Like `parse`, but also returns the comments of the document, which `parse`
+    /// drops, each with its span and text including the comment markers. Formats
+    /// which don't keep comments return none.
+    #[allow(clippy::type_complexity)]
+    fn parse_with_comments(
+        &self,
+        source: &str,
+        filename: &str,
+    ) -> Result<(Spanned<SpannedData>, Vec<(Span, String)>), Vec<ParseError>> {
+        Ok((self.parse(source, filename)?, vec![]))
+    }
+
+    /// Parses a file holding several documents, like JSON Lines, into each of
+    /// them, so a syntax error in one doesn't hide the others, along with the
+    /// comments of the file like `parse_with_comments`. Formats with one
+    /// document per file return just that.
+    #[allow(clippy::type_complexity)]
+    fn parse_many(
+        &self,
+        source: &str,
+        filename: &str,
+    ) -> (
+        Vec<Result<Spanned<SpannedData>, Vec<ParseError>>>,
+        Vec<(Span, String)>,
+    ) {
+        match self.parse_with_comments(source, filename) {
+            Ok((document, comments)) => (vec![Ok(document)], comments),
+            Err(errors) => (vec![Err(errors)], vec![]),
+        }
+    }
+
+    /// The name of this format, like `yaml`, for choosing it by name.
+    fn name(&self) -> &'static str;
+
+    /// The file extensions of this format, without the dot, like `["yml", "yaml"]`.
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
+
+    /// `value` written as a value of this format on one line, for inserting it
+    /// into a document. `None` if the format can't write it, like a null in TOML.
+    fn render_value(&self, _value: &AnnotatedData<()>) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, end: usize) -> Span {
+        Span {
+            filename: "test".to_owned(),
+            start,
+            end,
+        }
+    }
+
+    fn file_span(filename: &str, start: usize, end: usize) -> Span {
+        Span {
+            filename: filename.to_owned(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_merged_overlapping_spans() {
+        let spans = SpanSet(vec![span(10, 20), span(0, 5), span(15, 30), span(12, 14)]);
+        assert_eq!(spans.merged(), [span(0, 5), span(10, 30)]);
+        assert_eq!(spans.all().len(), 4);
+    }
+
+    #[test]
+    fn test_merged_adjacent_spans() {
+        let spans = SpanSet(vec![span(5, 10), span(0, 5), span(11, 12)]);
+        assert_eq!(spans.merged(), [span(0, 10), span(11, 12)]);
+    }
+
+    #[test]
+    fn test_merged_spans_of_different_files() {
+        let spans = SpanSet(vec![
+            file_span("b.toml", 0, 10),
+            file_span("a.toml", 5, 8),
+            file_span("a.toml", 0, 6),
+            file_span("b.toml", 2, 4),
+        ]);
+        assert_eq!(
+            spans.merged(),
+            [file_span("a.toml", 0, 8), file_span("b.toml", 0, 10)]
+        );
+    }
+
+    #[test]
+    fn test_line_col_ascii() {
+        let source = "a = 1\nbc = 2\n";
+        let (start, end) = span(6, 8).line_col(source);
+        assert_eq!(start, LineCol { line: 1, col: 0 });
+        assert_eq!(end, LineCol { line: 1, col: 2 });
+    }
+
+    #[test]
+    fn test_line_col_multi_byte() {
+        // `é` is two bytes and `😀` is four bytes, but each is a single column
+        let source = "x = \"é😀\"\ny = \"ü\"";
+        let value_start = source.find("\"é").unwrap();
+        let (start, end) = span(value_start, value_start + 8).line_col(source);
+        assert_eq!(start, LineCol { line: 0, col: 4 });
+        assert_eq!(end, LineCol { line: 0, col: 8 });
+
+        let second = source.rfind('ü').unwrap();
+        let (start, end) = span(second, second + 2).line_col(source);
+        assert_eq!(start, LineCol { line: 1, col: 5 });
+        assert_eq!(end, LineCol { line: 1, col: 6 });
+    }
+
+    #[test]
+    fn test_line_col_clamps_offsets() {
+        let source = "é";
+        assert_eq!(LineCol::from_offset(source, 1), LineCol { line: 0, col: 0 });
+        assert_eq!(
+            LineCol::from_offset(source, 10),
+            LineCol { line: 0, col: 1 }
+        );
+    }
+
+    #[test]
+    fn test_semantic_types() {
+        let spans = || SpanSet(vec![span(0, 0)]);
+        let data: Spanned<SpannedData> = Spanned {
+            value: SpannedData::Object(vec![
+                (
+                    Spanned {
+                        value: "a".to_owned(),
+                        annotation: spans(),
+                    },
+                    Spanned {
+                        value: SpannedData::Null,
+                        annotation: spans(),
+                    },
+                ),
+                (
+                    Spanned {
+                        value: "b".to_owned(),
+                        annotation: spans(),
+                    },
+                    Spanned {
+                        value: SpannedData::Bool(Spanned {
+                            value: true,
+                            annotation: spans(),
+                        }),
+                        annotation: spans(),
+                    },
+                ),
+            ]),
+            annotation: spans(),
+        };
+        let annotated: Annotated<AnnotatedData> = data.into();
+        let mut types = vec![];
+        annotated
+            .value
+            .walk(&mut |a: FullAnnotation| types.extend(a.semantic_type));
+        assert!(matches!(
+            types[..],
+            [
+                SemanticType::Key,
+                SemanticType::Null,
+                SemanticType::Key,
+                SemanticType::Bool,
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_walk_with_path() {
+        fn leaf(value: f64) -> Annotated<AnnotatedData<()>, ()> {
+            Annotated {
+                value: AnnotatedData::Number(Annotated {
+                    value: value.into(),
+                    annotation: (),
+                }),
+                annotation: (),
+            }
+        }
+        fn key(key: &str) -> Annotated<String, ()> {
+            Annotated {
+                value: key.to_owned(),
+                annotation: (),
+            }
+        }
+        // { a: 1, b: { c: [2, 3] } }
+        let data: AnnotatedData<()> = AnnotatedData::Object(vec![
+            (key("a"), leaf(1.)),
+            (
+                key("b"),
+                Annotated {
+                    value: AnnotatedData::Object(vec![(
+                        key("c"),
+                        Annotated {
+                            value: AnnotatedData::Array(vec![leaf(2.), leaf(3.)]),
+                            annotation: (),
+                        },
+                    )]),
+                    annotation: (),
+                },
+            ),
+        ]);
+        let mut paths = vec![];
+        data.walk_with_path(&mut |path, _| paths.push(path.to_vec()));
+        let key = |k: &str| PathSegment::Key(k.to_owned());
+        assert_eq!(
+            paths,
+            [
+                vec![],
+                vec![key("a")],
+                vec![key("b")],
+                vec![key("b"), key("c")],
+                vec![key("b"), key("c"), PathSegment::Index(0)],
+                vec![key("b"), key("c"), PathSegment::Index(1)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_structural_eq() {
+        fn data(value: AnnotatedData<()>) -> Annotated<AnnotatedData<()>, ()> {
+            Annotated {
+                value,
+                annotation: (),
+            }
+        }
+        fn key(key: &str) -> Annotated<String, ()> {
+            Annotated {
+                value: key.to_owned(),
+                annotation: (),
+            }
+        }
+        fn number(value: f64) -> Annotated<AnnotatedData<()>, ()> {
+            data(AnnotatedData::Number(Annotated {
+                value: value.into(),
+                annotation: (),
+            }))
+        }
+        // { a: [1, { b: null }], c: 2 }, with the keys of the outer object in `order`
+        let nested = |order: [&str; 2], inner: AnnotatedData<()>| {
+            let mut pairs = vec![
+                (
+                    key("a"),
+                    data(AnnotatedData::Array(vec![
+                        number(1.),
+                        data(AnnotatedData::Object(vec![(key("b"), data(inner))])),
+                    ])),
+                ),
+                (key("c"), number(2.)),
+            ];
+            if order[0] == "c" {
+                pairs.reverse();
+            }
+            AnnotatedData::Object(pairs)
+        };
+
+        let a = nested(["a", "c"], AnnotatedData::Null);
+        assert!(a.structural_eq(&nested(["a", "c"], AnnotatedData::Null)));
+        assert!(a.structural_eq(&nested(["c", "a"], AnnotatedData::Null)));
+        // Unlike the derived `==`, which is sensitive to key order
+        assert_ne!(a, nested(["c", "a"], AnnotatedData::Null));
+        assert!(!a.structural_eq(&nested(["a", "c"], number(0.).value)));
+        assert!(!a.structural_eq(&AnnotatedData::Object(vec![(key("c"), number(2.))])));
+        // Duplicate keys don't hide a pair missing from either side
+        let twice_c = AnnotatedData::Object(vec![(key("c"), number(2.)), (key("c"), number(2.))]);
+        let a_and_c = AnnotatedData::Object(vec![(key("a"), number(1.)), (key("c"), number(2.))]);
+        assert!(!twice_c.structural_eq(&a_and_c));
+        assert!(!a_and_c.structural_eq(&twice_c));
+        assert!(
+            !number(1.)
+                .value
+                .structural_eq(&AnnotatedData::Array(vec![number(1.)]))
+        );
+
+        // Annotations are ignored
+        let annotated: Annotated<AnnotatedData> = Spanned {
+            value: SpannedData::Number(Spanned {
+                value: Number::with_raw(1., "1.0"),
+                annotation: SpanSet(vec![span(3, 4)]),
+            }),
+            annotation: SpanSet(vec![span(3, 4)]),
+        }
+        .into();
+        assert!(annotated.value.structural_eq(&number(1.).value));
+    }
+
+    #[test]
+    fn test_number_integers() {
+        let exact = Number::with_raw(9007199254740993., "9007199254740993");
+        assert_eq!(exact.to_integer::<u64>(), Some(9007199254740993));
+        assert_eq!(Number::with_raw(31., "0x1F").to_integer::<u8>(), Some(31));
+        assert_eq!(
+            Number::with_raw(-1000., "-1_000").to_integer::<i16>(),
+            Some(-1000)
+        );
+        assert_eq!(Number::with_raw(-1., "-1").to_integer::<u64>(), None);
+        assert_eq!(Number::with_raw(300., "300").to_integer::<u8>(), None);
+        assert_eq!(
+            Number::with_raw(1000., "1e3").to_integer::<u32>(),
+            Some(1000)
+        );
+        assert_eq!(Number::from(2.5).to_integer::<i64>(), None);
+        assert_eq!(Number::from(f64::NAN).to_integer::<i64>(), None);
+        assert_eq!(Number::with_raw(1., "0x-1").raw_integer(), None);
+    }
+
+    #[test]
+    fn test_number_display() {
+        assert_eq!(Number::with_raw(1e6, "1e6").to_string(), "1e6");
+        assert_eq!(Number::with_raw(31., "0x1F").to_string(), "0x1F");
+        assert_eq!(Number::from(1e6).to_string(), "1000000");
+        assert_eq!(Number::from(2.5).to_string(), "2.5");
+        assert_eq!(Number::with_raw(31., "0x1F"), Number::from(31.));
+    }
 }
@@ -0,0 +1,323 @@
+use deval_data_model::{AnnotatedData, Format, ParseError, Span, SpanSet, Spanned, SpannedData};
+
+/// Dotenv files, made of `KEY=value` lines. Since the format has no types, all
+/// values are strings.
+pub struct EnvFile;
+
+impl Format for EnvFile {
+    fn parse(&self, source: &str, filename: &str) -> Result<Spanned<SpannedData>, Vec<ParseError>> {
+        Ok(self.parse_with_comments(source, filename)?.0)
+    }
+
+    fn parse_with_comments(
+        &self,
+        source: &str,
+        filename: &str,
+    ) -> Result<(Spanned<SpannedData>, Vec<(Span, String)>), Vec<ParseError>> {
+        let mut errors = Vec::new();
+        let mut comments = Vec::new();
+        let mut pairs: Vec<(Spanned<String>, Spanned<SpannedData>)> = Vec::new();
+
+        let mut line_start = 0;
+        for line in source.split('\n') {
+            let offset = line_start;
+            line_start += line.len() + 1;
+            let line = line.strip_suffix('\r').unwrap_or(line);
+
+            if let Some((key, value)) =
+                parse_line(line, offset, filename, &mut errors, &mut comments)
+            {
+                if pairs.iter().any(|(k, _)| k.value == key.value) {
+                    errors.push(ParseError {
+                        message: format!("Duplicate key '{}'", key.value),
+                        span: key.annotation.primary().unwrap(),
+                    });
+                } else {
+                    pairs.push((key, value));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let data = Spanned {
+            value: SpannedData::Object(pairs),
+            annotation: make_span_vec(0, source.len(), filename),
+        };
+        Ok((data, comments))
+    }
+
+    fn render_value(&self, value: &AnnotatedData<()>) -> Option<String> {
+        // Values are all strings, written as they are
+        match value {
+            AnnotatedData::String(s) if !s.value.contains('\n') => Some(s.value.clone()),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["env"]
+    }
+}
+
+/// Parses a single line starting at byte `offset` of the source, adding its
+/// comment to `comments`. Returns `None` for blank lines, comments and lines
+/// with errors.
+fn parse_line(
+    line: &str,
+    offset: usize,
+    filename: &str,
+    errors: &mut Vec<ParseError>,
+    comments: &mut Vec<(Span, String)>,
+) -> Option<(Spanned<String>, Spanned<SpannedData>)> {
+    // The comment running from `start` to the end of the line
+    let mut comment = |start: usize| {
+        let text = line[start..].trim_end();
+        let span = make_span(offset + start, offset + start + text.len(), filename);
+        comments.push((span, text.to_owned()));
+    };
+    let mut pos = skip_whitespace(line, 0);
+    let rest = &line[pos..];
+    if rest.starts_with('#') {
+        comment(pos);
+    }
+    if rest.is_empty() || rest.starts_with('#') {
+        return None;
+    }
+    if rest
+        .strip_prefix("export")
+        .is_some_and(|r| r.starts_with([' ', '\t']))
+    {
+        pos = skip_whitespace(line, pos + "export".len());
+    }
+
+    let Some(eq) = line[pos..].find('=').map(|i| pos + i) else {
+        errors.push(ParseError {
+            message: "Expected `=` after the key".to_string(),
+            span: make_span(offset + pos, offset + line.len(), filename),
+        });
+        return None;
+    };
+    let key = line[pos..eq].trim_end();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        errors.push(ParseError {
+            message: format!("Invalid key '{key}'"),
+            span: make_span(offset + pos, offset + eq, filename),
+        });
+        return None;
+    }
+    let key = Spanned {
+        value: key.to_string(),
+        annotation: make_span_vec(offset + pos, offset + pos + key.len(), filename),
+    };
+
+    let value_start = skip_whitespace(line, eq + 1);
+    let (value, value_end) = match line[value_start..].chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let Some((value, end)) = parse_quoted(line, value_start, quote) else {
+                errors.push(ParseError {
+                    message: "Unterminated quoted value".to_string(),
+                    span: make_span(offset + value_start, offset + line.len(), filename),
+                });
+                return None;
+            };
+            let trailing = line[end..].trim_start();
+            if !trailing.is_empty() && !trailing.starts_with('#') {
+                errors.push(ParseError {
+                    message: "Unexpected characters after quoted value".to_string(),
+                    span: make_span(offset + end, offset + line.len(), filename),
+                });
+                return None;
+            }
+            if !trailing.is_empty() {
+                comment(line.len() - trailing.len());
+            }
+            (value, end)
+        }
+        _ => {
+            // An unquoted value ends at a comment that is preceded by whitespace
+            let end = line[value_start..]
+                .char_indices()
+                .find(|&(i, c)| {
+                    c == '#' && (i == 0 || line[..value_start + i].ends_with(char::is_whitespace))
+                })
+                .map_or(line.len(), |(i, _)| value_start + i);
+            if end < line.len() {
+                comment(end);
+            }
+            let value = line[value_start..end].trim_end();
+            (value.to_string(), value_start + value.len())
+        }
+    };
+
+    let annotation = make_span_vec(offset + value_start, offset + value_end, filename);
+    Some((
+        key,
+        Spanned {
+            value: SpannedData::String(Spanned {
+                value,
+                annotation: annotation.clone(),
+            }),
+            annotation,
+        },
+    ))
+}
+
+/// Parses a quoted value starting at `start`, returning its content and the byte
+/// position after the closing quote. Escapes are only processed in double quoted
+/// values.
+fn parse_quoted(line: &str, start: usize, quote: char) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut chars = line[start + 1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Some((value, start + 1 + i + 1)),
+            '\\' if quote == '"' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                c @ ('"' | '\\' | '$') => value.push(c),
+                c => {
+                    value.push('\\');
+                    value.push(c);
+                }
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+fn skip_whitespace(line: &str, pos: usize) -> usize {
+    pos + (line[pos..].len() - line[pos..].trim_start().len())
+}
+
+fn make_span(start: usize, end: usize, filename: &str) -> Span {
+    Span {
+        filename: filename.to_string(),
+        start,
+        end,
+    }
+}
+
+fn make_span_vec(start: usize, end: usize, filename: &str) -> SpanSet {
+    SpanSet(vec![make_span(start, end, filename)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_pairs(source: &str) -> Vec<(String, String, &str)> {
+        let parsed = EnvFile
+            .parse(source, ".env")
+            .expect("Failed to parse env file");
+        match parsed.value {
+            SpannedData::Object(pairs) => pairs
+                .into_iter()
+                .map(|(k, v)| {
+                    let span = v.annotation.primary().unwrap();
+                    match v.value {
+                        SpannedData::String(s) => (k.value, s.value, &source[span.start..span.end]),
+                        _ => panic!("Expected string value"),
+                    }
+                })
+                .collect(),
+            _ => panic!("Expected object"),
+        }
+    }
+
+    #[test]
+    fn test_parse_simple_pairs() {
+        let source = "NAME=deval\nPORT = 8080\n";
+        let pairs = parse_pairs(source);
+        assert_eq!(
+            pairs,
+            [
+                ("NAME".to_string(), "deval".to_string(), "deval"),
+                ("PORT".to_string(), "8080".to_string(), "8080"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quoted_values_containing_equals() {
+        let source = "A=\"x=1\"\nB='y = 2' # comment\nC=\"line\\nbreak\"";
+        let pairs = parse_pairs(source);
+        assert_eq!(
+            pairs,
+            [
+                ("A".to_string(), "x=1".to_string(), "\"x=1\""),
+                ("B".to_string(), "y = 2".to_string(), "'y = 2'"),
+                (
+                    "C".to_string(),
+                    "line\nbreak".to_string(),
+                    "\"line\\nbreak\""
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let source =
+            "# a comment\n\n  # indented comment\r\nKEY=value # trailing\nURL=http://a#b\n";
+        let pairs = parse_pairs(source);
+        assert_eq!(
+            pairs,
+            [
+                ("KEY".to_string(), "value".to_string(), "value"),
+                ("URL".to_string(), "http://a#b".to_string(), "http://a#b"),
+            ]
+        );
+
+        let source = "# top
+A=1 # one
+B='2' #two
+";
+        let (_, comments) = EnvFile.parse_with_comments(source, ".env").unwrap();
+        let comments: Vec<_> = comments
+            .iter()
+            .map(|(span, text)| (&source[span.start..span.end], text.as_str()))
+            .collect();
+        assert_eq!(
+            comments,
+            [("# top", "# top"), ("# one", "# one"), ("#two", "#two")]
+        );
+    }
+
+    #[test]
+    fn test_export_prefix() {
+        let source = "export KEY=value\nexported=1";
+        let parsed = EnvFile.parse(source, ".env").unwrap();
+        let SpannedData::Object(pairs) = parsed.value else {
+            panic!("Expected object");
+        };
+        assert_eq!(pairs[0].0.value, "KEY");
+        let span = pairs[0].0.annotation.primary().unwrap();
+        assert_eq!((span.start, span.end), (7, 10));
+        assert_eq!(pairs[1].0.value, "exported");
+    }
+
+    #[test]
+    fn test_errors() {
+        let errors = EnvFile
+            .parse("KEY\nA=\"open\nA=1\nA=2", ".env")
+            .unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Expected `=` after the key",
+                "Unterminated quoted value",
+                "Duplicate key 'A'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use deval_data_model::{AnnotatedData, Format, ParseError, Span, SpanSet, Spanned, SpannedData};
+
+type Pairs = Vec<(Spanned<String>, Spanned<SpannedData>)>;
+
+/// INI files, made of `[section]` headers and `key = value` lines. Sections
+/// become objects, and since the format has no types, all values are strings.
+pub struct Ini;
+
+impl Format for Ini {
+    fn parse(&self, source: &str, filename: &str) -> Result<Spanned<SpannedData>, Vec<ParseError>> {
+        Ok(self.parse_with_comments(source, filename)?.0)
+    }
+
+    /// Comments take whole lines, so `key = value ; text` has no comment.
+    fn parse_with_comments(
+        &self,
+        source: &str,
+        filename: &str,
+    ) -> Result<(Spanned<SpannedData>, Vec<(Span, String)>), Vec<ParseError>> {
+        let mut errors = Vec::new();
+        let mut comments = Vec::new();
+        let mut root: Pairs = Vec::new();
+        // Index in `root` of the section the following keys belong to
+        let mut current_section: Option<usize> = None;
+
+        let mut line_start = 0;
+        for line in source.split('\n') {
+            let offset = line_start;
+            line_start += line.len() + 1;
+            let line = line.strip_suffix('\r').unwrap_or(line);
+
+            let start = line.len() - line.trim_start().len();
+            let rest = line.trim();
+            if rest.starts_with([';', '#']) {
+                let start = offset + start;
+                comments.push((
+                    make_span(start, start + rest.len(), filename),
+                    rest.to_owned(),
+                ));
+            }
+            if rest.is_empty() || rest.starts_with([';', '#']) {
+                continue;
+            }
+
+            if let Some(header) = rest.strip_prefix('[') {
+                let Some(name) = header.strip_suffix(']') else {
+                    errors.push(ParseError {
+                        message: "Expected `]` after the section name".to_string(),
+                        span: make_span(offset + start, offset + line.len(), filename),
+                    });
+                    current_section = None;
+                    continue;
+                };
+                let name_start = offset + start + 1 + (name.len() - name.trim_start().len());
+                let name = name.trim();
+                let header = make_span(offset + start, offset + start + rest.len(), filename);
+                current_section = open_section(
+                    &mut root,
+                    Spanned {
+                        value: name.to_string(),
+                        annotation: make_span_vec(name_start, name_start + name.len(), filename),
+                    },
+                    header,
+                    &mut errors,
+                );
+                continue;
+            }
+
+            let Some((key, value)) = parse_pair(line, offset, filename, &mut errors) else {
+                continue;
+            };
+            let pairs = match current_section {
+                Some(index) => match &mut root[index].1.value {
+                    SpannedData::Object(pairs) => pairs,
+                    _ => unreachable!("sections are objects"),
+                },
+                None => &mut root,
+            };
+            if pairs.iter().any(|(k, _)| k.value == key.value) {
+                errors.push(ParseError {
+                    message: format!("Duplicate key '{}'", key.value),
+                    span: key.annotation.primary().unwrap(),
+                });
+            } else {
+                pairs.push((key, value));
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let data = Spanned {
+            value: SpannedData::Object(root),
+            annotation: make_span_vec(0, source.len(), filename),
+        };
+        Ok((data, comments))
+    }
+
+    fn render_value(&self, value: &AnnotatedData<()>) -> Option<String> {
+        // Values are all strings, written as they are
+        match value {
+            AnnotatedData::String(s) if !s.value.contains('\n') => Some(s.value.clone()),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "ini"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ini"]
+    }
+}
+
+/// Returns the index of the section named `name` in `root`, adding it if this is
+/// its first header. A repeated header adds its keys to the same section, like
+/// tables in TOML.
+fn open_section(
+    root: &mut Pairs,
+    name: Spanned<String>,
+    header: Span,
+    errors: &mut Vec<ParseError>,
+) -> Option<usize> {
+    match root.iter().position(|(k, _)| k.value == name.value) {
+        Some(index) => {
+            let (key, value) = &mut root[index];
+            if !matches!(value.value, SpannedData::Object(_)) {
+                errors.push(ParseError {
+                    message: format!("Section '{}' conflicts with a key", name.value),
+                    span: name.annotation.primary().unwrap(),
+                });
+                return None;
+            }
+            key.annotation.0.extend(name.annotation.0);
+            value.annotation.0.push(header);
+            Some(index)
+        }
+        None => {
+            root.push((
+                name,
+                Spanned {
+                    value: SpannedData::Object(vec![]),
+                    annotation: SpanSet(vec![header]),
+                },
+            ));
+            Some(root.len() - 1)
+        }
+    }
+}
+
+/// Parses a `key = value` (or `key: value`) line starting at byte `offset` of
+/// the source.
+fn parse_pair(
+    line: &str,
+    offset: usize,
+    filename: &str,
+    errors: &mut Vec<ParseError>,
+) -> Option<(Spanned<String>, Spanned<SpannedData>)> {
+    let key_start = line.len() - line.trim_start().len();
+    let Some(separator) = line.find(['=', ':']) else {
+        errors.push(ParseError {
+            message: "Expected `=` after the key".to_string(),
+            span: make_span(offset + key_start, offset + line.len(), filename),
+        });
+        return None;
+    };
+    let key = line[key_start..separator].trim_end();
+    if key.is_empty() {
+        errors.push(ParseError {
+            message: "Expected a key before `=`".to_string(),
+            span: make_span(offset + separator, offset + separator + 1, filename),
+        });
+        return None;
+    }
+    let key = Spanned {
+        value: key.to_string(),
+        annotation: make_span_vec(offset + key_start, offset + key_start + key.len(), filename),
+    };
+
+    let value = line[separator + 1..].trim_start();
+    let value_start = line.len() - value.len();
+    let value = value.trim_end();
+    let annotation = make_span_vec(
+        offset + value_start,
+        offset + value_start + value.len(),
+        filename,
+    );
+    Some((
+        key,
+        Spanned {
+            value: SpannedData::String(Spanned {
+                value: value.to_string(),
+                annotation: annotation.clone(),
+            }),
+            annotation,
+        },
+    ))
+}
+
+fn make_span(start: usize, end: usize, filename: &str) -> Span {
+    Span {
+        filename: filename.to_string(),
+        start,
+        end,
+    }
+}
+
+fn make_span_vec(start: usize, end: usize, filename: &str) -> SpanSet {
+    SpanSet(vec![make_span(start, end, filename)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_object(source: &str) -> Pairs {
+        match Ini
+            .parse(source, "test.ini")
+            .expect("Failed to parse ini file")
+            .value
+        {
+            SpannedData::Object(pairs) => pairs,
+            _ => panic!("Expected object"),
+        }
+    }
+
+    /// The keys of an object, with the value and source text of string values.
+    fn strings<'a>(
+        pairs: &[(Spanned<String>, Spanned<SpannedData>)],
+        source: &'a str,
+    ) -> Vec<(String, String, &'a str)> {
+        pairs
+            .iter()
+            .map(|(k, v)| {
+                let span = v.annotation.primary().unwrap();
+                match &v.value {
+                    SpannedData::String(s) => (
+                        k.value.clone(),
+                        s.value.clone(),
+                        &source[span.start..span.end],
+                    ),
+                    _ => panic!("Expected string value"),
+                }
+            })
+            .collect()
+    }
+
+    fn section<'a>(pairs: &'a Pairs, name: &str) -> &'a Pairs {
+        match pairs
+            .iter()
+            .find(|(k, _)| k.value == name)
+            .map(|(_, v)| &v.value)
+        {
+            Some(SpannedData::Object(pairs)) => pairs,
+            _ => panic!("Expected section {name}"),
+        }
+    }
+
+    #[test]
+    fn test_two_sections() {
+        let source = "; settings\n[server]\nhost = localhost\nport=8080\n\n# db\n[ database ]\nurl: postgres://x\n";
+        let root = parse_object(source);
+        assert_eq!(root.len(), 2);
+        assert_eq!(
+            strings(section(&root, "server"), source),
+            [
+                ("host".to_string(), "localhost".to_string(), "localhost"),
+                ("port".to_string(), "8080".to_string(), "8080"),
+            ]
+        );
+        assert_eq!(
+            strings(section(&root, "database"), source),
+            [(
+                "url".to_string(),
+                "postgres://x".to_string(),
+                "postgres://x"
+            )]
+        );
+        let span = root[1].0.annotation.primary().unwrap();
+        assert_eq!(&source[span.start..span.end], "database");
+    }
+
+    #[test]
+    fn test_root_key() {
+        let source = "name = app\n[server]\nport = 1";
+        let root = parse_object(source);
+        assert_eq!(
+            strings(&root[..1], source),
+            [("name".to_string(), "app".to_string(), "app")]
+        );
+        assert_eq!(section(&root, "server").len(), 1);
+    }
+
+    #[test]
+    fn test_duplicated_section() {
+        let source = "[a]\nx = 1\n[b]\ny = 2\n[a]\nz = 3\n";
+        let root = parse_object(source);
+        assert_eq!(root.len(), 2);
+        let keys: Vec<_> = section(&root, "a").iter().map(|(k, _)| &*k.value).collect();
+        assert_eq!(keys, ["x", "z"]);
+        assert_eq!(root[0].0.annotation.all().len(), 2);
+        assert_eq!(root[0].1.annotation.all().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_with_comments() {
+        let source = "; top\n[a]\n  # inside\nx = 1 ; not a comment\n";
+        let (data, comments) = Ini.parse_with_comments(source, "test.ini").unwrap();
+        let comments: Vec<_> = comments
+            .iter()
+            .map(|(span, text)| (&source[span.start..span.end], text.as_str()))
+            .collect();
+        assert_eq!(comments, [("; top", "; top"), ("# inside", "# inside")]);
+        let SpannedData::Object(root) = data.value else {
+            panic!("Expected object");
+        };
+        assert!(matches!(
+            &section(&root, "a")[0].1.value,
+            SpannedData::String(s) if s.value == "1 ; not a comment"
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        let errors = Ini
+            .parse("[a\nkey\n[b]\nx = 1\n[b]\nx = 2\nx = 3\n[x]", "test.ini")
+            .unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Expected `]` after the section name",
+                "Expected `=` after the key",
+                "Duplicate key 'x'",
+                "Duplicate key 'x'",
+            ]
+        );
+    }
+}
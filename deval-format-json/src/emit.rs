@@ -0,0 +1,133 @@
+//! Writing data back as JSON text.
+
+use deval_data_model::AnnotatedData;
+
+/// Writes `data` as pretty printed JSON, with each array item and object key on
+/// its own line. Numbers are written as they appeared in the source.
+pub fn emit<A>(data: &AnnotatedData<A>) -> String {
+    let mut out = String::new();
+    emit_value(data, 0, &mut out);
+    out.push('\n');
+    out
+}
+
+fn emit_value<A>(data: &AnnotatedData<A>, depth: usize, out: &mut String) {
+    match data {
+        AnnotatedData::Null => out.push_str("null"),
+        AnnotatedData::Bool(b) => out.push_str(if b.value { "true" } else { "false" }),
+        AnnotatedData::Number(n) => out.push_str(&n.value.to_string()),
+        AnnotatedData::String(s) => emit_string(&s.value, out),
+        AnnotatedData::Array(items) if items.is_empty() => out.push_str("[]"),
+        AnnotatedData::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(depth + 1, out);
+                emit_value(&item.value, depth + 1, out);
+            }
+            newline(depth, out);
+            out.push(']');
+        }
+        AnnotatedData::Object(pairs) if pairs.is_empty() => out.push_str("{}"),
+        AnnotatedData::Object(pairs) => {
+            out.push('{');
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(depth + 1, out);
+                emit_string(&key.value, out);
+                out.push_str(": ");
+                emit_value(&value.value, depth + 1, out);
+            }
+            newline(depth, out);
+            out.push('}');
+        }
+    }
+}
+
+/// Writes `data` as JSON on a single line, like `{"a": [1, 2]}`.
+pub(crate) fn emit_inline<A>(data: &AnnotatedData<A>, out: &mut String) {
+    match data {
+        AnnotatedData::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                emit_inline(&item.value, out);
+            }
+            out.push(']');
+        }
+        AnnotatedData::Object(pairs) => {
+            out.push('{');
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                emit_string(&key.value, out);
+                out.push_str(": ");
+                emit_inline(&value.value, out);
+            }
+            out.push('}');
+        }
+        scalar => emit_value(scalar, 0, out),
+    }
+}
+
+fn newline(depth: usize, out: &mut String) {
+    out.push('\n');
+    out.push_str(&"  ".repeat(depth));
+}
+
+/// Writes a quoted JSON string, escaping quotes, backslashes and control
+/// characters.
+fn emit_string(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Json;
+    use deval_data_model::Format;
+
+    fn format(source: &str) -> String {
+        emit(&Json.parse(source, "test.json").unwrap().value)
+    }
+
+    #[test]
+    fn test_emit() {
+        let source = r#"{"name":"a \"b\"\n","tags":[ 1.50,true,null ],"empty":{},"none":[]}"#;
+        let formatted = format(source);
+        assert_eq!(
+            formatted,
+            r#"{
+  "name": "a \"b\"\n",
+  "tags": [
+    1.50,
+    true,
+    null
+  ],
+  "empty": {},
+  "none": []
+}
+"#
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+}
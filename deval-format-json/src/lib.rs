@@ -1,6 +1,13 @@
-use deval_data_model::{Format, ParseError, Span, SpanSet, Spanned, SpannedData};
+use deval_data_model::{
+    AnnotatedData, Format, Number, ParseError, Span, SpanSet, Spanned, SpannedData,
+};
+use deval_tree_sitter::{make_span, make_span_vec, syntax_errors};
 use tree_sitter::{Node, Parser};
 
+mod emit;
+
+pub use emit::emit;
+
 pub struct Json;
 
 impl Format for Json {
@@ -13,6 +20,10 @@ impl Format for Json {
         let root_node = tree.root_node();
 
         let mut errors = Vec::new();
+        if root_node.has_error() {
+            syntax_errors(&root_node, filename, &mut errors);
+            return Err(errors);
+        }
         let result = parse_value(&root_node, source, filename, &mut errors);
 
         let result = result.map(|x| Spanned {
@@ -26,6 +37,123 @@ impl Format for Json {
             result.ok_or_else(|| vec![])
         }
     }
+
+    fn render_value(&self, value: &AnnotatedData<()>) -> Option<String> {
+        let mut out = String::new();
+        emit::emit_inline(value, &mut out);
+        Some(out)
+    }
+
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+}
+
+/// JSON Lines, with one JSON document on each non-blank line.
+pub struct JsonLines;
+
+impl Format for JsonLines {
+    /// Parses the documents as a single array, for tools expecting one document.
+    /// Use `parse_many` to check each document on its own.
+    fn parse(&self, source: &str, filename: &str) -> Result<Spanned<SpannedData>, Vec<ParseError>> {
+        let mut documents = vec![];
+        let mut errors = vec![];
+        for document in self.parse_many(source, filename).0 {
+            match document {
+                Ok(document) => documents.push(document),
+                Err(document_errors) => errors.extend(document_errors),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Spanned {
+            value: SpannedData::Array(documents),
+            annotation: SpanSet(vec![Span {
+                filename: filename.to_owned(),
+                start: 0,
+                end: source.len(),
+            }]),
+        })
+    }
+
+    fn parse_many(
+        &self,
+        source: &str,
+        filename: &str,
+    ) -> (
+        Vec<Result<Spanned<SpannedData>, Vec<ParseError>>>,
+        Vec<(Span, String)>,
+    ) {
+        let mut documents = vec![];
+        let mut line_start = 0;
+        for line in source.split_inclusive('\n') {
+            if !line.trim().is_empty() {
+                // Each line is parsed on its own, so spans are moved to its offset
+                documents.push(match Json.parse(line, filename) {
+                    Ok(mut document) => {
+                        shift_spans(&mut document, line_start);
+                        Ok(document)
+                    }
+                    Err(errors) => Err(errors
+                        .into_iter()
+                        .map(|mut e| {
+                            e.span.start += line_start;
+                            e.span.end += line_start;
+                            e
+                        })
+                        .collect()),
+                });
+            }
+            line_start += line.len();
+        }
+        (documents, vec![])
+    }
+
+    fn render_value(&self, value: &AnnotatedData<()>) -> Option<String> {
+        let mut out = String::new();
+        emit::emit_inline(value, &mut out);
+        Some(out)
+    }
+
+    fn name(&self) -> &'static str {
+        "json-lines"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["jsonl", "ndjson"]
+    }
+}
+
+fn shift_spans(data: &mut Spanned<SpannedData>, offset: usize) {
+    fn shift(spans: &mut SpanSet, offset: usize) {
+        for span in &mut spans.0 {
+            span.start += offset;
+            span.end += offset;
+        }
+    }
+    shift(&mut data.annotation, offset);
+    match &mut data.value {
+        SpannedData::Null => (),
+        SpannedData::Bool(b) => shift(&mut b.annotation, offset),
+        SpannedData::Number(n) => shift(&mut n.annotation, offset),
+        SpannedData::String(s) => shift(&mut s.annotation, offset),
+        SpannedData::Array(items) => {
+            for item in items {
+                shift_spans(item, offset);
+            }
+        }
+        SpannedData::Object(pairs) => {
+            for (key, value) in pairs {
+                shift(&mut key.annotation, offset);
+                shift_spans(value, offset);
+            }
+        }
+    }
 }
 
 fn parse_value(
@@ -42,9 +170,24 @@ fn parse_value(
         })),
         "number" => {
             let text = node.utf8_text(source.as_bytes()).ok()?;
+            // Rust also parses `inf`, `NaN` and leading zeros, which JSON doesn't allow
+            if !is_json_number(text) {
+                errors.push(ParseError {
+                    message: format!("Invalid JSON number '{text}'"),
+                    span: make_span(node, filename),
+                });
+                return None;
+            }
             match text.parse::<f64>() {
+                Ok(num) if !num.is_finite() => {
+                    errors.push(ParseError {
+                        message: format!("Number '{text}' is out of range"),
+                        span: make_span(node, filename),
+                    });
+                    None
+                }
                 Ok(num) => Some(SpannedData::Number(Spanned {
-                    value: num,
+                    value: Number::with_raw(num, text),
                     annotation: make_span_vec(node, filename),
                 })),
                 Err(e) => {
@@ -57,9 +200,7 @@ fn parse_value(
             }
         }
         "string" => {
-            let text = node.utf8_text(source.as_bytes()).ok()?;
-            // Remove quotes
-            let content = text[1..text.len() - 1].to_string();
+            let content = parse_string_value(node, source, filename, errors)?;
             Some(SpannedData::String(Spanned {
                 value: content,
                 annotation: make_span_vec(node, filename),
@@ -95,7 +236,7 @@ fn parse_value(
                         let value_node = child
                             .child_by_field_name("value")
                             .or_else(|| child.named_child(1))?;
-                        let key = parse_string_value(&key_node, source, errors)?;
+                        let key = parse_string_value(&key_node, source, filename, errors)?;
                         let value = parse_value(&value_node, source, filename, errors)?;
                         pairs.push((
                             Spanned {
@@ -128,7 +269,20 @@ fn parse_value(
 
             Some(SpannedData::Object(pairs))
         }
-        "document" => parse_value(&node.child(0).unwrap(), source, filename, errors),
+        "document" => match node.child(0) {
+            Some(child) => parse_value(&child, source, filename, errors),
+            None => {
+                errors.push(ParseError {
+                    message: "Expected a value".to_owned(),
+                    span: Span {
+                        filename: filename.to_owned(),
+                        start: 0,
+                        end: 0,
+                    },
+                });
+                None
+            }
+        },
         _ => {
             errors.push(ParseError {
                 message: format!("Unexpected node type: {}", node.kind()),
@@ -139,38 +293,109 @@ fn parse_value(
     }
 }
 
-fn parse_string_value(node: &Node, source: &str, errors: &mut Vec<ParseError>) -> Option<String> {
+fn parse_string_value(
+    node: &Node,
+    source: &str,
+    filename: &str,
+    errors: &mut Vec<ParseError>,
+) -> Option<String> {
     if node.kind() != "string" {
         errors.push(ParseError {
             message: format!("Expected string, got {}", node.kind()),
-            span: make_span(node, "foo"),
+            span: make_span(node, filename),
         });
         return None;
     }
 
     let text = node.utf8_text(source.as_bytes()).ok()?;
     // Remove quotes
-    Some(text[1..text.len() - 1].to_string())
+    let unescaped = unescape_json_string(&text[1..text.len() - 1]);
+    if unescaped.is_none() {
+        errors.push(ParseError {
+            message: "Invalid escape sequence in string".to_string(),
+            span: make_span(node, filename),
+        });
+    }
+    unescaped
 }
 
-/// Creates a `Span` from a `tree_sitter::Node`.
-fn make_span(node: &Node, filename: &str) -> Span {
-    Span {
-        filename: filename.to_string(),
-        start: node.start_byte(),
-        end: node.end_byte(),
+/// Processes the escape sequences of a JSON string given without its quotes.
+/// Returns `None` if it contains an invalid escape sequence.
+fn unescape_json_string(text: &str) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    // Consecutive `\uXXXX` escapes, which may form surrogate pairs
+    let mut utf16 = vec![];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.clone().next() == Some('u') {
+            chars.next();
+            let hex: String = chars.by_ref().take(4).collect();
+            if hex.len() != 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            utf16.push(u16::from_str_radix(&hex, 16).ok()?);
+            continue;
+        }
+        result.extend(
+            char::decode_utf16(utf16.drain(..)).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)),
+        );
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        result.push(match chars.next()? {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            _ => return None,
+        });
     }
+    result.extend(char::decode_utf16(utf16).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+    Some(result)
 }
 
-/// Creates a `Vec<Span>` from a `tree_sitter::Node`.
-fn make_span_vec(node: &Node, filename: &str) -> SpanSet {
-    SpanSet(vec![make_span(node, filename)])
+/// Whether `text` matches the number grammar of JSON: an optional minus, an
+/// integer part without leading zeros, then an optional fraction and exponent.
+fn is_json_number(text: &str) -> bool {
+    fn digits(text: &str) -> (&str, &str) {
+        let end = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+        text.split_at(end)
+    }
+    let text = text.strip_prefix('-').unwrap_or(text);
+    let (integer, mut rest) = digits(text);
+    if integer.is_empty() || integer.len() > 1 && integer.starts_with('0') {
+        return false;
+    }
+    if let Some(after_dot) = rest.strip_prefix('.') {
+        let (fraction, after) = digits(after_dot);
+        if fraction.is_empty() {
+            return false;
+        }
+        rest = after;
+    }
+    if let Some(after_e) = rest.strip_prefix(['e', 'E']) {
+        let after_sign = after_e.strip_prefix(['+', '-']).unwrap_or(after_e);
+        let (exponent, after) = digits(after_sign);
+        if exponent.is_empty() {
+            return false;
+        }
+        rest = after;
+    }
+    rest.is_empty()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use deval_data_model::{Format, SpannedData};
+    use deval_validator::{AnyValidator, ObjectValidator, RecordValidator, Validator};
 
     #[test]
     fn test_parse_simple_object() {
@@ -321,6 +546,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_number_keeps_raw_text() {
+        let json = r#"[1e6, 1.50]"#;
+        let parsed = Json.parse(json, "test.json").expect("Failed to parse JSON");
+        let SpannedData::Array(items) = parsed.value else {
+            panic!("Expected array");
+        };
+        let numbers: Vec<String> = items
+            .iter()
+            .map(|item| match &item.value {
+                SpannedData::Number(n) => n.value.to_string(),
+                _ => panic!("Expected number value"),
+            })
+            .collect();
+        assert_eq!(numbers, ["1e6", "1.50"]);
+    }
+
     #[test]
     fn test_parse_empty_object() {
         let json = r#"{}"#;
@@ -361,4 +603,171 @@ mod tests {
         // This should fail
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_syntax_error_spans() {
+        let json = "{\n  \"a\": 1,\n  \"b\": ]\n}";
+        let errors = Json.parse(json, "test.json").unwrap_err();
+        assert!(!errors.is_empty());
+        // Recovery covers the pair around the stray `]`, not the whole object
+        let stray = json.find(']').unwrap();
+        for error in &errors {
+            assert!(error.span.start > json.find('1').unwrap(), "{error:?}");
+            assert!(error.span.end < json.len(), "{error:?}");
+        }
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.span.start <= stray && stray < e.span.end)
+        );
+
+        let errors = Json.parse("[1, 2", "test.json").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Missing `]`");
+        assert_eq!(errors[0].span.start, 5);
+    }
+
+    #[test]
+    fn test_parse_empty_document() {
+        for json in ["", "  \n"] {
+            let errors = Json.parse(json, "test.json").unwrap_err();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message, "Expected a value");
+            assert_eq!((errors[0].span.start, errors[0].span.end), (0, 0));
+        }
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let source = "{\"port\": 80}\n{\"port\": \"x\"}\n\n{\"port\": 443}\n";
+        let documents: Vec<_> = JsonLines
+            .parse_many(source, "test.jsonl")
+            .0
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(documents.len(), 3);
+
+        // The middle document is invalid, with its error inside the second line
+        let validator = ObjectValidator {
+            records: vec![RecordValidator::SimpleKey {
+                key: "port".to_owned(),
+                aliases: vec![],
+                ignore_case: false,
+                docs: String::new(),
+                value: Box::new(deval_validator::NumberValidator),
+                optional: false,
+                default: None,
+            }],
+            allow_unknown: false,
+            min_keys: None,
+            max_keys: None,
+        };
+        let errors: Vec<_> = documents
+            .into_iter()
+            .flat_map(|document| validator.validate(document).errors)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected Number, found String");
+        let x = source.find("\"x\"").unwrap();
+        assert_eq!((errors[0].span.start, errors[0].span.end), (x, x + 3));
+
+        // A syntax error is reported on its own line, and the other lines still
+        // parse
+        let source = "[1]\n[2,]\n[3]\n";
+        let (documents, _) = JsonLines.parse_many(source, "test.jsonl");
+        assert_eq!(documents.len(), 3);
+        assert!(documents[0].is_ok() && documents[2].is_ok());
+        let errors = documents[1].as_ref().unwrap_err();
+        assert!(
+            errors.iter().all(|e| (4..8).contains(&e.span.start)),
+            "{errors:?}"
+        );
+        assert!(JsonLines.parse(source, "test.jsonl").is_err());
+    }
+
+    #[test]
+    fn test_is_json_number() {
+        for valid in ["0", "-0", "12", "1.5", "1e10", "1.5E-3", "2e+8", "-0.25"] {
+            assert!(is_json_number(valid), "{valid} should be valid");
+        }
+        for invalid in [
+            "NaN", "inf", "Infinity", "-inf", "01", "-01", "1.", ".5", "+1", "1e", "1e+", "0x10",
+        ] {
+            assert!(!is_json_number(invalid), "{invalid} should be invalid");
+        }
+    }
+
+    #[test]
+    fn test_parse_number_grammar() {
+        assert!(Json.parse("[1e10, 1.5E-3]", "test.json").is_ok());
+        assert!(Json.parse("[NaN]", "test.json").is_err());
+        assert!(Json.parse("[01]", "test.json").is_err());
+        let errors = Json.parse("[1e400]", "test.json").unwrap_err();
+        assert_eq!(errors[0].message, "Number '1e400' is out of range");
+        assert_eq!((errors[0].span.start, errors[0].span.end), (1, 6));
+    }
+
+    #[test]
+    fn test_unescape_json_string() {
+        assert_eq!(
+            unescape_json_string(r#"a\"b\\c\/d\n\t"#).as_deref(),
+            Some("a\"b\\c/d\n\t")
+        );
+        assert_eq!(unescape_json_string(r"a\u002eb").as_deref(), Some("a.b"));
+        assert_eq!(
+            unescape_json_string(r"\uD83D\uDE00!").as_deref(),
+            Some("😀!")
+        );
+        assert_eq!(unescape_json_string(r"\u00E9").as_deref(), Some("é"));
+        assert_eq!(unescape_json_string(r"\x"), None);
+        assert_eq!(unescape_json_string(r"\u12"), None);
+    }
+
+    #[test]
+    fn test_escaped_keys() {
+        let json = r#"{"a\u002eb": 1, "with\"quote": 2}"#;
+        let parsed = Json.parse(json, "test.json").expect("Failed to parse JSON");
+
+        match parsed.value {
+            SpannedData::Object(pairs) => {
+                assert_eq!(pairs[0].0.value, "a.b");
+                assert_eq!(pairs[1].0.value, "with\"quote");
+
+                // The span covers the whole quoted key
+                let span = &pairs[0].0.annotation.0[0];
+                assert_eq!(&json[span.start..span.end], r#""a\u002eb""#);
+            }
+            _ => panic!("Expected object"),
+        }
+    }
+
+    #[test]
+    fn test_escaped_keys_match_schema() {
+        let record = |key: &str| RecordValidator::SimpleKey {
+            key: key.to_owned(),
+            aliases: vec![],
+            ignore_case: false,
+            docs: String::new(),
+            value: Box::new(AnyValidator),
+            optional: false,
+            default: None,
+        };
+        let validator = ObjectValidator {
+            records: vec![record("a.b"), record("c")],
+            allow_unknown: false,
+            min_keys: None,
+            max_keys: None,
+        };
+
+        let json = r#"{"a\u002eb": 1, "c": 2}"#;
+        let parsed = Json.parse(json, "test.json").expect("Failed to parse JSON");
+        assert!(validator.validate(parsed).errors.is_empty());
+
+        let json = r#"{"a.b": 1, "c": 2, "\u0063": 3}"#;
+        let parsed = Json.parse(json, "test.json").expect("Failed to parse JSON");
+        let errors = validator.validate(parsed).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Duplicate key c");
+    }
 }
@@ -0,0 +1,701 @@
+use deval_data_model::{
+    AnnotatedData, Format, Number, ParseError, Span, SpanSet, Spanned, SpannedData,
+};
+
+type Pairs = Vec<(Spanned<String>, Spanned<SpannedData>)>;
+
+/// Rusty Object Notation. Structs and maps become objects, with the struct name
+/// dropped, and tuples and lists become arrays. `None` is null and `Some(x)` is
+/// just `x`, while unit enum variants like `Debug` become strings.
+pub struct Ron;
+
+impl Format for Ron {
+    fn parse(&self, source: &str, filename: &str) -> Result<Spanned<SpannedData>, Vec<ParseError>> {
+        Ok(self.parse_with_comments(source, filename)?.0)
+    }
+
+    fn parse_with_comments(
+        &self,
+        source: &str,
+        filename: &str,
+    ) -> Result<(Spanned<SpannedData>, Vec<(Span, String)>), Vec<ParseError>> {
+        let mut parser = Parser {
+            source,
+            filename,
+            pos: 0,
+            comments: vec![],
+        };
+        let result = parser.attributes().and_then(|()| {
+            let value = parser.value()?;
+            parser.skip_trivia()?;
+            if parser.pos < source.len() {
+                return Err(parser.error("Expected end of file"));
+            }
+            Ok(value)
+        });
+        match result {
+            Ok(value) => Ok((value, parser.comments)),
+            Err(e) => Err(vec![e]),
+        }
+    }
+
+    fn render_value(&self, value: &AnnotatedData<()>) -> Option<String> {
+        let mut out = String::new();
+        render(value, &mut out);
+        Some(out)
+    }
+
+    fn name(&self) -> &'static str {
+        "ron"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// Writes `data` as RON on one line. Objects whose keys are all identifiers
+/// become structs, and other objects maps.
+fn render<A>(data: &AnnotatedData<A>, out: &mut String) {
+    match data {
+        AnnotatedData::Null => out.push_str("None"),
+        AnnotatedData::Bool(b) => out.push_str(if b.value { "true" } else { "false" }),
+        AnnotatedData::Number(n) => out.push_str(&n.value.to_string()),
+        AnnotatedData::String(s) => render_string(&s.value, out),
+        AnnotatedData::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                render(&item.value, out);
+            }
+            out.push(']');
+        }
+        AnnotatedData::Object(pairs) => {
+            let is_struct = !pairs.is_empty()
+                && pairs.iter().all(|(key, _)| {
+                    key.value
+                        .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                        && key
+                            .value
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                });
+            out.push(if is_struct { '(' } else { '{' });
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                if is_struct {
+                    out.push_str(&key.value);
+                } else {
+                    render_string(&key.value, out);
+                }
+                out.push_str(": ");
+                render(&value.value, out);
+            }
+            out.push(if is_struct { ')' } else { '}' });
+        }
+    }
+}
+
+fn render_string(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    filename: &'a str,
+    pos: usize,
+    /// The comments skipped so far, in order
+    comments: Vec<(Span, String)>,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span {
+            filename: self.filename.to_string(),
+            start,
+            end,
+        }
+    }
+
+    fn span_set(&self, start: usize) -> SpanSet {
+        SpanSet(vec![self.span(start, self.pos)])
+    }
+
+    /// An error at the next character, or at the end of the file.
+    fn error(&self, message: &str) -> ParseError {
+        let end = self.peek().map_or(self.pos, |c| self.pos + c.len_utf8());
+        ParseError {
+            message: message.to_string(),
+            span: self.span(self.pos, end),
+        }
+    }
+
+    /// Skips whitespace, `//` comments and (nested) `/* */` comments.
+    fn skip_trivia(&mut self) -> Result<(), ParseError> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            let start = self.pos;
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+                self.push_comment(start);
+            } else if trimmed.starts_with("/*") {
+                let mut depth = 0;
+                loop {
+                    let rest = self.rest();
+                    if rest.starts_with("/*") {
+                        depth += 1;
+                        self.pos += 2;
+                    } else if rest.starts_with("*/") {
+                        depth -= 1;
+                        self.pos += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else if let Some(c) = self.peek() {
+                        self.pos += c.len_utf8();
+                    } else {
+                        return Err(ParseError {
+                            message: "Unterminated block comment".to_string(),
+                            span: self.span(start, start + 2),
+                        });
+                    }
+                }
+                self.push_comment(start);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Records the comment from `start` to here, unless it was already seen
+    /// before backtracking.
+    fn push_comment(&mut self, start: usize) {
+        if self.comments.last().is_some_and(|(s, _)| s.start >= start) {
+            return;
+        }
+        let text = self.source[start..self.pos].trim_end();
+        let span = self.span(start, start + text.len());
+        self.comments.push((span, text.to_owned()));
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        self.skip_trivia()?;
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("Expected `{c}`")));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Skips `#![enable(...)]` extension attributes at the start of the file.
+    fn attributes(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.skip_trivia()?;
+            if !self.rest().starts_with("#!") {
+                return Ok(());
+            }
+            self.pos += 2;
+            self.expect('[')?;
+            let Some(end) = self.rest().find(']') else {
+                return Err(self.error("Expected `]` after the attribute"));
+            };
+            self.pos += end + 1;
+        }
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let mut chars = rest.char_indices();
+        let (_, first) = chars.next()?;
+        if !(first.is_alphabetic() || first == '_') {
+            return None;
+        }
+        let len = chars
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+            .map_or(rest.len(), |(i, _)| i);
+        self.pos += len;
+        Some(&self.source[self.pos - len..self.pos])
+    }
+
+    /// Parses items separated by commas, with an optional trailing comma, up to
+    /// and including `close`.
+    fn items(
+        &mut self,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                return Ok(());
+            }
+            item(self)?;
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(c) if c == close => (),
+                _ => return Err(self.error(&format!("Expected `,` or `{close}`"))),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Spanned<SpannedData>, ParseError> {
+        self.skip_trivia()?;
+        let start = self.pos;
+        match self.peek() {
+            Some('(') => self.parenthesized(start),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                self.items(']', |p| {
+                    items.push(p.value()?);
+                    Ok(())
+                })?;
+                Ok(Spanned {
+                    value: SpannedData::Array(items),
+                    annotation: self.span_set(start),
+                })
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut pairs = vec![];
+                self.items('}', |p| {
+                    let key = p.map_key()?;
+                    p.expect(':')?;
+                    let value = p.value()?;
+                    push_pair(&mut pairs, key, value)
+                })?;
+                Ok(Spanned {
+                    value: SpannedData::Object(pairs),
+                    annotation: self.span_set(start),
+                })
+            }
+            Some('"') => {
+                let value = self.string()?;
+                Ok(self.scalar(start, SpannedData::String, value))
+            }
+            Some('r') if self.rest()[1..].starts_with(['"', '#']) => {
+                let value = self.raw_string()?;
+                Ok(self.scalar(start, SpannedData::String, value))
+            }
+            Some('\'') => {
+                let value = self.char()?;
+                Ok(self.scalar(start, SpannedData::String, value))
+            }
+            Some(c) if c.is_ascii_digit() || matches!(c, '+' | '-' | '.') => {
+                let value = self.number()?;
+                Ok(self.scalar(start, SpannedData::Number, value))
+            }
+            _ => {
+                let Some(ident) = self.ident() else {
+                    return Err(self.error("Expected a value"));
+                };
+                match ident {
+                    "true" => Ok(self.scalar(start, SpannedData::Bool, true)),
+                    "false" => Ok(self.scalar(start, SpannedData::Bool, false)),
+                    "inf" | "NaN" => {
+                        let value = Number::with_raw(ident.parse().unwrap(), ident);
+                        Ok(self.scalar(start, SpannedData::Number, value))
+                    }
+                    "None" => Ok(Spanned {
+                        value: SpannedData::Null,
+                        annotation: self.span_set(start),
+                    }),
+                    "Some" => {
+                        self.expect('(')?;
+                        let value = self.value()?;
+                        self.skip_trivia()?;
+                        if self.peek() == Some(',') {
+                            self.pos += 1;
+                        }
+                        self.expect(')')?;
+                        Ok(value)
+                    }
+                    ident => {
+                        let ident = ident.to_string();
+                        let name_end = self.pos;
+                        self.skip_trivia()?;
+                        if self.peek() == Some('(') {
+                            return self.parenthesized(start);
+                        }
+                        // A unit struct or enum variant
+                        self.pos = name_end;
+                        Ok(self.scalar(start, SpannedData::String, ident))
+                    }
+                }
+            }
+        }
+    }
+
+    fn scalar<T>(
+        &self,
+        start: usize,
+        kind: impl FnOnce(Spanned<T>) -> SpannedData,
+        value: T,
+    ) -> Spanned<SpannedData> {
+        let annotation = self.span_set(start);
+        Spanned {
+            value: kind(Spanned {
+                value,
+                annotation: annotation.clone(),
+            }),
+            annotation,
+        }
+    }
+
+    /// Parses the `(...)` of a struct or tuple, possibly after its name at `start`.
+    /// Named fields make an object and anything else an array, while `()` is null
+    /// and an empty named struct like `Config()` is an empty object.
+    fn parenthesized(&mut self, start: usize) -> Result<Spanned<SpannedData>, ParseError> {
+        let is_named = self.pos > start;
+        self.pos += 1;
+        let after_paren = self.pos;
+        self.skip_trivia()?;
+        let is_struct = self.ident().is_some() && {
+            self.skip_trivia()?;
+            self.peek() == Some(':')
+        };
+        self.pos = after_paren;
+
+        let value = if is_struct {
+            let mut pairs = vec![];
+            self.items(')', |p| {
+                p.skip_trivia()?;
+                let key_start = p.pos;
+                let Some(key) = p.ident() else {
+                    return Err(p.error("Expected a field name"));
+                };
+                let key = Spanned {
+                    value: key.to_string(),
+                    annotation: p.span_set(key_start),
+                };
+                p.expect(':')?;
+                let value = p.value()?;
+                push_pair(&mut pairs, key, value)
+            })?;
+            SpannedData::Object(pairs)
+        } else {
+            let mut items = vec![];
+            self.items(')', |p| {
+                items.push(p.value()?);
+                Ok(())
+            })?;
+            match (items.is_empty(), is_named) {
+                (true, false) => SpannedData::Null,
+                (true, true) => SpannedData::Object(vec![]),
+                (false, _) => SpannedData::Array(items),
+            }
+        };
+        Ok(Spanned {
+            value,
+            annotation: self.span_set(start),
+        })
+    }
+
+    fn map_key(&mut self) -> Result<Spanned<String>, ParseError> {
+        let key = self.value()?;
+        match key.value {
+            SpannedData::String(s) => Ok(s),
+            _ => Err(ParseError {
+                message: "Expected a string as the map key".to_string(),
+                span: key.annotation.primary().unwrap(),
+            }),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(ParseError {
+                    message: "Unterminated string".to_string(),
+                    span: self.span(start, self.pos),
+                });
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\\' => value.push(self.escape()?),
+                c => value.push(c),
+            }
+        }
+    }
+
+    /// Parses `r"..."` or `r#"..."#`, with any number of `#`.
+    fn raw_string(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let hashes = self.rest().len() - self.rest().trim_start_matches('#').len();
+        self.pos += hashes;
+        if self.peek() != Some('"') {
+            return Err(self.error("Expected `\"`"));
+        }
+        self.pos += 1;
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let Some(end) = self.rest().find(&terminator) else {
+            return Err(ParseError {
+                message: "Unterminated string".to_string(),
+                span: self.span(start, self.source.len()),
+            });
+        };
+        let value = self.rest()[..end].to_string();
+        self.pos += end + terminator.len();
+        Ok(value)
+    }
+
+    fn char(&mut self) -> Result<String, ParseError> {
+        self.pos += 1;
+        let value = match self.peek() {
+            Some('\\') => {
+                self.pos += 1;
+                self.escape()?
+            }
+            Some(c) if c != '\'' => {
+                self.pos += c.len_utf8();
+                c
+            }
+            _ => return Err(self.error("Expected a character")),
+        };
+        self.expect('\'')?;
+        Ok(value.to_string())
+    }
+
+    /// Parses the escape sequence after a `\`.
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let start = self.pos - 1;
+        let c = self.peek();
+        self.pos += c.map_or(0, char::len_utf8);
+        let value = match c {
+            Some('n') => Some('\n'),
+            Some('r') => Some('\r'),
+            Some('t') => Some('\t'),
+            Some('0') => Some('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => Some(c),
+            Some('u') if self.rest().starts_with('{') => match self.rest().find('}') {
+                Some(end) => {
+                    let code = u32::from_str_radix(&self.rest()[1..end], 16).ok();
+                    self.pos += end + 1;
+                    code.and_then(char::from_u32)
+                }
+                None => None,
+            },
+            _ => None,
+        };
+        value.ok_or_else(|| ParseError {
+            message: "Invalid escape sequence".to_string(),
+            span: self.span(start, self.pos),
+        })
+    }
+
+    fn number(&mut self) -> Result<Number, ParseError> {
+        let start = self.pos;
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_')))
+            .unwrap_or(self.rest().len());
+        self.pos += len;
+        let text = self.source[start..self.pos].replace('_', "");
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(&text)),
+        };
+        let radix = [("0x", 16), ("0o", 8), ("0b", 2)]
+            .into_iter()
+            .find_map(|(prefix, radix)| Some((digits.strip_prefix(prefix)?, radix)));
+        let value = match (radix, digits) {
+            (Some((digits, radix)), _) => i64::from_str_radix(digits, radix).ok().map(|x| x as f64),
+            (None, "inf") => Some(f64::INFINITY),
+            (None, "NaN") => Some(f64::NAN),
+            // Rust's float parsing also takes words like `infinity`
+            (None, digits) if digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') => {
+                digits.parse().ok()
+            }
+            _ => None,
+        };
+        let raw = &self.source[start..self.pos];
+        match value {
+            Some(value) if negative => Ok(Number::with_raw(-value, raw)),
+            Some(value) => Ok(Number::with_raw(value, raw)),
+            None => Err(ParseError {
+                message: format!("Invalid number '{}'", &self.source[start..self.pos]),
+                span: self.span(start, self.pos),
+            }),
+        }
+    }
+}
+
+fn push_pair(
+    pairs: &mut Pairs,
+    key: Spanned<String>,
+    value: Spanned<SpannedData>,
+) -> Result<(), ParseError> {
+    if pairs.iter().any(|(k, _)| k.value == key.value) {
+        return Err(ParseError {
+            message: format!("Duplicate key '{}'", key.value),
+            span: key.annotation.primary().unwrap(),
+        });
+    }
+    pairs.push((key, value));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Spanned<SpannedData> {
+        Ron.parse(source, "test.ron")
+            .expect("Failed to parse ron file")
+    }
+
+    fn text<'a>(source: &'a str, data: &Spanned<SpannedData>) -> &'a str {
+        let span = data.annotation.primary().unwrap();
+        &source[span.start..span.end]
+    }
+
+    fn field<'a>(data: &'a Spanned<SpannedData>, name: &str) -> &'a Spanned<SpannedData> {
+        let SpannedData::Object(pairs) = &data.value else {
+            panic!("Expected object");
+        };
+        match pairs.iter().find(|(k, _)| k.value == name) {
+            Some((_, value)) => value,
+            None => panic!("Expected field {name}"),
+        }
+    }
+
+    #[test]
+    fn test_named_struct() {
+        let source = r#"
+            #![enable(implicit_some)]
+            // The server config
+            Config(
+                name: "app", /* inline */
+                port: 8_080,
+                debug: true,
+                level: Debug,
+                proxy: None,
+                limit: Some(-1.5e3),
+                point: Point(x: 1, y: 2,),
+            )
+        "#;
+        let root = parse(source);
+        assert!(text(source, &root).starts_with("Config("));
+        assert!(matches!(&field(&root, "name").value, SpannedData::String(s) if s.value == "app"));
+        assert!(matches!(&field(&root, "port").value, SpannedData::Number(n) if n.value == 8080.));
+        assert_eq!(text(source, field(&root, "port")), "8_080");
+        assert!(matches!(&field(&root, "debug").value, SpannedData::Bool(b) if b.value));
+        assert!(
+            matches!(&field(&root, "level").value, SpannedData::String(s) if s.value == "Debug")
+        );
+        assert!(matches!(field(&root, "proxy").value, SpannedData::Null));
+        assert!(
+            matches!(&field(&root, "limit").value, SpannedData::Number(n) if n.value == -1500.)
+        );
+        let point = field(&root, "point");
+        assert_eq!(text(source, point), "Point(x: 1, y: 2,)");
+        assert!(matches!(&field(point, "y").value, SpannedData::Number(n) if n.value == 2.));
+    }
+
+    #[test]
+    fn test_list_and_tuple() {
+        let source = "([1, 0x1F, 'c',], (\"a\", r#\"b\"#), ())";
+        let SpannedData::Array(items) = parse(source).value else {
+            panic!("Expected array");
+        };
+        let SpannedData::Array(list) = &items[0].value else {
+            panic!("Expected array");
+        };
+        assert_eq!(list.len(), 3);
+        assert!(matches!(&list[1].value, SpannedData::Number(n) if n.value == 31.));
+        assert!(matches!(&list[2].value, SpannedData::String(s) if s.value == "c"));
+        let SpannedData::Array(tuple) = &items[1].value else {
+            panic!("Expected array");
+        };
+        assert!(matches!(&tuple[1].value, SpannedData::String(s) if s.value == "b"));
+        assert_eq!(text(source, &tuple[1]), "r#\"b\"#");
+        assert!(matches!(items[2].value, SpannedData::Null));
+    }
+
+    #[test]
+    fn test_map() {
+        let source = "{\n  \"a\\tb\": 1,\n  \"c\": [],\n}";
+        let root = parse(source);
+        let SpannedData::Object(pairs) = &root.value else {
+            panic!("Expected object");
+        };
+        let keys: Vec<_> = pairs.iter().map(|(k, _)| &*k.value).collect();
+        assert_eq!(keys, ["a\tb", "c"]);
+        let span = pairs[1].0.annotation.primary().unwrap();
+        assert_eq!(&source[span.start..span.end], "\"c\"");
+    }
+
+    #[test]
+    fn test_parse_with_comments() {
+        // Fields are looked ahead at, so the comments before them are skipped twice
+        let source = "Config /* a */ (\n  /// b\n  // c\n  x: 1, // d\n)";
+        let (_, comments) = Ron.parse_with_comments(source, "test.ron").unwrap();
+        let comments: Vec<_> = comments
+            .iter()
+            .map(|(span, text)| (&source[span.start..span.end], text.as_str()))
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                ("/* a */", "/* a */"),
+                ("/// b", "/// b"),
+                ("// c", "// c"),
+                ("// d", "// d")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        fn error(source: &str) -> (String, &str) {
+            let errors = Ron.parse(source, "test.ron").unwrap_err();
+            let span = &errors[0].span;
+            (errors[0].message.clone(), &source[span.start..span.end])
+        }
+        assert_eq!(
+            error("{ 1: 2 }"),
+            ("Expected a string as the map key".to_string(), "1")
+        );
+        assert_eq!(
+            error("(a: 1 b: 2)"),
+            ("Expected `,` or `)`".to_string(), "b")
+        );
+        assert_eq!(
+            error("(a: 1, a: 2)"),
+            ("Duplicate key 'a'".to_string(), "a")
+        );
+        assert_eq!(error("[1] 2"), ("Expected end of file".to_string(), "2"));
+    }
+}
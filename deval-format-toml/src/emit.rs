@@ -0,0 +1,226 @@
+//! Writing data back as TOML text.
+
+use deval_data_model::{Annotated, AnnotatedData, SpanSet, SpannedData};
+
+type Pairs<A> = [(Annotated<String, A>, Annotated<AnnotatedData<A>, A>)];
+
+/// Writes `data`, parsed from `source`, as TOML. Objects become tables and
+/// arrays of objects become arrays of tables, except inside other arrays, where
+/// they are written inline. Numbers and datetimes are written as they appeared
+/// in the source.
+///
+/// Fails if the root is not an object or there is a null, which TOML can't
+/// represent.
+pub fn emit(data: &SpannedData, source: &str) -> Result<String, String> {
+    let AnnotatedData::Object(pairs) = data else {
+        return Err("The root of a TOML document must be a table".to_string());
+    };
+    // Datetimes are parsed as strings, and are the only ones written unquoted
+    let is_datetime = |span: &SpanSet| {
+        span.primary()
+            .and_then(|span| source.get(span.start..))
+            .is_some_and(|text| !text.starts_with(['"', '\'']))
+    };
+    let mut out = String::new();
+    emit_table(&[], pairs, &is_datetime, &mut out)?;
+    Ok(out)
+}
+
+/// Whether `data` is written under its own header, rather than as a value.
+fn is_table<A>(data: &AnnotatedData<A>) -> bool {
+    match data {
+        AnnotatedData::Object(_) => true,
+        AnnotatedData::Array(items) => {
+            !items.is_empty()
+                && items
+                    .iter()
+                    .all(|item| matches!(item.value, AnnotatedData::Object(_)))
+        }
+        _ => false,
+    }
+}
+
+fn emit_table<A>(
+    path: &[&str],
+    pairs: &Pairs<A>,
+    is_datetime: &impl Fn(&A) -> bool,
+    out: &mut String,
+) -> Result<(), String> {
+    for (key, value) in pairs {
+        if !is_table(&value.value) {
+            emit_key(&key.value, out);
+            out.push_str(" = ");
+            emit_inline(&value.value, is_datetime, out)?;
+            out.push('\n');
+        }
+    }
+    for (key, value) in pairs {
+        let path = [path, &[key.value.as_str()]].concat();
+        match &value.value {
+            AnnotatedData::Object(inner) => {
+                // A table with only subtables is implied by their headers
+                if inner.is_empty() || inner.iter().any(|(_, v)| !is_table(&v.value)) {
+                    emit_header("[", &path, "]", out);
+                }
+                emit_table(&path, inner, is_datetime, out)?;
+            }
+            AnnotatedData::Array(items) if is_table(&value.value) => {
+                for item in items {
+                    let AnnotatedData::Object(inner) = &item.value else {
+                        unreachable!("arrays of tables only have objects");
+                    };
+                    emit_header("[[", &path, "]]", out);
+                    emit_table(&path, inner, is_datetime, out)?;
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn emit_header(open: &str, path: &[&str], close: &str, out: &mut String) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(open);
+    for (i, key) in path.iter().enumerate() {
+        if i > 0 {
+            out.push('.');
+        }
+        emit_key(key, out);
+    }
+    out.push_str(close);
+    out.push('\n');
+}
+
+/// Writes a value inline. `is_datetime` tells the strings which are datetimes
+/// by their annotation.
+pub(crate) fn emit_inline<A>(
+    data: &AnnotatedData<A>,
+    is_datetime: &impl Fn(&A) -> bool,
+    out: &mut String,
+) -> Result<(), String> {
+    match data {
+        AnnotatedData::Null => return Err("TOML has no null value".to_string()),
+        AnnotatedData::Bool(b) => out.push_str(if b.value { "true" } else { "false" }),
+        AnnotatedData::Number(n) => out.push_str(&n.value.to_string()),
+        AnnotatedData::String(s) if is_datetime(&s.annotation) => out.push_str(&s.value),
+        AnnotatedData::String(s) => emit_string(&s.value, out),
+        AnnotatedData::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                emit_inline(&item.value, is_datetime, out)?;
+            }
+            out.push(']');
+        }
+        AnnotatedData::Object(pairs) if pairs.is_empty() => out.push_str("{}"),
+        AnnotatedData::Object(pairs) => {
+            out.push_str("{ ");
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                emit_key(&key.value, out);
+                out.push_str(" = ");
+                emit_inline(&value.value, is_datetime, out)?;
+            }
+            out.push_str(" }");
+        }
+    }
+    Ok(())
+}
+
+/// Writes a key bare if it can be, and quoted otherwise.
+fn emit_key(key: &str, out: &mut String) {
+    let is_bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if is_bare {
+        out.push_str(key);
+    } else {
+        emit_string(key, out);
+    }
+}
+
+/// Writes a basic string, escaping quotes, backslashes and control characters.
+fn emit_string(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Toml;
+    use deval_data_model::Format;
+
+    fn format(source: &str) -> String {
+        emit(&Toml.parse(source, "test.toml").unwrap().value, source).unwrap()
+    }
+
+    #[test]
+    fn test_emit() {
+        let source = r#"
+# Comments are dropped
+title = "t"
+[server]
+host="localhost"
+ports = [ 80,0x1F ]
+
+[[users]]
+name = 'a "b"'
+[[users]]
+name = "c"
+
+[a.b]
+"key with space" = [{ x = 1 }, 2]
+"#;
+        let formatted = format(source);
+        assert_eq!(
+            formatted,
+            r#"title = "t"
+
+[server]
+host = "localhost"
+ports = [80, 0x1F]
+
+[[users]]
+name = "a \"b\""
+
+[[users]]
+name = "c"
+
+[a.b]
+"key with space" = [{ x = 1 }, 2]
+"#
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_emit_dotted_keys_and_datetimes() {
+        let source = "a.b = 1\na.c = 1979-05-27\nd = \"1979-05-27\"\n";
+        let formatted = format(source);
+        assert_eq!(
+            formatted,
+            "d = \"1979-05-27\"\n\n[a]\nb = 1\nc = 1979-05-27\n"
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+}
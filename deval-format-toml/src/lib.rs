@@ -1,10 +1,24 @@
-use deval_data_model::{Format, ParseError, Span, SpanSet, Spanned, SpannedData};
+use deval_data_model::{AnnotatedData, Format, Number, ParseError, Span, Spanned, SpannedData};
+use deval_tree_sitter::{make_span, make_span_vec, syntax_errors};
+use std::collections::HashSet;
 use tree_sitter::{Node, Parser};
 
+mod emit;
+
+pub use emit::emit;
+
 pub struct Toml;
 
 impl Format for Toml {
     fn parse(&self, source: &str, filename: &str) -> Result<Spanned<SpannedData>, Vec<ParseError>> {
+        Ok(self.parse_with_comments(source, filename)?.0)
+    }
+
+    fn parse_with_comments(
+        &self,
+        source: &str,
+        filename: &str,
+    ) -> Result<(Spanned<SpannedData>, Vec<(Span, String)>), Vec<ParseError>> {
         let mut parser = Parser::new();
         parser
             .set_language(tree_sitter_toml::language())
@@ -16,13 +30,13 @@ impl Format for Toml {
         let mut errors = Vec::new();
 
         if root_node.has_error() {
-            errors.push(ParseError {
-                message: "Failed to parse TOML structure due to syntax errors.".to_string(),
-                span: make_span(&root_node, filename),
-            });
+            syntax_errors(&root_node, filename, &mut errors);
+            return Err(errors);
         }
 
         let mut root_data = SpannedData::Object(Vec::new());
+        // The start of the `[[..]]` header that created each array of tables
+        let mut array_tables = HashSet::new();
 
         // Iterate through all top-level nodes in the document.
         let mut cursor = root_node.walk();
@@ -31,16 +45,8 @@ impl Format for Toml {
                 // A key-value pair at the top level.
                 "pair" => {
                     if let SpannedData::Object(pairs) = &mut root_data {
-                        if let Some((key, value)) = parse_pair(&node, source, filename, &mut errors)
-                        {
-                            if pairs.iter().any(|(k, _)| k.value == key.value) {
-                                errors.push(ParseError {
-                                    message: format!("Duplicate key '{}' at top level", key.value),
-                                    span: key.annotation.primary(),
-                                });
-                            } else {
-                                pairs.push((key, value));
-                            }
+                        if let Some(pair) = parse_pair(&node, source, filename, &mut errors) {
+                            insert_pair(pairs, pair, "at top level", &mut errors);
                         }
                     }
                 }
@@ -58,35 +64,27 @@ impl Format for Toml {
                         }
                     };
                     let key_path = key_node.utf8_text(source.as_bytes()).unwrap();
-                    let key_parts: Vec<&str> = key_path.split('.').collect();
+                    let mut keys = vec![];
+                    header_keys(&key_node, source, filename, &mut keys);
 
                     // Get the target table, creating it if it doesn't exist.
                     if let Some(target_pairs) = get_or_insert_table(
                         &mut root_data,
-                        &key_parts,
+                        &keys,
                         &node,
-                        source,
                         filename,
+                        &array_tables,
                         &mut errors,
                     ) {
                         // Now, parse all pairs that are *children* of this table node.
                         let mut table_cursor = node.walk();
                         for child in node.children(&mut table_cursor) {
                             if child.kind() == "pair" {
-                                if let Some((key, value)) =
+                                if let Some(pair) =
                                     parse_pair(&child, source, filename, &mut errors)
                                 {
-                                    if target_pairs.iter().any(|(k, _)| k.value == key.value) {
-                                        errors.push(ParseError {
-                                            message: format!(
-                                                "Duplicate key '{}' in table '{}'",
-                                                key.value, key_path
-                                            ),
-                                            span: key.annotation.primary(),
-                                        });
-                                    } else {
-                                        target_pairs.push((key, value));
-                                    }
+                                    let table = format!("in table '{key_path}'");
+                                    insert_pair(target_pairs, pair, &table, &mut errors);
                                 }
                             }
                         }
@@ -106,35 +104,27 @@ impl Format for Toml {
                         }
                     };
                     let key_path = key_node.utf8_text(source.as_bytes()).unwrap();
-                    let key_parts: Vec<&str> = key_path.split('.').collect();
+                    let mut keys = vec![];
+                    header_keys(&key_node, source, filename, &mut keys);
 
                     // Append a new table to the array and get a reference to its pairs.
                     if let Some(target_pairs) = append_to_array_of_tables(
                         &mut root_data,
-                        &key_parts,
+                        &keys,
                         &node,
-                        source,
                         filename,
+                        &mut array_tables,
                         &mut errors,
                     ) {
                         // Parse all pairs that are *children* of this array table node.
                         let mut array_table_cursor = node.walk();
                         for child in node.children(&mut array_table_cursor) {
                             if child.kind() == "pair" {
-                                if let Some((key, value)) =
+                                if let Some(pair) =
                                     parse_pair(&child, source, filename, &mut errors)
                                 {
-                                    if target_pairs.iter().any(|(k, _)| k.value == key.value) {
-                                        errors.push(ParseError {
-                                            message: format!(
-                                                "Duplicate key '{}' in table '{}'",
-                                                key.value, key_path
-                                            ),
-                                            span: key.annotation.primary(),
-                                        });
-                                    } else {
-                                        target_pairs.push((key, value));
-                                    }
+                                    let table = format!("in table '{key_path}'");
+                                    insert_pair(target_pairs, pair, &table, &mut errors);
                                 }
                             }
                         }
@@ -154,74 +144,107 @@ impl Format for Toml {
         }
 
         if !errors.is_empty() {
-            Err(errors)
-        } else {
-            Ok(Spanned {
-                value: root_data,
-                annotation: make_span_vec(&root_node, filename),
-            })
+            return Err(errors);
         }
+        let mut comments = vec![];
+        collect_comments(&root_node, source, filename, &mut comments);
+        let data = Spanned {
+            value: root_data,
+            annotation: make_span_vec(&root_node, filename),
+        };
+        Ok((data, comments))
+    }
+
+    fn render_value(&self, value: &AnnotatedData<()>) -> Option<String> {
+        let mut out = String::new();
+        emit::emit_inline(value, &|_| false, &mut out).ok()?;
+        Some(out)
+    }
+
+    fn name(&self) -> &'static str {
+        "toml"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["toml"]
+    }
+}
+
+/// Collects the `comment` nodes under `node` in source order.
+fn collect_comments(node: &Node, source: &str, filename: &str, out: &mut Vec<(Span, String)>) {
+    if node.kind() == "comment" {
+        let text = node.utf8_text(source.as_bytes()).unwrap();
+        out.push((make_span(node, filename), text.to_owned()));
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_comments(&child, source, filename, out);
     }
 }
 
 /// Navigates or creates a path of tables and returns a mutable reference to the final table's pairs.
-/// Merges spans along the way.
+/// Arrays of tables on the path resolve to their last element. Merges spans along the way.
+/// `array_tables` holds the header starts of the arrays of tables, as other arrays can't be
+/// extended.
 fn get_or_insert_table<'a>(
     mut current_data: &'a mut SpannedData,
-    path: &[&str],
+    path: &[Spanned<String>],
     table_header_node: &Node,
-    source: &str,
     filename: &str,
+    array_tables: &HashSet<usize>,
     errors: &mut Vec<ParseError>,
 ) -> Option<&'a mut Vec<(Spanned<String>, Spanned<SpannedData>)>> {
-    // Extract individual key spans from the table header
-    let key_spans = extract_individual_key_spans(table_header_node, source, filename, path);
-
-    for (i, &key) in path.iter().enumerate() {
+    for (i, key) in path.iter().enumerate() {
         let current_table_pairs = match current_data {
             SpannedData::Object(pairs) => pairs,
             _ => {
                 errors.push(ParseError {
-                    message: format!("Cannot define table '{}' because a key with this name was already defined as a non-table.", path[..i].join(".")),
+                    message: format!("Cannot define table '{}' because a key with this name was already defined as a non-table.", join_keys(&path[..i])),
                     span: make_span(table_header_node, filename),
                 });
                 return None;
             }
         };
 
-        let found_index = current_table_pairs.iter().position(|(k, _)| k.value == key);
+        let found_index = current_table_pairs
+            .iter()
+            .position(|(k, _)| k.value == key.value);
 
         if let Some(index) = found_index {
             let (found_key, found_value) = &mut current_table_pairs[index];
             // Don't add a span if it's for an implicitly created table.
             if !table_header_node.is_extra() {
                 // Use the specific key span instead of the whole table header
-                let key_span = key_spans
-                    .get(i)
-                    .cloned()
-                    .unwrap_or(make_span(table_header_node, filename));
-                found_key.annotation.0.push(key_span.clone());
-                found_value.annotation.0.push(key_span);
+                found_key
+                    .annotation
+                    .0
+                    .extend(key.annotation.0.iter().cloned());
+                found_value
+                    .annotation
+                    .0
+                    .extend(key.annotation.0.iter().cloned());
             }
+            // A table under an array of tables, like `[a.b]` after `[[a]]`,
+            // belongs to the last element of the array. An array written as a
+            // value, like `a = [{ y = 1 }]`, is left as a non-table
+            let in_array = matches!(&found_value.value, SpannedData::Array(e) if !e.is_empty())
+                && array_tables.contains(&found_value.annotation.0[0].start);
             current_data = &mut found_value.value;
+            if in_array {
+                let SpannedData::Array(elements) = current_data else {
+                    unreachable!()
+                };
+                current_data = &mut elements.last_mut().unwrap().value;
+            }
         } else {
             let new_table = SpannedData::Object(Vec::new());
             let new_spanned_table = Spanned {
                 value: new_table,
                 annotation: make_span_vec(table_header_node, filename),
             };
-            let new_spanned_key = Spanned {
-                value: key.to_string(),
-                // Use the specific key span instead of the whole table header
-                annotation: SpanSet(vec![
-                    key_spans
-                        .get(i)
-                        .cloned()
-                        .unwrap_or(make_span(table_header_node, filename)),
-                ]),
-            };
 
-            current_table_pairs.push((new_spanned_key, new_spanned_table));
+            current_table_pairs.push((key.clone(), new_spanned_table));
             current_data = &mut current_table_pairs.last_mut().unwrap().1.value;
         }
     }
@@ -230,7 +253,7 @@ fn get_or_insert_table<'a>(
         Some(pairs)
     } else {
         errors.push(ParseError {
-            message: format!("Cannot define table '{}' because a key with this name was already defined as a non-table.", path.join(".")),
+            message: format!("Cannot define table '{}' because a key with this name was already defined as a non-table.", join_keys(path)),
             span: make_span(table_header_node, filename),
         });
         None
@@ -241,10 +264,10 @@ fn get_or_insert_table<'a>(
 /// to a new table element appended to it.
 fn append_to_array_of_tables<'a>(
     current_data: &'a mut SpannedData,
-    path: &[&str],
+    path: &[Spanned<String>],
     array_header_node: &Node,
-    source: &str,
     filename: &str,
+    array_tables: &mut HashSet<usize>,
     errors: &mut Vec<ParseError>,
 ) -> Option<&'a mut Vec<(Spanned<String>, Spanned<SpannedData>)>> {
     let (array_key, table_path) = path.split_last()?;
@@ -253,46 +276,43 @@ fn append_to_array_of_tables<'a>(
         current_data,
         table_path,
         array_header_node,
-        source,
         filename,
+        array_tables,
         errors,
     )?;
 
-    let found_index = parent_table.iter().position(|(k, _)| k.value == *array_key);
+    let found_index = parent_table
+        .iter()
+        .position(|(k, _)| k.value == array_key.value);
 
     let array = match found_index {
         Some(index) => {
             let (key, spanned_value) = &mut parent_table[index];
             // Use the specific key span instead of the whole table header
-            let key_spans = extract_individual_key_spans(array_header_node, source, filename, path);
-            let key_span = key_spans
-                .get(table_path.len())
-                .cloned()
-                .unwrap_or(make_span(array_header_node, filename));
-            key.annotation.0.push(key_span.clone());
-            spanned_value.annotation.0.push(key_span);
+            key.annotation
+                .0
+                .extend(array_key.annotation.0.iter().cloned());
+            spanned_value
+                .annotation
+                .0
+                .extend(array_key.annotation.0.iter().cloned());
             if let SpannedData::Array(arr) = &mut spanned_value.value {
                 arr
             } else {
                 errors.push(ParseError {
-                    message: format!("Key '{}' was already defined as a non-array.", array_key),
+                    message: format!(
+                        "Key '{}' was already defined as a non-array.",
+                        array_key.value
+                    ),
                     span: make_span(array_header_node, filename),
                 });
                 return None;
             }
         }
         None => {
-            // Use the specific key span instead of the whole table header
-            let key_spans = extract_individual_key_spans(array_header_node, source, filename, path);
-            let key_span = key_spans
-                .get(table_path.len())
-                .cloned()
-                .unwrap_or(make_span(array_header_node, filename));
+            array_tables.insert(array_header_node.start_byte());
             parent_table.push((
-                Spanned {
-                    value: array_key.to_string(),
-                    annotation: SpanSet(vec![key_span]),
-                },
+                array_key.clone(),
                 Spanned {
                     value: SpannedData::Array(Vec::new()),
                     annotation: make_span_vec(array_header_node, filename),
@@ -322,25 +342,24 @@ fn append_to_array_of_tables<'a>(
     }
 }
 
-/// Parses a single key-value pair node.
+/// Parses a single key-value pair node. A dotted key, like `a.b = 1`, has one
+/// key per table on its path.
 fn parse_pair(
     pair_node: &Node,
     source: &str,
     filename: &str,
     errors: &mut Vec<ParseError>,
-) -> Option<(Spanned<String>, Spanned<SpannedData>)> {
+) -> Option<(Vec<Spanned<String>>, Spanned<SpannedData>)> {
     // A `pair` node's children are `key`, `=`, `value`. We access by index.
     let key_node = pair_node.child(0)?;
     let value_node = pair_node.child(2)?;
 
-    let key_text = unquote_toml_string(&key_node.utf8_text(source.as_bytes()).ok()?);
+    let mut keys = vec![];
+    header_keys(&key_node, source, filename, &mut keys);
     let value_data = parse_value(&value_node, source, filename, errors)?;
 
     Some((
-        Spanned {
-            value: key_text,
-            annotation: make_span_vec(&key_node, filename),
-        },
+        keys,
         Spanned {
             value: value_data,
             annotation: make_span_vec(&value_node, filename),
@@ -348,6 +367,68 @@ fn parse_pair(
     ))
 }
 
+/// Adds a parsed pair to `pairs`, creating or extending the tables on the path
+/// of a dotted key. `place` says where the pair is, for the duplicate key error.
+fn insert_pair(
+    mut pairs: &mut Vec<(Spanned<String>, Spanned<SpannedData>)>,
+    (keys, value): (Vec<Spanned<String>>, Spanned<SpannedData>),
+    place: &str,
+    errors: &mut Vec<ParseError>,
+) {
+    let Some((key, path)) = keys.split_last() else {
+        return;
+    };
+    for (i, table_key) in path.iter().enumerate() {
+        let index = match pairs.iter().position(|(k, _)| k.value == table_key.value) {
+            Some(index) => {
+                let (found_key, found_value) = &mut pairs[index];
+                found_key
+                    .annotation
+                    .0
+                    .extend(table_key.annotation.0.iter().cloned());
+                found_value
+                    .annotation
+                    .0
+                    .extend(table_key.annotation.0.iter().cloned());
+                index
+            }
+            None => {
+                pairs.push((
+                    table_key.clone(),
+                    Spanned {
+                        value: SpannedData::Object(Vec::new()),
+                        annotation: table_key.annotation.clone(),
+                    },
+                ));
+                pairs.len() - 1
+            }
+        };
+        let current = pairs;
+        pairs = match &mut current[index].1.value {
+            SpannedData::Object(inner) => inner,
+            _ => {
+                errors.push(ParseError {
+                    message: format!(
+                        "Cannot define key '{}' because '{}' was already defined as a non-table.",
+                        join_keys(&keys),
+                        join_keys(&path[..=i])
+                    ),
+                    span: table_key.annotation.primary().unwrap(),
+                });
+                return;
+            }
+        };
+    }
+    if pairs.iter().any(|(k, _)| k.value == key.value) {
+        errors.push(ParseError {
+            message: format!("Duplicate key '{}' {place}", join_keys(&keys)),
+            span: key.annotation.primary().unwrap(),
+        });
+    } else {
+        pairs.push((key.clone(), value));
+    }
+}
+
 /// Recursively parses a tree-sitter node representing a VALUE into SpannedData.
 fn parse_value(
     node: &Node,
@@ -374,9 +455,9 @@ fn parse_value(
         }
         "integer" | "float" => {
             let text = node.utf8_text(source.as_bytes()).unwrap();
-            match text.replace('_', "").parse::<f64>() {
+            match parse_toml_number(text) {
                 Ok(num) => Some(SpannedData::Number(Spanned {
-                    value: num,
+                    value: Number::with_raw(num, text),
                     annotation: make_span_vec(node, filename),
                 })),
                 Err(e) => {
@@ -392,7 +473,7 @@ fn parse_value(
             value: node.utf8_text(source.as_bytes()).unwrap() == "true",
             annotation: make_span_vec(node, filename),
         })),
-        "date_time" => {
+        "offset_date_time" | "local_date_time" | "local_date" | "local_time" => {
             let text = node.utf8_text(source.as_bytes()).unwrap().to_string();
             Some(SpannedData::String(Spanned {
                 value: text,
@@ -402,7 +483,8 @@ fn parse_value(
         "array" => {
             let mut children = Vec::new();
             let mut cursor = node.walk();
-            for child in node.named_children(&mut cursor) {
+            // Comments between the items are extra nodes
+            for child in node.named_children(&mut cursor).filter(|c| !c.is_extra()) {
                 if let Some(value) = parse_value(&child, source, filename, errors) {
                     children.push(Spanned {
                         value,
@@ -418,7 +500,7 @@ fn parse_value(
             for child in node.children(&mut cursor) {
                 if child.kind() == "pair" {
                     if let Some(pair) = parse_pair(&child, source, filename, errors) {
-                        pairs.push(pair);
+                        insert_pair(&mut pairs, pair, "in inline table", errors);
                     }
                 }
             }
@@ -434,104 +516,146 @@ fn parse_value(
     }
 }
 
-/// Creates a `Span` from a `tree_sitter::Node`.
-fn make_span(node: &Node, filename: &str) -> Span {
-    Span {
-        filename: filename.to_string(),
-        start: node.start_byte(),
-        end: node.end_byte(),
-    }
-}
-
-/// Creates a `Vec<Span>` from a `tree_sitter::Node`.
-fn make_span_vec(node: &Node, filename: &str) -> SpanSet {
-    SpanSet(vec![make_span(node, filename)])
+/// Parses a TOML integer or float, including `0x`, `0o` and `0b` integers and
+/// the special `inf` and `nan` floats.
+fn parse_toml_number(text: &str) -> Result<f64, String> {
+    let text = text.replace('_', "");
+    let radix = match text.get(..2) {
+        Some("0x") => 16,
+        Some("0o") => 8,
+        Some("0b") => 2,
+        _ => return text.parse::<f64>().map_err(|e| e.to_string()),
+    };
+    u64::from_str_radix(&text[2..], radix)
+        .map(|n| n as f64)
+        .map_err(|e| e.to_string())
 }
 
-/// A simple helper to remove quotes from TOML string literals.
-/// Also handles bare keys.
+/// Removes quotes from TOML string literals and processes escape sequences in
+/// basic strings. Literal strings are kept raw. Also handles bare keys.
 fn unquote_toml_string(text: &str) -> String {
-    if text.starts_with("\"\"\"") && text.ends_with("\"\"\"") {
-        return text[3..text.len() - 3].to_string();
+    if text.len() >= 6 && text.starts_with("\"\"\"") && text.ends_with("\"\"\"") {
+        return unescape_toml_string(trim_leading_newline(&text[3..text.len() - 3]));
     }
-    if text.starts_with("'''") && text.ends_with("'''") {
-        return text[3..text.len() - 3].to_string();
+    if text.len() >= 6 && text.starts_with("'''") && text.ends_with("'''") {
+        return trim_leading_newline(&text[3..text.len() - 3]).to_string();
     }
-    if text.starts_with('"') && text.ends_with('"') {
-        return text[1..text.len() - 1].to_string();
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        return unescape_toml_string(&text[1..text.len() - 1]);
     }
-    if text.starts_with('\'') && text.ends_with('\'') {
+    if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
         return text[1..text.len() - 1].to_string();
     }
     text.to_string()
 }
 
-/// Extract individual key spans from a table header node
-fn extract_individual_key_spans(
-    table_header_node: &Node,
-    source: &str,
-    filename: &str,
-    path: &[&str],
-) -> Vec<Span> {
-    // For a table header like [a.b.c], the second child (index 1) contains the key "a.b.c"
-    let key_node = match table_header_node.child(1) {
-        Some(node) => node,
-        None => {
-            return path
-                .iter()
-                .map(|_| make_span(table_header_node, filename))
-                .collect();
-        } // fallback
-    };
-
-    let key_text = match key_node.utf8_text(source.as_bytes()) {
-        Ok(text) => text,
-        Err(_) => {
-            return path
-                .iter()
-                .map(|_| make_span(table_header_node, filename))
-                .collect();
-        } // fallback
-    };
-
-    // Find the start position of the key node in the source
-    let key_start = key_node.start_byte();
-
-    // Split the key text and find positions of each part
-    let mut spans = Vec::new();
-    let mut current_pos = 0;
-
-    for &key_part in path {
-        // Find the key part in the key text starting from current position
-        if let Some(pos) = key_text[current_pos..].find(key_part) {
-            let absolute_pos = current_pos + pos;
-            let start = key_start + absolute_pos;
-            let end = start + key_part.len();
-
-            spans.push(Span {
-                filename: filename.to_string(),
-                start,
-                end,
-            });
+/// A newline immediately following the opening delimiter of a multi-line string
+/// is not part of the string.
+fn trim_leading_newline(text: &str) -> &str {
+    text.strip_prefix("\r\n")
+        .or_else(|| text.strip_prefix('\n'))
+        .unwrap_or(text)
+}
 
-            // Move position past this key part and the dot (if any)
-            current_pos = absolute_pos + key_part.len();
-            if current_pos < key_text.len() && key_text[current_pos..].starts_with('.') {
-                current_pos += 1; // skip the dot
+/// Processes the escape sequences of a basic string. Invalid escapes are kept as
+/// written.
+fn unescape_toml_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some('b') => result.push('\u{8}'),
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('f') => result.push('\u{c}'),
+            Some('r') => result.push('\r'),
+            Some('"') => result.push('"'),
+            Some('\\') => result.push('\\'),
+            Some(u @ ('u' | 'U')) => {
+                let len = if u == 'u' { 4 } else { 8 };
+                let hex: String = chars.clone().skip(1).take(len).collect();
+                let decoded = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .filter(|_| hex.len() == len);
+                let Some(decoded) = decoded else {
+                    result.push('\\');
+                    continue;
+                };
+                result.push(decoded);
+                for _ in 0..len {
+                    chars.next();
+                }
+            }
+            // A line ending backslash trims all whitespace up to the next
+            // non-whitespace character
+            Some(w) if w.is_whitespace() => {
+                let rest: String = chars.clone().take_while(|c| c.is_whitespace()).collect();
+                if !rest.contains('\n') {
+                    result.push('\\');
+                    continue;
+                }
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                continue;
+            }
+            _ => {
+                result.push('\\');
+                continue;
             }
-        } else {
-            // Fallback if we can't find the key part
-            spans.push(make_span(table_header_node, filename));
         }
+        chars.next();
     }
+    result
+}
 
-    spans
+/// Collects the keys of a dotted key, like `a`, `b` and `c.d` for `[a.b."c.d"]`
+/// or `a.b."c.d" = 1`. Each key is unquoted and spans its own text.
+fn header_keys(key_node: &Node, source: &str, filename: &str, out: &mut Vec<Spanned<String>>) {
+    if key_node.kind() == "dotted_key" {
+        let mut cursor = key_node.walk();
+        for child in key_node.named_children(&mut cursor) {
+            header_keys(&child, source, filename, out);
+        }
+    } else {
+        out.push(Spanned {
+            value: unquote_toml_string(key_node.utf8_text(source.as_bytes()).unwrap()),
+            annotation: make_span_vec(key_node, filename),
+        });
+    }
+}
+
+/// The keys of a table header joined back into a path, for messages.
+fn join_keys(keys: &[Spanned<String>]) -> String {
+    keys.iter()
+        .map(|key| key.value.as_str())
+        .collect::<Vec<_>>()
+        .join(".")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use deval_data_model::{Format, SpannedData};
+    use deval_data_model::{Annotated, Format, SpannedData};
+
+    #[test]
+    fn test_render_value() {
+        let parsed = Toml
+            .parse("a = { b = [1, \"x\"], \"c d\" = true }", "test.toml")
+            .unwrap();
+        let data = Annotated::<AnnotatedData>::from(parsed).discard_annotation();
+        let AnnotatedData::Object(pairs) = &data.value else {
+            panic!("expected a table");
+        };
+        assert_eq!(
+            Toml.render_value(&pairs[0].1.value).unwrap(),
+            "{ b = [1, \"x\"], \"c d\" = true }"
+        );
+        assert_eq!(Toml.render_value(&AnnotatedData::Null), None);
+    }
 
     #[test]
     fn test_parse_simple_key_value() {
@@ -585,6 +709,42 @@ height = 5.9"#;
         }
     }
 
+    #[test]
+    fn test_number_keeps_raw_text() {
+        let toml = "mask = 0x1F\nlimit = 1e6";
+        let parsed = Toml.parse(toml, "test.toml").expect("Failed to parse TOML");
+        let SpannedData::Object(pairs) = parsed.value else {
+            panic!("Expected object");
+        };
+        let numbers: Vec<String> = pairs
+            .iter()
+            .map(|(_, value)| match &value.value {
+                SpannedData::Number(n) => n.value.to_string(),
+                _ => panic!("Expected number value"),
+            })
+            .collect();
+        assert_eq!(numbers, ["0x1F", "1e6"]);
+        assert!(matches!(&pairs[0].1.value, SpannedData::Number(n) if n.value == 31.));
+    }
+
+    #[test]
+    fn test_parse_with_comments() {
+        let source = "# top\n[server]\nport = 80 # inline\nhosts = [\n  # inside\n  \"a\",\n]\n";
+        let (data, comments) = Toml.parse_with_comments(source, "test.toml").unwrap();
+        assert!(matches!(data.value, SpannedData::Object(_)));
+        let comments: Vec<_> = comments
+            .iter()
+            .map(|(span, text)| (span.start, span.end, text.as_str()))
+            .collect();
+        assert_eq!(
+            comments,
+            [(0, 5, "# top"), (25, 33, "# inline"), (46, 54, "# inside")]
+        );
+        for (start, end, text) in comments {
+            assert_eq!(&source[start..end], text);
+        }
+    }
+
     #[test]
     fn test_parse_booleans() {
         let toml = r#"is_active = true
@@ -843,6 +1003,18 @@ age = 30"#; // Unclosed string
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_syntax_error_spans() {
+        let toml = "name = \"app\"\nport = = 8080\n";
+        let errors = Toml.parse(toml, "test.toml").unwrap_err();
+        assert!(!errors.is_empty());
+        for error in &errors {
+            // Only the broken line is reported, not the whole document
+            assert!(error.span.start >= toml.find("port").unwrap(), "{error:?}");
+            assert!(error.span.end < toml.len(), "{error:?}");
+        }
+    }
+
     #[test]
     fn test_nested_table_key_spans() {
         let toml = r#"[a.b]
@@ -930,6 +1102,90 @@ y = 2"#;
         }
     }
 
+    /// The keys of the table at the top of the document, with their spans.
+    fn top_keys(data: &SpannedData) -> Vec<(String, usize, usize)> {
+        let SpannedData::Object(pairs) = data else {
+            panic!("Expected object");
+        };
+        pairs
+            .iter()
+            .map(|(k, _)| {
+                (
+                    k.value.clone(),
+                    k.annotation.0[0].start,
+                    k.annotation.0[0].end,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quoted_dotted_header_key() {
+        let toml = "[\"a.b\"]\nx = 1\n\n[c.\"d.e\".f]\ny = 2";
+        let parsed = Toml.parse(toml, "test.toml").expect("Failed to parse TOML");
+        assert_eq!(
+            top_keys(&parsed.value),
+            [("a.b".to_owned(), 1, 6), ("c".to_owned(), 16, 17)]
+        );
+        let SpannedData::Object(pairs) = &parsed.value else {
+            panic!("Expected object");
+        };
+        assert_eq!(top_keys(&pairs[1].1.value), [("d.e".to_owned(), 18, 23)]);
+        let SpannedData::Object(inner) = &pairs[1].1.value else {
+            panic!("Expected object");
+        };
+        assert_eq!(top_keys(&inner[0].1.value), [("f".to_owned(), 24, 25)]);
+    }
+
+    #[test]
+    fn test_dotted_pair_keys() {
+        let toml = "a.b = 1\na.\"c.d\" = 2\n\n[t]\nx.y = 3\nx.y = 4";
+        let errors = Toml.parse(toml, "test.toml").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Duplicate key 'x.y' in table 't'");
+
+        let parsed = Toml.parse(&toml[..toml.len() - 8], "test.toml").unwrap();
+        assert_eq!(
+            top_keys(&parsed.value),
+            [("a".to_owned(), 0, 1), ("t".to_owned(), 22, 23)]
+        );
+        let SpannedData::Object(pairs) = &parsed.value else {
+            panic!("Expected object");
+        };
+        // The table spans each key that extends it
+        assert_eq!(pairs[0].0.annotation.0.len(), 2);
+        assert_eq!(
+            top_keys(&pairs[0].1.value),
+            [("b".to_owned(), 2, 3), ("c.d".to_owned(), 10, 15)]
+        );
+        assert_eq!(top_keys(&pairs[1].1.value), [("x".to_owned(), 25, 26)]);
+
+        let errors = Toml.parse("a = 1\na.b = 2", "test.toml").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "Cannot define key 'a.b' because 'a' was already defined as a non-table."
+        );
+    }
+
+    #[test]
+    fn test_header_keys_that_are_substrings() {
+        let toml = "[ab.b]\nx = 1\n\n[[b.ab.b]]\ny = 2";
+        let parsed = Toml.parse(toml, "test.toml").expect("Failed to parse TOML");
+        assert_eq!(
+            top_keys(&parsed.value),
+            [("ab".to_owned(), 1, 3), ("b".to_owned(), 16, 17)]
+        );
+        let SpannedData::Object(pairs) = &parsed.value else {
+            panic!("Expected object");
+        };
+        assert_eq!(top_keys(&pairs[0].1.value), [("b".to_owned(), 4, 5)]);
+        assert_eq!(top_keys(&pairs[1].1.value), [("ab".to_owned(), 18, 20)]);
+        let SpannedData::Object(inner) = &pairs[1].1.value else {
+            panic!("Expected object");
+        };
+        assert_eq!(top_keys(&inner[0].1.value), [("b".to_owned(), 21, 22)]);
+    }
+
     #[test]
     fn test_array_table_key_spans() {
         let toml = r#"[[products]]
@@ -1017,4 +1273,161 @@ key = "value""#;
             _ => panic!("Expected object"),
         }
     }
+
+    #[test]
+    fn test_sub_table_of_array_table() {
+        let toml = r#"[[servers]]
+name = "a"
+
+[[servers]]
+name = "b"
+
+[servers.config]
+port = 80
+
+[servers.config.tls]
+enabled = true
+"#;
+        let parsed = Toml.parse(toml, "test.toml").expect("Failed to parse TOML");
+        let SpannedData::Object(pairs) = parsed.value else {
+            panic!("Expected object");
+        };
+        // The sub-tables don't add top level keys
+        assert_eq!(pairs.len(), 1);
+        let SpannedData::Array(servers) = &pairs[0].1.value else {
+            panic!("Expected array for 'servers' value");
+        };
+        assert_eq!(servers.len(), 2);
+        let SpannedData::Object(first) = &servers[0].value else {
+            panic!("Expected object for array element");
+        };
+        assert_eq!(first.len(), 1);
+        let SpannedData::Object(last) = &servers[1].value else {
+            panic!("Expected object for array element");
+        };
+        assert_eq!(last.len(), 2);
+        assert_eq!(last[1].0.value, "config");
+        // The span of 'config' is in its own header, not an array header
+        let span_config = &last[1].0.annotation.0[0];
+        assert_eq!(
+            &toml[span_config.start..span_config.end],
+            "config",
+            "{span_config:?}"
+        );
+        assert_eq!(span_config.start, toml.find("config").unwrap());
+        let SpannedData::Object(config) = &last[1].1.value else {
+            panic!("Expected object for 'config' value");
+        };
+        assert_eq!(config[0].0.value, "port");
+        assert_eq!(config[1].0.value, "tls");
+        let span_tls = &config[1].0.annotation.0[0];
+        assert_eq!(span_tls.start, toml.find("tls").unwrap());
+
+        // Every header naming 'servers' adds its span to the array key
+        let servers_spans: Vec<_> = pairs[0].0.annotation.0.iter().map(|s| s.start).collect();
+        let servers_offsets: Vec<_> = toml.match_indices("servers").map(|(i, _)| i).collect();
+        assert_eq!(servers_spans, servers_offsets);
+    }
+
+    #[test]
+    fn test_nested_array_table_in_array_table() {
+        let toml = r#"[[fruits]]
+name = "apple"
+
+[[fruits.varieties]]
+name = "red delicious"
+
+[[fruits]]
+name = "banana"
+
+[[fruits.varieties]]
+name = "plantain"
+"#;
+        let parsed = Toml.parse(toml, "test.toml").expect("Failed to parse TOML");
+        let SpannedData::Object(pairs) = parsed.value else {
+            panic!("Expected object");
+        };
+        let SpannedData::Array(fruits) = &pairs[0].1.value else {
+            panic!("Expected array for 'fruits' value");
+        };
+        assert_eq!(fruits.len(), 2);
+        for (fruit, variety) in fruits.iter().zip(["red delicious", "plantain"]) {
+            let SpannedData::Object(fields) = &fruit.value else {
+                panic!("Expected object for array element");
+            };
+            let SpannedData::Array(varieties) = &fields[1].1.value else {
+                panic!("Expected array for 'varieties' value");
+            };
+            assert_eq!(varieties.len(), 1);
+            let SpannedData::Object(variety_fields) = &varieties[0].value else {
+                panic!("Expected object for array element");
+            };
+            let SpannedData::String(name) = &variety_fields[0].1.value else {
+                panic!("Expected string for 'name' value");
+            };
+            assert_eq!(name.value, variety);
+        }
+    }
+
+    #[test]
+    fn test_table_under_inline_array() {
+        // Only arrays of tables take sub-tables, not arrays written as values
+        let toml = "a = [{ y = 1 }]\n\n[a.b]\nx = 2\n";
+        let errors = Toml.parse(toml, "test.toml").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span.start, toml.find("[a.b]").unwrap());
+    }
+
+    #[test]
+    fn test_parse_escaped_and_multiline_strings() {
+        let toml = "a = \"tab\\there\"\nb = \"\"\"\nline one\nline two\"\"\"\nc = 'C:\\path'";
+        let parsed = Toml.parse(toml, "test.toml").expect("Failed to parse TOML");
+
+        match parsed.value {
+            SpannedData::Object(pairs) => {
+                let values: Vec<_> = pairs
+                    .iter()
+                    .map(|(_, v)| match &v.value {
+                        SpannedData::String(s) => s.value.clone(),
+                        _ => panic!("Expected string value"),
+                    })
+                    .collect();
+                assert_eq!(values, ["tab\there", "line one\nline two", "C:\\path"]);
+
+                // The span still covers the quotes
+                let span = &pairs[0].1.annotation.0[0];
+                assert_eq!(&toml[span.start..span.end], "\"tab\\there\"");
+            }
+            _ => panic!("Expected object"),
+        }
+    }
+
+    #[test]
+    fn test_unquote_toml_string() {
+        assert_eq!(unquote_toml_string(r#""a\nb\\c\"d""#), "a\nb\\c\"d");
+        assert_eq!(unquote_toml_string(r#""\u00e9\U0001F600""#), "é😀");
+        assert_eq!(unquote_toml_string(r#""bad \q escape""#), "bad \\q escape");
+        assert_eq!(
+            unquote_toml_string("\"\"\"\r\nThe quick \\\n    brown fox\"\"\""),
+            "The quick brown fox"
+        );
+        assert_eq!(unquote_toml_string("'''\nraw \\n'''"), "raw \\n");
+        assert_eq!(unquote_toml_string(r"'raw \t'"), r"raw \t");
+        assert_eq!(unquote_toml_string("bare_key"), "bare_key");
+    }
+
+    #[test]
+    fn test_parse_toml_number() {
+        assert_eq!(parse_toml_number("0xdead_beef"), Ok(3735928559.));
+        assert_eq!(parse_toml_number("0o777"), Ok(511.));
+        assert_eq!(parse_toml_number("0b1101"), Ok(13.));
+        assert_eq!(parse_toml_number("1_000"), Ok(1000.));
+        assert_eq!(parse_toml_number("-3.5e2"), Ok(-350.));
+        assert_eq!(parse_toml_number("inf"), Ok(f64::INFINITY));
+        assert_eq!(parse_toml_number("+inf"), Ok(f64::INFINITY));
+        assert_eq!(parse_toml_number("-inf"), Ok(f64::NEG_INFINITY));
+        assert!(parse_toml_number("nan").unwrap().is_nan());
+        assert!(parse_toml_number("-nan").unwrap().is_nan());
+        assert!(parse_toml_number("0xZZ").is_err());
+    }
 }
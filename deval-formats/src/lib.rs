@@ -0,0 +1,68 @@
+//! The formats deval reads, found by the extensions each `Format` declares.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use deval_data_model::Format;
+use deval_format_env::EnvFile;
+use deval_format_ini::Ini;
+use deval_format_json::{Json, JsonLines};
+use deval_format_ron::Ron;
+use deval_format_toml::Toml;
+
+/// Every supported format. Adding a format only needs an entry here.
+pub fn formats() -> Vec<Arc<dyn Format>> {
+    vec![
+        Arc::new(Json),
+        Arc::new(JsonLines),
+        Arc::new(Toml),
+        Arc::new(EnvFile),
+        Arc::new(Ini),
+        Arc::new(Ron),
+    ]
+}
+
+/// The format called `name`, like `toml`.
+pub fn format_by_name(name: &str) -> Option<Arc<dyn Format>> {
+    formats().into_iter().find(|format| format.name() == name)
+}
+
+/// The parser of the format of `path`, from its extension. A dotfile without
+/// an extension, like `.env`, is taken by its name.
+pub fn format_for_path(path: &Path) -> Option<Arc<dyn Format>> {
+    let extension = match path.extension() {
+        Some(extension) => extension.to_str()?,
+        None => path.file_name()?.to_str()?.strip_prefix('.')?,
+    };
+    formats()
+        .into_iter()
+        .find(|format| format.extensions().contains(&extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_for_path() {
+        let extensions =
+            |path: &str| format_for_path(Path::new(path)).map(|f| f.extensions().join(" "));
+        assert_eq!(extensions("a/config.json").as_deref(), Some("json"));
+        assert_eq!(extensions("logs.ndjson").as_deref(), Some("jsonl ndjson"));
+        assert_eq!(extensions("Cargo.toml").as_deref(), Some("toml"));
+        assert_eq!(extensions("prod.env").as_deref(), Some("env"));
+        assert_eq!(extensions(".env").as_deref(), Some("env"));
+        assert_eq!(extensions("app.ron").as_deref(), Some("ron"));
+        assert!(extensions("notes.txt").is_none());
+        assert!(extensions("Makefile").is_none());
+    }
+
+    #[test]
+    fn test_format_by_name() {
+        for format in formats() {
+            let found = format_by_name(format.name()).unwrap();
+            assert_eq!(found.extensions(), format.extensions());
+        }
+        assert!(format_by_name("yaml").is_none());
+    }
+}
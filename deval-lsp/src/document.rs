@@ -1,26 +1,58 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use deval_data_model::{Annotated, AnnotatedData, Format};
-use deval_validator::Validator;
+use deval_data_model::{
+    Annotated, AnnotatedData, Format, ParseError, Span, SpanSet, Spanned, SpannedData,
+};
+use deval_validator::{
+    Severity, ValidationError, ValidationErrorKind, ValidationResult, Validator,
+};
 use line_index::LineIndex;
 
 pub mod token_store;
 pub use token_store::TokenStore;
 
 pub struct Document {
+    /// The tree of the current text, or `None` if it doesn't parse
     pub annotated: Option<Annotated<AnnotatedData>>,
+    /// The tree of the last text that parsed. Tokens and hover are served from it
+    /// while the current text has parse errors.
+    pub last_good: Option<Annotated<AnnotatedData>>,
     pub line_index: LineIndex,
     pub token_store: TokenStore,
+    /// The line index of the text the tokens were built from, which is older
+    /// than `line_index` while the current text has parse errors
+    pub token_line_index: LineIndex,
+    pub parse_errors: Vec<ParseError>,
+    pub validation_errors: Vec<ValidationError>,
+    /// The schema file this document is validated against, if any
+    pub schema_path: Option<PathBuf>,
+    /// The path of the document, used as the filename of its spans
+    filename: String,
+    text: String,
     format: Arc<dyn Format>,
     schema: Arc<dyn Validator>,
 }
 
 impl Document {
-    pub fn new(text: &str, format: Arc<dyn Format>, schema: Arc<dyn Validator>) -> Self {
+    pub fn new(
+        text: &str,
+        filename: &str,
+        format: Arc<dyn Format>,
+        schema: Arc<dyn Validator>,
+        schema_path: Option<PathBuf>,
+    ) -> Self {
         let mut this = Self {
             line_index: LineIndex::new(""),
             annotated: None,
+            last_good: None,
             token_store: TokenStore::new(),
+            token_line_index: LineIndex::new(""),
+            parse_errors: vec![],
+            validation_errors: vec![],
+            schema_path,
+            filename: filename.to_owned(),
+            text: String::new(),
             format,
             schema,
         };
@@ -29,18 +61,661 @@ impl Document {
     }
 
     pub fn update_text(&mut self, text: &str) {
+        self.text = text.to_owned();
         self.line_index = LineIndex::new(text);
-        let parsed = match self.format.parse(text, "") {
+        self.validation_errors = vec![];
+        let (validated, comments) = match self.validate(text) {
             Ok(v) => v,
-            Err(_) => {
+            Err(errors) => {
+                self.parse_errors = errors;
                 self.annotated = None;
                 return;
             }
         };
-        let annotated = self.schema.validate(parsed).result;
-        self.annotated = Some(annotated.clone());
+        self.parse_errors = vec![];
+        self.validation_errors = validated.errors;
+        let mut annotated = validated.result;
+        attach_comment_docs(&mut annotated.value, text, &comments);
 
         // Update the token store with the new annotated data
         self.token_store.build_from_annotated(&annotated);
+        self.token_line_index = self.line_index.clone();
+        self.annotated = Some(annotated.clone());
+        self.last_good = Some(annotated);
+    }
+
+    /// Parses `text` and validates it against the schema, returning the result
+    /// with the comments of the text. Each document of a file with several, like
+    /// JSON Lines, is validated on its own, and the result is an array of them.
+    #[allow(clippy::type_complexity)]
+    fn validate(
+        &self,
+        text: &str,
+    ) -> Result<(ValidationResult, Vec<(Span, String)>), Vec<ParseError>> {
+        let mut documents = vec![];
+        let mut parse_errors = vec![];
+        let (parsed, comments) = self.format.parse_many(text, &self.filename);
+        for document in parsed {
+            match document {
+                Ok(document) => documents.push(document),
+                Err(errors) => parse_errors.extend(errors),
+            }
+        }
+        if !parse_errors.is_empty() {
+            return Err(parse_errors);
+        }
+        if let [_] = &documents[..] {
+            return Ok((self.schema.validate(documents.pop().unwrap()), comments));
+        }
+        let mut errors = vec![];
+        let mut results = vec![];
+        for document in documents {
+            let validated = self.schema.validate(document);
+            errors.extend(validated.errors);
+            results.push(validated.result);
+        }
+        let mut result: Annotated<AnnotatedData> = Spanned {
+            value: SpannedData::Array(vec![]),
+            annotation: SpanSet(vec![Span {
+                filename: self.filename.clone(),
+                start: 0,
+                end: text.len(),
+            }]),
+        }
+        .into();
+        result.value = AnnotatedData::Array(results);
+        Ok((ValidationResult { result, errors }, comments))
+    }
+
+    /// Replaces the schema and validates the current text against it.
+    pub fn set_schema(&mut self, schema: Arc<dyn Validator>) {
+        self.schema = schema;
+        let text = std::mem::take(&mut self.text);
+        self.update_text(&text);
+    }
+
+    /// The schema type of each literal value that matched it, along with the offset
+    /// of the end of the value, where the type is shown as an inlay hint. Values
+    /// validated against `any`, including every value of a document without a
+    /// schema, get no hint.
+    pub fn inlay_hints(&self) -> Vec<(usize, String)> {
+        fn go(
+            value: &Annotated<AnnotatedData>,
+            errors: &[ValidationError],
+            hints: &mut Vec<(usize, String)>,
+        ) {
+            match &value.value {
+                AnnotatedData::Array(items) => {
+                    for item in items {
+                        go(item, errors, hints);
+                    }
+                }
+                AnnotatedData::Object(items) => {
+                    for (_, item) in items {
+                        go(item, errors, hints);
+                    }
+                }
+                _ => {
+                    let Some(schema_type) = &value.annotation.schema_type else {
+                        return;
+                    };
+                    // Defaults inserted by the schema aren't in the text
+                    let Some(span) = value.annotation.span.all().first() else {
+                        return;
+                    };
+                    let failed = errors.iter().any(|e| {
+                        e.severity == Severity::Error
+                            && e.span.start >= span.start
+                            && e.span.end <= span.end
+                    });
+                    if schema_type != "any" && !failed {
+                        hints.push((span.end, schema_type.clone()));
+                    }
+                }
+            }
+        }
+        let mut hints = vec![];
+        if let Some(annotated) = &self.annotated {
+            go(annotated, &self.validation_errors, &mut hints);
+        }
+        hints
+    }
+
+    /// The position and type of the array element at `offset`, like `element 1 of
+    /// [string, number]: number`, if it is a literal directly inside an array
+    /// which was validated against a schema.
+    pub fn array_element_at(&self, offset: usize) -> Option<String> {
+        fn contains(value: &Annotated<AnnotatedData>, offset: usize) -> bool {
+            value
+                .annotation
+                .span
+                .all()
+                .iter()
+                .any(|span| span.start <= offset && offset <= span.end)
+        }
+        fn go(value: &Annotated<AnnotatedData>, offset: usize) -> Option<String> {
+            match &value.value {
+                AnnotatedData::Array(items) => {
+                    let (index, item) = items
+                        .iter()
+                        .enumerate()
+                        .find(|(_, item)| contains(item, offset))?;
+                    if matches!(
+                        item.value,
+                        AnnotatedData::Array(_) | AnnotatedData::Object(_)
+                    ) {
+                        return go(item, offset);
+                    }
+                    let array_type = value.annotation.schema_type.as_ref()?;
+                    let item_type = item.annotation.schema_type.as_ref()?;
+                    Some(format!("element {index} of {array_type}: {item_type}"))
+                }
+                AnnotatedData::Object(pairs) => pairs
+                    .iter()
+                    .find(|(_, v)| contains(v, offset))
+                    .and_then(|(_, v)| go(v, offset)),
+                _ => None,
+            }
+        }
+        go(self.last_good.as_ref()?, offset)
+    }
+
+    /// The byte ranges of the object key at `offset`, without the quotes of a
+    /// quoted key. A key has more than one range when it is written more than
+    /// once, like a TOML table extended by a dotted key.
+    pub fn key_ranges_at(&self, offset: usize) -> Option<Vec<(usize, usize)>> {
+        let (key, _) = key_at(&self.annotated.as_ref()?.value, offset)?;
+        let ranges = key
+            .annotation
+            .span
+            .all()
+            .iter()
+            .map(|span| {
+                let text = &self.text[span.start..span.end];
+                let quoted = text.len() >= 2
+                    && (text.starts_with('"') && text.ends_with('"')
+                        || text.starts_with('\'') && text.ends_with('\''));
+                match quoted {
+                    true => (span.start + 1, span.end - 1),
+                    false => (span.start, span.end),
+                }
+            })
+            .collect();
+        Some(ranges)
+    }
+
+    /// The edits renaming the object key at `offset` to `new_name`. The new name
+    /// must not be taken by a sibling key, and must be allowed by the schema.
+    pub fn rename_key(&self, offset: usize, new_name: &str) -> Result<Vec<(usize, usize)>, String> {
+        let annotated = self
+            .annotated
+            .as_ref()
+            .ok_or("Can't rename in a document with syntax errors")?;
+        let (key, siblings) = key_at(&annotated.value, offset).ok_or("Only keys can be renamed")?;
+        // Keys filled in from schema defaults aren't in the text, so they don't collide
+        let collides = siblings.iter().any(|(k, _)| {
+            k.value == new_name && !std::ptr::eq(k, key) && !k.annotation.span.all().is_empty()
+        });
+        if collides {
+            return Err(format!("Key `{new_name}` already exists"));
+        }
+        let ranges = self.key_ranges_at(offset).unwrap_or_default();
+
+        let mut text = self.text.clone();
+        for &(start, end) in ranges.iter().rev() {
+            text.replace_range(start..end, new_name);
+        }
+        let (validated, _) = self
+            .validate(&text)
+            .map_err(|_| format!("`{new_name}` is not a valid key"))?;
+        let unknown_key = |errors: &[ValidationError]| {
+            errors
+                .iter()
+                .filter(|e| {
+                    e.kind == ValidationErrorKind::UnknownKey
+                        && e.path.last().is_some_and(|key| key == new_name)
+                })
+                .count()
+        };
+        if unknown_key(&validated.errors) > unknown_key(&self.validation_errors) {
+            return Err(format!("The schema doesn't allow the key `{new_name}`"));
+        }
+        Ok(ranges)
+    }
+
+    /// The offset and text inserting the key of a missing key error into its
+    /// object. The key goes after the last key written with its value on the
+    /// same line, copying its separator, indentation and quoting. An empty
+    /// object gets the key just before its closing bracket. `None` if the object
+    /// has no such key to copy the layout of, or the schema has no value for the
+    /// key which the format can write.
+    pub fn missing_key_insertion(&self, error: &ValidationError) -> Option<(usize, String)> {
+        let ValidationErrorKind::MissingKey { key, placeholder } = &error.kind else {
+            return None;
+        };
+        let pairs = object_at(self.annotated.as_ref()?, &error.span)?;
+        let text = &self.text;
+        if pairs.is_empty() {
+            let object = &text[error.span.start..error.span.end];
+            let object = object.trim_end();
+            if !object.ends_with(['}', ')']) {
+                return None;
+            }
+            // Let the format write the key in an object of its own, and take
+            // the entry out of it
+            let pair = (
+                Annotated {
+                    value: key.clone(),
+                    annotation: (),
+                },
+                Annotated {
+                    value: placeholder.clone()?,
+                    annotation: (),
+                },
+            );
+            let rendered = self
+                .format
+                .render_value(&AnnotatedData::Object(vec![pair]))?;
+            let entry = rendered.get(1..rendered.len() - 1)?.trim();
+            return Some((error.span.start + object.len() - 1, entry.to_owned()));
+        }
+        let placeholder = self.format.render_value(placeholder.as_ref()?)?;
+        let (last_key, last_value) = pairs
+            .iter()
+            .filter_map(|(k, v)| {
+                Some((
+                    k.annotation.span.all().first()?,
+                    v.annotation.span.all().first()?,
+                ))
+            })
+            .filter(|(k, v)| k.end <= v.start && !text[k.end..v.start].contains('\n'))
+            .max_by_key(|(k, _)| k.start)?;
+
+        let separator = &text[last_key.end..last_value.start];
+        let is_bare = key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        let new_key = match text[last_key.start..].starts_with('"') || !is_bare {
+            true => format!("{key:?}"),
+            false => key.clone(),
+        };
+        let line_start = text[..last_key.start].rfind('\n').map_or(0, |i| i + 1);
+        let indent: String = text[line_start..last_key.start]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .collect();
+        let object_end = error.span.end.max(last_value.end);
+        let rest = &text[last_value.end..object_end];
+        // Objects closed by a bracket separate their keys with commas
+        let closed = rest.trim_end().ends_with(['}', ')']);
+        let multiline = !closed || text[error.span.start..last_key.start].contains('\n');
+        let entry = match multiline {
+            true => format!("\n{indent}{new_key}{separator}{placeholder}"),
+            false => format!(" {new_key}{separator}{placeholder}"),
+        };
+        let trailing_comma = rest.trim_start().starts_with(',');
+        Some(match (closed, trailing_comma) {
+            (true, true) => (last_value.end + rest.find(',').unwrap() + 1, entry + ","),
+            (true, false) => (last_value.end, format!(",{entry}")),
+            (false, _) => (last_value.end, entry),
+        })
+    }
+}
+
+/// The pairs of the object spanning exactly `span`.
+fn object_at<'a>(data: &'a Annotated<AnnotatedData>, span: &Span) -> Option<&'a [Pair]> {
+    match &data.value {
+        AnnotatedData::Object(pairs) if data.annotation.span.all().first() == Some(span) => {
+            Some(pairs)
+        }
+        AnnotatedData::Object(pairs) => pairs.iter().find_map(|(_, v)| object_at(v, span)),
+        AnnotatedData::Array(items) => items.iter().find_map(|item| object_at(item, span)),
+        _ => None,
+    }
+}
+
+type Pair = (Annotated<String>, Annotated<AnnotatedData>);
+
+/// The object key whose span contains `offset`, along with its sibling keys.
+fn key_at(data: &AnnotatedData, offset: usize) -> Option<(&Annotated<String>, &[Pair])> {
+    match data {
+        AnnotatedData::Array(items) => items.iter().find_map(|item| key_at(&item.value, offset)),
+        AnnotatedData::Object(items) => {
+            let key = items.iter().map(|(k, _)| k).find(|k| {
+                k.annotation
+                    .span
+                    .all()
+                    .iter()
+                    .any(|span| span.start <= offset && offset <= span.end)
+            });
+            match key {
+                Some(key) => Some((key, items)),
+                None => items.iter().find_map(|(_, v)| key_at(&v.value, offset)),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Documents keys which have no docs from the schema with the line comments
+/// right above them, given in order with their markers. Only keys starting
+/// their line have them, so keys later on the line get none.
+fn attach_comment_docs(data: &mut AnnotatedData, text: &str, comments: &[(Span, String)]) {
+    match data {
+        AnnotatedData::Array(items) => {
+            for item in items {
+                attach_comment_docs(&mut item.value, text, comments);
+            }
+        }
+        AnnotatedData::Object(items) => {
+            for (key, value) in items {
+                if key.annotation.docs.is_empty()
+                    && let Some(span) = key.annotation.span.all().first()
+                    && let Some(docs) = comment_docs(text, comments, span.start)
+                {
+                    key.annotation.docs = docs;
+                }
+                attach_comment_docs(&mut value.value, text, comments);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The text of the line comments on the lines right above `offset`, without
+/// their markers.
+fn comment_docs(text: &str, comments: &[(Span, String)], offset: usize) -> Option<String> {
+    let mut lines = vec![];
+    let mut next = offset;
+    let above = comments.partition_point(|(span, _)| span.end <= offset);
+    for (span, comment) in comments[..above].iter().rev() {
+        let between = &text[span.end..next];
+        let line_start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        if !between.trim().is_empty()
+            || between.matches('\n').count() != 1
+            || !text[line_start..span.start].trim().is_empty()
+            || comment.starts_with("/*")
+        {
+            break;
+        }
+        let comment = comment.trim_start_matches(['#', ';', '/']);
+        lines.push(comment.strip_prefix(' ').unwrap_or(comment).trim_end());
+        next = span.start;
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deval_data_model::{SemanticType, Span, SpanSet, Spanned, SpannedData};
+    use deval_validator::AnyValidator;
+
+    /// Parses the whole text as a string, failing if it contains `!`
+    struct StringFormat;
+
+    impl Format for StringFormat {
+        fn parse(
+            &self,
+            source: &str,
+            filename: &str,
+        ) -> Result<Spanned<SpannedData>, Vec<ParseError>> {
+            let span = |start, end| Span {
+                filename: filename.to_owned(),
+                start,
+                end,
+            };
+            if let Some(pos) = source.find('!') {
+                return Err(vec![ParseError {
+                    message: "Unexpected `!`".to_owned(),
+                    span: span(pos, pos + 1),
+                }]);
+            }
+            Ok(Spanned {
+                value: SpannedData::String(Spanned {
+                    value: source.to_owned(),
+                    annotation: SpanSet(vec![span(0, source.len())]),
+                }),
+                annotation: SpanSet(vec![span(0, source.len())]),
+            })
+        }
+
+        fn name(&self) -> &'static str {
+            "string"
+        }
+    }
+
+    /// Parses `key value` lines into an object of number values
+    struct PairsFormat;
+
+    impl Format for PairsFormat {
+        fn parse(
+            &self,
+            source: &str,
+            filename: &str,
+        ) -> Result<Spanned<SpannedData>, Vec<ParseError>> {
+            let span = |start, end| {
+                SpanSet(vec![Span {
+                    filename: filename.to_owned(),
+                    start,
+                    end,
+                }])
+            };
+            let mut pairs = vec![];
+            let mut offset = 0;
+            for line in source.split('\n') {
+                let (key, value) = line.split_once(' ').unwrap();
+                let value_start = offset + key.len() + 1;
+                let value_span = span(value_start, value_start + value.len());
+                pairs.push((
+                    Spanned {
+                        value: key.to_owned(),
+                        annotation: span(offset, offset + key.len()),
+                    },
+                    Spanned {
+                        value: SpannedData::Number(Spanned {
+                            value: value.parse::<f64>().unwrap().into(),
+                            annotation: value_span.clone(),
+                        }),
+                        annotation: value_span,
+                    },
+                ));
+                offset += line.len() + 1;
+            }
+            Ok(Spanned {
+                value: SpannedData::Object(pairs),
+                annotation: span(0, source.len()),
+            })
+        }
+
+        fn name(&self) -> &'static str {
+            "pairs"
+        }
+    }
+
+    #[test]
+    fn test_inlay_hints() {
+        let schema = deval_schema::compile("{ a: integer, b: any, c: integer }").unwrap();
+        let doc = Document::new(
+            "a 1\nb 2\nc 3.5",
+            "/test.txt",
+            Arc::new(PairsFormat),
+            Arc::from(schema),
+            None,
+        );
+        assert_eq!(doc.inlay_hints(), [(3, "integer".to_owned())]);
+    }
+
+    #[test]
+    fn test_no_inlay_hints_for_defaults() {
+        let schema = deval_schema::compile("{ a: integer, b?: integer = 2 }").unwrap();
+        let doc = Document::new(
+            "a 1",
+            "/test.txt",
+            Arc::new(PairsFormat),
+            Arc::from(schema),
+            None,
+        );
+        assert_eq!(doc.inlay_hints(), [(3, "integer".to_owned())]);
+    }
+
+    #[test]
+    fn test_json_lines_validates_each_line() {
+        let schema = deval_schema::compile("{ port: number }").unwrap();
+        let doc = Document::new(
+            "{\"port\": 80}\n{\"port\": \"x\"}\n",
+            "/test.jsonl",
+            Arc::new(deval_format_json::JsonLines),
+            Arc::from(schema),
+            None,
+        );
+        let errors: Vec<_> = doc.validation_errors.iter().map(|e| &e.text).collect();
+        assert_eq!(errors, ["Expected Number, found String"]);
+        assert_eq!(doc.inlay_hints(), [(11, "number".to_owned())]);
+    }
+
+    #[test]
+    fn test_no_inlay_hints_without_schema() {
+        let doc = Document::new(
+            "a 1\nb 2",
+            "/test.txt",
+            Arc::new(PairsFormat),
+            Arc::new(AnyValidator),
+            None,
+        );
+        assert!(doc.annotated.is_some());
+        assert!(doc.inlay_hints().is_empty());
+    }
+
+    #[test]
+    fn test_comment_docs_on_keys() {
+        let text = "# this is the port\nport = 8080\n[server]\n; the host\nhost = a";
+        let doc = Document::new(
+            text,
+            "/test.ini",
+            Arc::new(deval_format_ini::Ini),
+            Arc::new(AnyValidator),
+            None,
+        );
+        let docs_at = |key| {
+            let token = doc
+                .token_store
+                .smallest_token_containing(text.find(key).unwrap())
+                .unwrap();
+            assert!(matches!(token.token_type, SemanticType::Key));
+            token.docs.clone()
+        };
+        assert_eq!(docs_at("port ="), "this is the port");
+        assert_eq!(docs_at("host ="), "the host");
+        assert_eq!(docs_at("server]"), "");
+
+        // Docs from the schema take precedence
+        let schema =
+            deval_schema::compile("{\n    /// From the schema\n    port: integer,\n    ..\n}")
+                .unwrap();
+        let doc = Document::new(
+            text,
+            "/test.ini",
+            Arc::new(deval_format_ini::Ini),
+            Arc::from(schema),
+            None,
+        );
+        let token = doc.token_store.smallest_token_containing(20).unwrap();
+        assert_eq!(token.docs.trim(), "From the schema");
+    }
+
+    #[test]
+    fn test_comment_docs() {
+        let text = "a = 1\n\n# The port\n#   to listen on\nport = 80\nb = 2 # two\nc = 3\n# A table\nd = { e = 4 }";
+        let (_, comments) = deval_format_toml::Toml
+            .parse_with_comments(text, "test.toml")
+            .unwrap();
+        let docs = |key| comment_docs(text, &comments, text.find(key).unwrap());
+        assert_eq!(docs("port =").as_deref(), Some("The port\n  to listen on"));
+        assert_eq!(docs("a ="), None);
+        assert_eq!(docs("b ="), None);
+        // A comment at the end of the line above doesn't document the key
+        assert_eq!(docs("c ="), None);
+        assert_eq!(docs("d =").as_deref(), Some("A table"));
+        assert_eq!(docs("e ="), None);
+    }
+
+    #[test]
+    fn test_toml_comment_docs_on_keys() {
+        let text = "# The server\nserver = { host = \"a\", port = 1 }\n\n# The name\nname = \"x\"";
+        let doc = Document::new(
+            text,
+            "/test.toml",
+            Arc::new(deval_format_toml::Toml),
+            Arc::new(AnyValidator),
+            None,
+        );
+        let docs_at = |key| {
+            let token = doc
+                .token_store
+                .smallest_token_containing(text.find(key).unwrap())
+                .unwrap();
+            assert!(matches!(token.token_type, SemanticType::Key));
+            token.docs.clone()
+        };
+        assert_eq!(docs_at("server ="), "The server");
+        assert_eq!(docs_at("name ="), "The name");
+        // Keys of an inline table don't take the comment above its line
+        assert_eq!(docs_at("host ="), "");
+        assert_eq!(docs_at("port ="), "");
+    }
+
+    #[test]
+    fn test_tokens_survive_parse_errors() {
+        let mut doc = Document::new(
+            "hello",
+            "/test.txt",
+            Arc::new(StringFormat),
+            Arc::new(AnyValidator),
+            None,
+        );
+        assert!(doc.parse_errors.is_empty());
+        assert_eq!(doc.token_store.all_tokens().len(), 1);
+
+        doc.update_text("hello!");
+        assert_eq!(doc.parse_errors.len(), 1);
+        assert!(doc.annotated.is_none());
+        assert!(doc.last_good.is_some());
+        let token = doc.token_store.smallest_token_containing(2).unwrap();
+        assert_eq!((token.start, token.end), (0, 5));
+        assert!(matches!(token.token_type, SemanticType::String));
+
+        // Broken text shorter than the tokens keeps the index they were built with
+        doc.update_text("!");
+        assert_eq!(doc.token_store.all_tokens()[0].end, 5);
+        assert_eq!(u32::from(doc.token_line_index.len()), 5);
+
+        doc.update_text("hi");
+        assert!(doc.parse_errors.is_empty());
+        assert!(doc.annotated.is_some());
+        assert_eq!(doc.token_store.all_tokens()[0].end, 2);
+    }
+
+    #[test]
+    fn test_spans_carry_filename() {
+        let mut doc = Document::new(
+            "hello",
+            "/project/config.txt",
+            Arc::new(StringFormat),
+            Arc::new(AnyValidator),
+            None,
+        );
+        let annotated = doc.annotated.as_ref().unwrap();
+        assert_eq!(
+            annotated.annotation.span.primary().unwrap().filename,
+            "/project/config.txt"
+        );
+
+        doc.update_text("hello!");
+        assert_eq!(doc.parse_errors[0].span.filename, "/project/config.txt");
     }
 }
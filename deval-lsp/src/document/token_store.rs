@@ -8,15 +8,27 @@ pub struct SemanticToken {
     pub end: usize,
     pub token_type: SemanticType,
     pub docs: String,
+    pub schema_type: Option<String>,
+    /// Whether this token is a key that the schema marks as optional
+    pub optional: bool,
 }
 
 impl SemanticToken {
-    pub fn new(start: usize, end: usize, token_type: SemanticType, docs: String) -> Self {
+    pub fn new(
+        start: usize,
+        end: usize,
+        token_type: SemanticType,
+        docs: String,
+        schema_type: Option<String>,
+        optional: bool,
+    ) -> Self {
         Self {
             start,
             end,
             token_type,
             docs,
+            schema_type,
+            optional,
         }
     }
 
@@ -87,6 +99,8 @@ impl TokenStore {
                         span.end,
                         token_type,
                         annotation.docs.clone(),
+                        annotation.schema_type.clone(),
+                        annotation.optional,
                     ));
                 }
             }
@@ -124,3 +138,66 @@ impl TokenStore {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deval_data_model::{Span, SpanSet, Spanned, SpannedData};
+
+    fn spans(start: usize, end: usize) -> SpanSet {
+        SpanSet(vec![Span {
+            filename: "test".to_owned(),
+            start,
+            end,
+        }])
+    }
+
+    #[test]
+    fn test_bool_and_null_tokens() {
+        // {"a": true, "b": null}
+        let data: Spanned<SpannedData> = Spanned {
+            value: SpannedData::Object(vec![
+                (
+                    Spanned {
+                        value: "a".to_owned(),
+                        annotation: spans(1, 4),
+                    },
+                    Spanned {
+                        value: SpannedData::Bool(Spanned {
+                            value: true,
+                            annotation: spans(6, 10),
+                        }),
+                        annotation: spans(6, 10),
+                    },
+                ),
+                (
+                    Spanned {
+                        value: "b".to_owned(),
+                        annotation: spans(12, 15),
+                    },
+                    Spanned {
+                        value: SpannedData::Null,
+                        annotation: spans(17, 21),
+                    },
+                ),
+            ]),
+            annotation: spans(0, 22),
+        };
+        let mut store = TokenStore::new();
+        store.build_from_annotated(&data.into());
+        let tokens: Vec<_> = store
+            .all_tokens()
+            .iter()
+            .map(|t| (t.start, t.token_type))
+            .collect();
+        assert!(matches!(
+            tokens[..],
+            [
+                (1, SemanticType::Key),
+                (6, SemanticType::Bool),
+                (12, SemanticType::Key),
+                (17, SemanticType::Null),
+            ]
+        ));
+    }
+}
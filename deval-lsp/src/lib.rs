@@ -1,13 +1,13 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use dashmap::DashMap;
-use deval_data_model::{Format, SemanticType};
-use deval_validator::Validator;
-use line_index::{LineCol, TextSize};
+use deval_data_model::{Annotated, AnnotatedData, SemanticType, Span};
+use deval_validator::{AnyValidator, Severity, ValidationErrorKind, Validator};
+use line_index::{LineIndex, TextSize, WideEncoding, WideLineCol};
 use tower_lsp_server::jsonrpc::Result;
 use tower_lsp_server::lsp_types::*;
-use tower_lsp_server::{Client, LanguageServer, LspService, Server};
+use tower_lsp_server::{Client, LanguageServer, LspService, Server, UriExt};
 
 mod document;
 
@@ -16,12 +16,56 @@ use document::Document;
 struct Backend<F> {
     client: Client,
     documents: DashMap<Uri, Document>,
+    /// The last successfully compiled validator of each schema file
+    schemas: DashMap<PathBuf, Arc<dyn Validator>>,
+    /// The schema file of a document, if it has one
     schema_finder: F,
 }
 
-impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync + 'static>
-    LanguageServer for Backend<F>
-{
+impl<F> Backend<F> {
+    /// Compiles the schema at `path` and publishes its compile errors on the schema
+    /// file. If it doesn't compile, the last good validator is kept.
+    async fn load_schema(&self, path: &Path) -> Arc<dyn Validator> {
+        let diagnostics = match std::fs::read_to_string(path) {
+            Ok(source) => match compile_schema(&source) {
+                Ok(v) => {
+                    self.schemas.insert(path.to_owned(), Arc::from(v));
+                    vec![]
+                }
+                Err(diagnostics) => diagnostics,
+            },
+            Err(e) => vec![Diagnostic {
+                severity: Some(DiagnosticSeverity::ERROR),
+                message: format!("Failed to read schema: {e}"),
+                ..Default::default()
+            }],
+        };
+        if let Some(uri) = Uri::from_file_path(path) {
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+        }
+        match self.schemas.get(path) {
+            Some(v) => v.clone(),
+            None => Arc::new(AnyValidator),
+        }
+    }
+
+    async fn publish_document_diagnostics(&self, uri: &Uri) {
+        let Some(diagnostics) = self
+            .documents
+            .get(uri)
+            .map(|doc| document_diagnostics(&doc))
+        else {
+            return;
+        };
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics, None)
+            .await;
+    }
+}
+
+impl<F: Fn(&Path) -> Option<PathBuf> + Send + Sync + 'static> LanguageServer for Backend<F> {
     async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
         Ok(InitializeResult {
             server_info: Some(ServerInfo {
@@ -49,13 +93,13 @@ impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync
                                     SemanticTokenType::new("typeParameter"),
                                     SemanticTokenType::new("parameter"),
                                     SemanticTokenType::new("variable"), // 8
-                                    SemanticTokenType::new("property"),
+                                    SemanticTokenType::new("property"), // 9
                                     SemanticTokenType::new("enumMember"),
                                     SemanticTokenType::new("event"),
                                     SemanticTokenType::new("function"),
                                     SemanticTokenType::new("method"),
                                     SemanticTokenType::new("macro"),
-                                    SemanticTokenType::new("keyword"),
+                                    SemanticTokenType::new("keyword"), // 15
                                     SemanticTokenType::new("modifier"),
                                     SemanticTokenType::new("comment"),
                                     SemanticTokenType::new("string"), // 18
@@ -64,7 +108,9 @@ impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync
                                     SemanticTokenType::new("operator"),
                                     SemanticTokenType::new("decorator"),
                                 ],
-                                token_modifiers: vec![],
+                                // Optional keys are marked readonly, the closest
+                                // standard modifier that editors style
+                                token_modifiers: vec![SemanticTokenModifier::READONLY],
                             },
                             range: Some(true),
                             full: Some(SemanticTokensFullOptions::Bool(true)),
@@ -72,7 +118,18 @@ impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync
                     ),
                 ),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
-                // hover_provider: Some(HoverProviderCapability::Simple(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                        ..Default::default()
+                    },
+                )),
                 ..Default::default()
             },
         })
@@ -82,6 +139,26 @@ impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync
         self.client
             .log_message(MessageType::INFO, "server initialized!")
             .await;
+
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.dvl".to_owned()),
+                kind: None,
+            }],
+        };
+        let registration = Registration {
+            id: "deval-schema-watcher".to_owned(),
+            method: "workspace/didChangeWatchedFiles".to_owned(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("failed to watch schema files: {e}"),
+                )
+                .await;
+        }
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
@@ -94,12 +171,31 @@ impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync
 
         let path = Path::new(uri.path().as_str());
 
-        let Some((format, schema)) = (self.schema_finder)(path) else {
+        let Some(format) = deval_formats::format_for_path(path) else {
             return;
         };
 
-        self.documents
-            .insert(uri, Document::new(&text, format, schema));
+        let schema_path =
+            (self.schema_finder)(path).map(|p| std::fs::canonicalize(&p).unwrap_or(p));
+        let schema = match &schema_path {
+            Some(p) => match self.schemas.get(p).map(|v| v.clone()) {
+                Some(v) => v,
+                None => self.load_schema(p).await,
+            },
+            None => Arc::new(AnyValidator),
+        };
+
+        self.documents.insert(
+            uri.clone(),
+            Document::new(
+                &text,
+                &path.display().to_string(),
+                format,
+                schema,
+                schema_path,
+            ),
+        );
+        self.publish_document_diagnostics(&uri).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
@@ -109,6 +205,30 @@ impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync
         if let Some(mut doc) = self.documents.get_mut(&uri) {
             doc.update_text(&text);
         }
+        self.publish_document_diagnostics(&uri).await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            if change.typ == FileChangeType::DELETED {
+                continue;
+            }
+            let path = Path::new(change.uri.path().as_str());
+            let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+            let schema = self.load_schema(&path).await;
+
+            // Revalidate every open document using this schema
+            let mut affected = vec![];
+            for mut doc in self.documents.iter_mut() {
+                if doc.schema_path.as_ref() == Some(&path) {
+                    doc.set_schema(schema.clone());
+                    affected.push(doc.key().clone());
+                }
+            }
+            for uri in affected {
+                self.publish_document_diagnostics(&uri).await;
+            }
+        }
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
@@ -158,22 +278,12 @@ impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync
         };
 
         // Convert LSP range to byte offsets
-        let start_offset: usize = doc
-            .line_index
-            .offset(LineCol {
-                line: params.range.start.line,
-                col: params.range.start.character,
-            })
-            .unwrap()
-            .into();
-        let end_offset: usize = doc
-            .line_index
-            .offset(LineCol {
-                line: params.range.end.line,
-                col: params.range.end.character,
-            })
-            .unwrap()
-            .into();
+        let (Some(start_offset), Some(end_offset)) = (
+            position_to_offset(&doc.line_index, params.range.start),
+            position_to_offset(&doc.line_index, params.range.end),
+        ) else {
+            return Ok(None);
+        };
 
         // Get tokens in range from our token store
         let tokens = doc.token_store.tokens_in_range(start_offset, end_offset);
@@ -194,35 +304,93 @@ impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync
         };
 
         // Convert LSP position to byte offset
-        let offset: usize = doc
-            .line_index
-            .offset(LineCol {
-                line: params.text_document_position_params.position.line,
-                col: params.text_document_position_params.position.character,
+        let position = params.text_document_position_params.position;
+        let Some(offset) = position_to_offset(&doc.line_index, position) else {
+            return Ok(None);
+        };
+
+        Ok(hover_text(&doc, offset).map(|text| Hover {
+            contents: HoverContents::Scalar(MarkedString::String(text)),
+            range: None,
+        }))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let Some(doc) = self.documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        let hints = doc
+            .inlay_hints()
+            .into_iter()
+            .map(|(offset, schema_type)| InlayHint {
+                position: offset_to_position(&doc.line_index, offset),
+                label: InlayHintLabel::String(format!(": {schema_type}")),
+                kind: Some(InlayHintKind::TYPE),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: None,
+                data: None,
             })
-            .unwrap()
-            .into();
-
-        // Find the smallest token containing this position
-        let token = doc.token_store.smallest_token_containing(offset);
-
-        if let Some(token) = token {
-            // Create hover content based on token type
-            let header = match token.token_type {
-                SemanticType::Number => "Number literal",
-                SemanticType::String => "String literal",
-                SemanticType::Variable => "Variable",
-            };
+            .filter(|hint| params.range.start <= hint.position && hint.position <= params.range.end)
+            .collect();
+        Ok(Some(hints))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let Some(doc) = self.documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(Some(DocumentSymbolResponse::Nested(document_symbols(&doc))))
+    }
 
-            let data = &token.docs;
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let Some(doc) = self.documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(offset) = position_to_offset(&doc.line_index, params.position) else {
+            return Ok(None);
+        };
+        Ok(rename_range(&doc, offset).map(PrepareRenameResponse::Range))
+    }
 
-            return Ok(Some(Hover {
-                contents: HoverContents::Scalar(MarkedString::String(format!("{header}\n{data}"))),
-                range: None,
-            }));
-        }
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let Some(doc) = self.documents.get(&uri) else {
+            return Ok(None);
+        };
+        let Some(offset) =
+            position_to_offset(&doc.line_index, params.text_document_position.position)
+        else {
+            return Ok(None);
+        };
+        let edits = rename_edits(&doc, offset, &params.new_name)
+            .map_err(tower_lsp_server::jsonrpc::Error::invalid_params)?;
+        Ok(Some(WorkspaceEdit {
+            changes: Some([(uri, edits)].into_iter().collect()),
+            ..Default::default()
+        }))
+    }
 
-        Ok(None)
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let Some(doc) = self.documents.get(&uri) else {
+            return Ok(None);
+        };
+        let actions = missing_key_actions(&doc, &uri, &params.context.diagnostics);
+        Ok(Some(
+            actions
+                .into_iter()
+                .map(CodeActionOrCommand::CodeAction)
+                .collect(),
+        ))
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -230,6 +398,238 @@ impl<F: Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)> + Send + Sync
     }
 }
 
+/// Converts a byte offset to an LSP position, whose character is counted in
+/// UTF-16 code units.
+fn offset_to_position(line_index: &LineIndex, offset: usize) -> Position {
+    let l = line_index.line_col(TextSize::try_from(offset).unwrap());
+    let l = line_index.to_wide(WideEncoding::Utf16, l).unwrap();
+    Position {
+        line: l.line,
+        character: l.col,
+    }
+}
+
+fn position_to_offset(line_index: &LineIndex, position: Position) -> Option<usize> {
+    let l = line_index.to_utf8(
+        WideEncoding::Utf16,
+        WideLineCol {
+            line: position.line,
+            col: position.character,
+        },
+    )?;
+    let offset = line_index.offset(l)?;
+    Some(offset.into())
+}
+
+fn offsets_to_range(line_index: &LineIndex, start: usize, end: usize) -> Range {
+    Range {
+        start: offset_to_position(line_index, start),
+        end: offset_to_position(line_index, end),
+    }
+}
+
+/// The hover text at `offset` of a document: the type of the token there, then
+/// its docs.
+fn hover_text(doc: &Document, offset: usize) -> Option<String> {
+    // Find the smallest token containing this position
+    let token = doc.token_store.smallest_token_containing(offset)?;
+
+    // An array element is shown with its position in the array, then prefer the
+    // schema type the token was validated against
+    let element = doc.array_element_at(offset);
+    let header = match (&element, &token.schema_type) {
+        (Some(element), _) => element.as_str(),
+        (None, Some(schema_type)) => schema_type.as_str(),
+        (None, None) => match token.token_type {
+            SemanticType::Number => "Number literal",
+            SemanticType::String => "String literal",
+            SemanticType::Bool => "Boolean literal",
+            SemanticType::Null => "Null literal",
+            SemanticType::Key => "Key",
+            SemanticType::Variable => "Variable",
+        },
+    };
+
+    let data = &token.docs;
+    Some(format!("{header}\n{data}"))
+}
+
+/// Compiles a schema, returning its compile errors as diagnostics on the schema
+/// file if it fails.
+fn compile_schema(source: &str) -> std::result::Result<Box<dyn Validator>, Vec<Diagnostic>> {
+    deval_schema::compile_owned(source).map_err(|errors| {
+        let line_index = LineIndex::new(source);
+        errors
+            .into_iter()
+            .map(|e| Diagnostic {
+                range: offsets_to_range(&line_index, e.span.start, e.span.end),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("deval".to_owned()),
+                message: e.message,
+                ..Default::default()
+            })
+            .collect()
+    })
+}
+
+/// The range of the key occurrence under `offset`, which a rename replaces.
+fn rename_range(doc: &Document, offset: usize) -> Option<Range> {
+    let (start, end) = doc
+        .key_ranges_at(offset)?
+        .into_iter()
+        .find(|&(start, end)| start <= offset && offset <= end)?;
+    Some(offsets_to_range(&doc.line_index, start, end))
+}
+
+/// The edits renaming the key at `offset` of a document to `new_name`.
+fn rename_edits(
+    doc: &Document,
+    offset: usize,
+    new_name: &str,
+) -> std::result::Result<Vec<TextEdit>, String> {
+    let ranges = doc.rename_key(offset, new_name)?;
+    Ok(ranges
+        .into_iter()
+        .map(|(start, end)| TextEdit {
+            range: offsets_to_range(&doc.line_index, start, end),
+            new_text: new_name.to_owned(),
+        })
+        .collect())
+}
+
+/// Collects the parse and validation errors of a document as LSP diagnostics
+fn document_diagnostics(doc: &Document) -> Vec<Diagnostic> {
+    let parse_errors = doc
+        .parse_errors
+        .iter()
+        .map(|e| (&e.span, &e.message, DiagnosticSeverity::ERROR, None));
+    let validation_errors = doc.validation_errors.iter().map(|e| {
+        let severity = match e.severity {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+        };
+        (&e.span, &e.text, severity, Some(e.kind.code()))
+    });
+    parse_errors
+        .chain(validation_errors)
+        .map(|(span, message, severity, code)| Diagnostic {
+            range: offsets_to_range(&doc.line_index, span.start, span.end),
+            severity: Some(severity),
+            code: code.map(|code| NumberOrString::String(code.to_owned())),
+            source: Some("deval".to_owned()),
+            message: message.clone(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Quick fixes inserting the keys reported missing by `diagnostics`.
+fn missing_key_actions(doc: &Document, uri: &Uri, diagnostics: &[Diagnostic]) -> Vec<CodeAction> {
+    let missing_key = Some(NumberOrString::String("missing-key".to_owned()));
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.code == missing_key)
+        .filter_map(|diagnostic| {
+            let error = doc.validation_errors.iter().find(|e| {
+                e.text == diagnostic.message
+                    && offsets_to_range(&doc.line_index, e.span.start, e.span.end)
+                        == diagnostic.range
+            })?;
+            let ValidationErrorKind::MissingKey { key, .. } = &error.kind else {
+                return None;
+            };
+            let (offset, new_text) = doc.missing_key_insertion(error)?;
+            let position = offset_to_position(&doc.line_index, offset);
+            let edit = TextEdit {
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                new_text,
+            };
+            Some(CodeAction {
+                title: format!("Insert missing key `{key}`"),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some([(uri.clone(), vec![edit])].into_iter().collect()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// The outline of a document: each key of an object and each item of an array
+/// becomes a symbol, nested under the symbol of its container. Defaults
+/// inserted by the schema have no span, and are left out.
+fn document_symbols(doc: &Document) -> Vec<DocumentSymbol> {
+    fn children(line_index: &LineIndex, value: &AnnotatedData) -> Option<Vec<DocumentSymbol>> {
+        match value {
+            AnnotatedData::Object(items) => Some(
+                items
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        let key_span = key.annotation.span.all().first()?;
+                        symbol(line_index, key.value.clone(), key_span, value)
+                    })
+                    .collect(),
+            ),
+            AnnotatedData::Array(items) => Some(
+                items
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, item)| {
+                        let span = item.annotation.span.all().first()?;
+                        symbol(line_index, i.to_string(), span, item)
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The range of the symbol covers both its key and value, since the
+    /// selection range has to be inside it.
+    fn symbol(
+        line_index: &LineIndex,
+        name: String,
+        key_span: &Span,
+        value: &Annotated<AnnotatedData>,
+    ) -> Option<DocumentSymbol> {
+        let value_span = value.annotation.span.all().first()?;
+        let kind = match &value.value {
+            AnnotatedData::Null => SymbolKind::NULL,
+            AnnotatedData::Bool(_) => SymbolKind::BOOLEAN,
+            AnnotatedData::Number(_) => SymbolKind::NUMBER,
+            AnnotatedData::String(_) => SymbolKind::STRING,
+            AnnotatedData::Array(_) => SymbolKind::ARRAY,
+            AnnotatedData::Object(_) => SymbolKind::STRUCT,
+        };
+        #[allow(deprecated)]
+        Some(DocumentSymbol {
+            name,
+            detail: value.annotation.schema_type.clone(),
+            kind,
+            tags: None,
+            deprecated: None,
+            range: offsets_to_range(
+                line_index,
+                key_span.start.min(value_span.start),
+                key_span.end.max(value_span.end),
+            ),
+            selection_range: offsets_to_range(line_index, key_span.start, key_span.end),
+            children: children(line_index, &value.value),
+        })
+    }
+
+    match &doc.annotated {
+        Some(annotated) => children(&doc.line_index, &annotated.value).unwrap_or_default(),
+        None => vec![],
+    }
+}
+
 /// Convert semantic tokens to LSP semantic tokens
 fn convert_tokens_to_lsp(
     doc: &Document,
@@ -241,40 +641,52 @@ fn convert_tokens_to_lsp(
     let mut prev_col = 0;
 
     for token in tokens {
-        let l = doc
-            .line_index
-            .line_col(TextSize::try_from(token.start).unwrap());
+        // The tokens may be from the last text that parsed
+        let line_index = &doc.token_line_index;
+        let start = offset_to_position(line_index, token.start);
+        let end = offset_to_position(line_index, token.end);
 
         // Convert our internal semantic type to LSP token type
         let token_type = match token.token_type {
             SemanticType::Number => 19,
             SemanticType::String => 18,
+            SemanticType::Bool | SemanticType::Null => 15,
+            SemanticType::Key => 9,
             SemanticType::Variable => 8,
         };
 
-        if l.line != prev_line {
+        if start.line != prev_line {
             prev_col = 0;
         }
 
+        // A token spanning lines is cut at the end of its first line
+        let length = if end.line == start.line {
+            end.character - start.character
+        } else {
+            // The range of a line ends where the next one starts, after the
+            // newline
+            let line_end = line_index
+                .line(start.line)
+                .map_or(token.end, |r| usize::from(r.end()) - 1)
+                .max(token.start);
+            offset_to_position(line_index, line_end).character - start.character
+        };
         result.push(SemanticToken {
-            delta_line: l.line - prev_line,
-            delta_start: l.col - prev_col,
-            length: (token.end - token.start) as u32,
+            delta_line: start.line - prev_line,
+            delta_start: start.character - prev_col,
+            length,
             token_type,
-            token_modifiers_bitset: 0,
+            token_modifiers_bitset: if token.optional { 1 } else { 0 },
         });
-        prev_col = l.col;
-        prev_line = l.line;
+        prev_col = start.character;
+        prev_line = start.line;
     }
 
     result
 }
 
 pub async fn start_server(
-    schema_finder: impl Fn(&Path) -> Option<(Arc<dyn Format>, Arc<dyn Validator>)>
-    + Send
-    + Sync
-    + 'static,
+    schema_finder: impl Fn(&Path) -> Option<PathBuf> + Send + Sync + 'static,
 ) {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
@@ -282,7 +694,402 @@ pub async fn start_server(
     let (service, socket) = LspService::new(|client| Backend {
         client,
         documents: DashMap::new(),
+        schemas: DashMap::new(),
         schema_finder,
     });
     Server::new(stdin, stdout, socket).serve(service).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deval_data_model::Format;
+
+    /// The name and kind of each symbol, indented by depth
+    fn outline(symbols: &[DocumentSymbol], depth: usize, lines: &mut Vec<String>) {
+        for symbol in symbols {
+            lines.push(format!(
+                "{}{} {:?}",
+                "  ".repeat(depth),
+                symbol.name,
+                symbol.kind
+            ));
+            outline(
+                symbol.children.as_deref().unwrap_or_default(),
+                depth + 1,
+                lines,
+            );
+        }
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let text = "(\n    name: \"app\",\n    server: (\n        port: 8080,\n        tags: [\"a\", true],\n    ),\n)";
+        let doc = Document::new(
+            text,
+            "/test.ron",
+            Arc::new(deval_format_ron::Ron),
+            Arc::new(AnyValidator),
+            None,
+        );
+        let symbols = document_symbols(&doc);
+
+        let mut lines = vec![];
+        outline(&symbols, 0, &mut lines);
+        assert_eq!(
+            lines,
+            [
+                "name String",
+                "server Struct",
+                "  port Number",
+                "  tags Array",
+                "    0 String",
+                "    1 Boolean",
+            ]
+        );
+
+        let port = &symbols[1].children.as_ref().unwrap()[0];
+        let position = |line, character| Position { line, character };
+        assert_eq!(
+            port.selection_range,
+            Range {
+                start: position(3, 8),
+                end: position(3, 12),
+            }
+        );
+        assert_eq!(
+            port.range,
+            Range {
+                start: position(3, 8),
+                end: position(3, 18),
+            }
+        );
+        assert_eq!(symbols[1].range.end, position(5, 5));
+    }
+
+    #[test]
+    fn test_document_symbols_skip_defaults() {
+        let schema = deval_schema::compile("{ name: string, port?: integer = 80 }").unwrap();
+        let doc = Document::new(
+            "(name: \"app\")",
+            "/test.ron",
+            Arc::new(deval_format_ron::Ron),
+            Arc::from(schema),
+            None,
+        );
+        let mut lines = vec![];
+        outline(&document_symbols(&doc), 0, &mut lines);
+        assert_eq!(lines, ["name String"]);
+    }
+
+    #[test]
+    fn test_rename_key() {
+        let schema = deval_schema::compile("{ port: string, host?: string, name?: string }");
+        let doc = Document::new(
+            "port = 80\nhost = a",
+            "/test.ini",
+            Arc::new(deval_format_ini::Ini),
+            Arc::from(schema.unwrap()),
+            None,
+        );
+        let position = |line, character| Position { line, character };
+
+        assert_eq!(
+            rename_edits(&doc, 12, "name"),
+            Ok(vec![TextEdit {
+                range: Range {
+                    start: position(1, 0),
+                    end: position(1, 4),
+                },
+                new_text: "name".to_owned(),
+            }])
+        );
+        assert_eq!(
+            rename_edits(&doc, 2, "host"),
+            Err("Key `host` already exists".to_owned())
+        );
+        assert_eq!(
+            rename_edits(&doc, 2, "prot"),
+            Err("The schema doesn't allow the key `prot`".to_owned())
+        );
+        assert_eq!(
+            rename_edits(&doc, 8, "name"),
+            Err("Only keys can be renamed".to_owned())
+        );
+        assert_eq!(
+            rename_range(&doc, 11),
+            Some(Range {
+                start: position(1, 0),
+                end: position(1, 4),
+            })
+        );
+        assert_eq!(rename_range(&doc, 8), None);
+    }
+
+    #[test]
+    fn test_rename_quoted_key() {
+        let doc = Document::new(
+            "{\n    \"a b\": 1,\n}",
+            "/test.ron",
+            Arc::new(deval_format_ron::Ron),
+            Arc::new(AnyValidator),
+            None,
+        );
+        assert_eq!(doc.key_ranges_at(7), Some(vec![(7, 10)]));
+        let edits = rename_edits(&doc, 7, "c").unwrap();
+        assert_eq!(edits[0].new_text, "c");
+        assert_eq!(
+            edits[0].range,
+            Range {
+                start: Position {
+                    line: 1,
+                    character: 5
+                },
+                end: Position {
+                    line: 1,
+                    character: 8
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_insert_missing_key() {
+        let schema = deval_schema::compile("{ name: string, port: integer, debug: bool }");
+        let text = "(\n    name: \"a\",\n    port: 1\n)";
+        let doc = Document::new(
+            text,
+            "/test.ron",
+            Arc::new(deval_format_ron::Ron),
+            Arc::from(schema.unwrap()),
+            None,
+        );
+        let diagnostics = document_diagnostics(&doc);
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("missing-key".to_owned()))
+        );
+
+        let uri = Uri::from_file_path("/test.ron").unwrap();
+        let actions = missing_key_actions(&doc, &uri, &diagnostics);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].title, "Insert missing key `debug`");
+        let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+        let end_of_port = Position {
+            line: 2,
+            character: 11,
+        };
+        assert_eq!(
+            edits,
+            &[TextEdit {
+                range: Range {
+                    start: end_of_port,
+                    end: end_of_port,
+                },
+                new_text: ",\n    debug: false".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_insert_missing_key_after_trailing_comma() {
+        let schema = deval_schema::compile("{ name: string, tags: string[] }");
+        let doc = Document::new(
+            "{\"name\": \"a\",}",
+            "/test.ron",
+            Arc::new(deval_format_ron::Ron),
+            Arc::from(schema.unwrap()),
+            None,
+        );
+        assert_eq!(
+            doc.missing_key_insertion(&doc.validation_errors[0]),
+            Some((13, " \"tags\": [],".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_insert_missing_key_into_empty_object() {
+        let schema = deval_schema::compile("{ name: string, tags: string[] }");
+        let doc = |text: &str, format: Arc<dyn Format>| {
+            let schema = Arc::from(schema.clone().unwrap());
+            Document::new(text, "/test", format, schema, None)
+        };
+        let json = doc("{}", Arc::new(deval_format_json::Json));
+        assert_eq!(
+            json.missing_key_insertion(&json.validation_errors[0]),
+            Some((1, "\"name\": \"\"".to_owned()))
+        );
+        let ron = doc("Config( )", Arc::new(deval_format_ron::Ron));
+        assert_eq!(
+            ron.missing_key_insertion(&ron.validation_errors[0]),
+            Some((8, "name: \"\"".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_insert_missing_key_placeholder() {
+        let doc = |schema: &str, text: &str, format: Arc<dyn Format>| {
+            let schema = deval_schema::compile(schema).unwrap();
+            Document::new(text, "/test", format, Arc::from(schema), None)
+        };
+        // The placeholder is written in the syntax of the document
+        let json = doc(
+            "{ name: string, point: [1.., string] }",
+            "{\"name\": \"a\"}",
+            Arc::new(deval_format_json::Json),
+        );
+        assert_eq!(
+            json.missing_key_insertion(&json.validation_errors[0]),
+            Some((12, ", \"point\": [1, \"\"]".to_owned()))
+        );
+        let ron = doc(
+            "{ name: string, point: { x: 0..=5, y: 2..3 } }",
+            "(name: \"a\")",
+            Arc::new(deval_format_ron::Ron),
+        );
+        assert_eq!(
+            ron.missing_key_insertion(&ron.validation_errors[0]),
+            Some((10, ", point: (x: 0, y: 2)".to_owned()))
+        );
+
+        // No insertion without a value to insert
+        let negated = doc(
+            "{ name: string, id: !null }",
+            "(name: \"a\")",
+            Arc::new(deval_format_ron::Ron),
+        );
+        assert_eq!(
+            negated.missing_key_insertion(&negated.validation_errors[0]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hover_array_elements() {
+        let schema = deval_schema::compile("{ point: [string, number, bool], tags: string[] }");
+        let text = "(point: (\"a\", 2, true), tags: [\"x\", \"y\"])";
+        let doc = Document::new(
+            text,
+            "/test.ron",
+            Arc::new(deval_format_ron::Ron),
+            Arc::from(schema.unwrap()),
+            None,
+        );
+        let hover = |needle: &str| {
+            let text = hover_text(&doc, text.find(needle).unwrap()).unwrap();
+            text.lines().next().unwrap().to_owned()
+        };
+        assert_eq!(hover("2,"), "element 1 of [string, number, bool]: number");
+        assert_eq!(hover("\"y\""), "element 1 of string[]: string");
+        assert_eq!(hover("point"), "point: [string, number, bool]");
+    }
+
+    #[test]
+    fn test_tokens_of_longer_text_after_parse_error() {
+        let mut doc = Document::new(
+            "{\n  \"name\": \"a long value\"\n}",
+            "/test.json",
+            Arc::new(deval_format_json::Json),
+            Arc::new(AnyValidator),
+            None,
+        );
+        doc.update_text("{");
+        assert!(doc.annotated.is_none());
+        let tokens: Vec<_> = doc.token_store.all_tokens().iter().collect();
+        let tokens: Vec<_> = convert_tokens_to_lsp(&doc, &tokens, 0)
+            .iter()
+            .map(|t| (t.delta_line, t.delta_start, t.length))
+            .collect();
+        assert_eq!(tokens, [(1, 2, 6), (0, 8, 14)]);
+    }
+
+    #[test]
+    fn test_multiline_token_after_key() {
+        let doc = Document::new(
+            "key = \"\"\"\nfirst\nsecond\"\"\"\n",
+            "/test.toml",
+            Arc::new(deval_format_toml::Toml),
+            Arc::new(AnyValidator),
+            None,
+        );
+        let tokens: Vec<_> = doc.token_store.all_tokens().iter().collect();
+        let tokens: Vec<_> = convert_tokens_to_lsp(&doc, &tokens, 0)
+            .iter()
+            .map(|t| (t.delta_line, t.delta_start, t.length))
+            .collect();
+        // The string is cut after the `"""` which ends its first line
+        assert_eq!(tokens, [(0, 0, 3), (0, 6, 3)]);
+    }
+
+    #[test]
+    fn test_multibyte_positions() {
+        let schema = deval_schema::compile("{ a: string, b: number }");
+        let text = "(a: \"é😀\", b: 1)";
+        let doc = Document::new(
+            text,
+            "/test.ron",
+            Arc::new(deval_format_ron::Ron),
+            Arc::from(schema.unwrap()),
+            None,
+        );
+        let tokens: Vec<_> = doc.token_store.all_tokens().iter().collect();
+        let tokens: Vec<_> = convert_tokens_to_lsp(&doc, &tokens, 0)
+            .iter()
+            .map(|t| (t.delta_start, t.length))
+            .collect();
+        // Columns and lengths count UTF-16 code units, where `😀` takes two
+        assert_eq!(tokens, [(1, 1), (3, 5), (7, 1), (3, 1)]);
+        let number = Position {
+            line: 0,
+            character: 14,
+        };
+        let offset = position_to_offset(&doc.line_index, number).unwrap();
+        assert_eq!(offset, text.find('1').unwrap());
+        assert_eq!(offset_to_position(&doc.line_index, offset), number);
+    }
+
+    #[test]
+    fn test_optional_key_modifier() {
+        let schema =
+            deval_schema::compile("{ a: string, b?: number, c?: bool = true, d: bool = true }");
+        let doc = Document::new(
+            "(a: \"x\", b: 1, d: false)",
+            "/test.ron",
+            Arc::new(deval_format_ron::Ron),
+            Arc::from(schema.unwrap()),
+            None,
+        );
+        let tokens: Vec<_> = doc.token_store.all_tokens().iter().collect();
+        let tokens: Vec<_> = convert_tokens_to_lsp(&doc, &tokens, 0)
+            .iter()
+            .map(|t| (t.token_type, t.token_modifiers_bitset))
+            .collect();
+        // `d` has a default, so it may be left out like `b`
+        assert_eq!(tokens, [(9, 0), (18, 0), (9, 1), (19, 0), (9, 1), (15, 0)]);
+    }
+
+    #[test]
+    fn test_schema_compile_diagnostics() {
+        assert!(compile_schema("{ a: integer }").is_ok());
+
+        let Err(diagnostics) = compile_schema("{\n  a: integr\n}") else {
+            panic!("Expected the schema to fail");
+        };
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert!(diagnostics[0].message.contains("integr"));
+        assert_eq!(
+            diagnostics[0].range,
+            Range {
+                start: Position {
+                    line: 1,
+                    character: 5
+                },
+                end: Position {
+                    line: 1,
+                    character: 11
+                },
+            }
+        );
+    }
+}
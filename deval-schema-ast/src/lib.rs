@@ -10,26 +10,66 @@ pub struct Spanned<T> {
 pub enum RecordMatcher {
     SimpleKey {
         key: String,
+        /// Other names of the key, as in `user_name | userName: string`
+        aliases: Vec<String>,
+        /// Whether the key and its aliases match in any case, as written with
+        /// `ignore_case name: string`
+        ignore_case: bool,
         optional: bool,
         docs: String,
         value: Expression,
+        /// A literal used when the key is absent
+        default: Option<Spanned<Expression>>,
+    },
+    /// `[key]: value`, for any number of keys matching `key`
+    Map {
+        key: Expression,
+        value: Expression,
     },
     AnyKey,
+    /// `keys 2..=10`, bounding the number of keys
+    KeyCount(Spanned<Expression>),
 }
 
 #[derive(Debug)]
 pub enum Expression {
     Number(Spanned<f64>),
+    BoolLiteral(Spanned<bool>),
+    StringLiteral(Spanned<String>),
+    Regex(Spanned<String>),
     Range {
         start: Option<Spanned<Box<Expression>>>,
         end: Option<Spanned<Box<Expression>>>,
+        /// Whether the start is written with `<..`, like `0<..1`
+        excludes_start: bool,
         is_inclusive: bool,
     },
     Ident(Spanned<String>),
     Array {
         element: Box<Expression>,
         index: Option<Spanned<Box<Expression>>>,
+        /// Whether the brackets start with `unique`
+        unique: bool,
     },
     Object(Vec<RecordMatcher>),
+    Tuple(Vec<Expression>),
     Union(Vec<Expression>),
+    OneOf(Vec<Expression>),
+    Not(Box<Expression>),
+    /// A named string format, like `format("email")`
+    Format(Spanned<String>),
+}
+
+#[derive(Debug)]
+pub struct TypeDefinition {
+    pub name: Spanned<String>,
+    pub value: Expression,
+}
+
+#[derive(Debug)]
+pub struct Schema {
+    /// The `///` comments at the start of the schema, documenting it as a whole
+    pub docs: String,
+    pub definitions: Vec<TypeDefinition>,
+    pub root: Expression,
 }
@@ -1,5 +1,6 @@
+use deval_validator::StringFormat;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -10,15 +11,39 @@ struct JsonSchema {
     properties: HashMap<String, Box<JsonSchema>>,
     #[serde(default)]
     required: Vec<String>,
-    items: Option<Box<JsonSchema>>,
+    items: Option<Items>,
+    additional_items: Option<AdditionalProperties>,
     min_items: Option<i32>,
     max_items: Option<i32>,
+    unique_items: Option<bool>,
+    min_properties: Option<i32>,
+    max_properties: Option<i32>,
     minimum: Option<f64>,
     maximum: Option<f64>,
-    #[serde(default)]
-    exclusive_maximum: bool,
+    exclusive_minimum: Option<ExclusiveBound>,
+    exclusive_maximum: Option<ExclusiveBound>,
+    multiple_of: Option<f64>,
+    pattern: Option<String>,
+    format: Option<String>,
+    #[serde(rename = "const")]
+    const_value: Option<serde_json::Value>,
+    #[serde(rename = "enum")]
+    enum_values: Option<Vec<serde_json::Value>>,
     additional_properties: Option<AdditionalProperties>,
+    #[serde(default)]
+    pattern_properties: BTreeMap<String, JsonSchema>,
+    property_names: Option<Box<JsonSchema>>,
+    one_of: Option<Vec<JsonSchema>>,
+    title: Option<String>,
     description: Option<String>,
+    #[serde(rename = "$comment")]
+    comment: Option<String>,
+    #[serde(rename = "$ref")]
+    reference: Option<String>,
+    #[serde(default)]
+    definitions: HashMap<String, Box<JsonSchema>>,
+    #[serde(default, rename = "$defs")]
+    defs: HashMap<String, Box<JsonSchema>>,
     #[serde(flatten)]
     extra: HashMap<String, serde_json::Value>,
 }
@@ -30,6 +55,23 @@ enum JsonSchemaType {
     Multiple(Vec<String>),
 }
 
+/// Draft 4 uses a boolean flag modifying `minimum`/`maximum`, while later drafts
+/// use the bound itself as the value.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum ExclusiveBound {
+    Flag(bool),
+    Value(f64),
+}
+
+/// A schema for every element, or in the array form, one schema per position.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Items {
+    Single(Box<JsonSchema>),
+    Tuple(Vec<JsonSchema>),
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 enum AdditionalProperties {
@@ -37,10 +79,217 @@ enum AdditionalProperties {
     Schema(Box<JsonSchema>),
 }
 
+/// State shared across a single conversion.
+struct Context<'a> {
+    /// Local `$ref` targets, keyed by the reference string.
+    definitions: HashMap<String, &'a JsonSchema>,
+    /// References currently being inlined, used to break reference cycles.
+    expanding: Vec<String>,
+    /// References found to be part of a cycle.
+    cyclic: BTreeSet<String>,
+    /// Type names of the references which are emitted as named types instead of
+    /// being inlined.
+    named: HashMap<String, String>,
+}
+
+impl<'a> Context<'a> {
+    fn new(root: &'a JsonSchema) -> Self {
+        let mut definitions = HashMap::from([("#".to_string(), root)]);
+        for (name, schema) in &root.definitions {
+            definitions.insert(format!("#/definitions/{name}"), &**schema);
+        }
+        for (name, schema) in &root.defs {
+            definitions.insert(format!("#/$defs/{name}"), &**schema);
+        }
+        Self {
+            definitions,
+            expanding: vec![],
+            cyclic: BTreeSet::new(),
+            named: HashMap::new(),
+        }
+    }
+}
+
 pub fn convert(json_schema_text: &str) -> String {
-    let json_schema: JsonSchema =
-        serde_json::from_str(json_schema_text).expect("Invalid JSON Schema");
-    json_schema_to_deval(&json_schema)
+    try_convert(json_schema_text).expect("Invalid JSON Schema")
+}
+
+/// Like [`convert`], but emits every entry of `definitions` and `$defs` as a
+/// named type, instead of inlining it where it's referenced.
+pub fn convert_named(json_schema_text: &str) -> String {
+    try_convert_named(json_schema_text).expect("Invalid JSON Schema")
+}
+
+/// Like [`convert`], but returns an error, with its line and column, if the
+/// text is not a JSON Schema.
+pub fn try_convert(json_schema_text: &str) -> Result<String, serde_json::Error> {
+    convert_with(json_schema_text, false)
+}
+
+/// Like [`convert_named`], but returns an error if the text is not a JSON
+/// Schema.
+pub fn try_convert_named(json_schema_text: &str) -> Result<String, serde_json::Error> {
+    convert_with(json_schema_text, true)
+}
+
+fn convert_with(
+    json_schema_text: &str,
+    name_definitions: bool,
+) -> Result<String, serde_json::Error> {
+    let json_schema: JsonSchema = serde_json::from_str(json_schema_text)?;
+    let mut ctx = Context::new(&json_schema);
+    // The title and description document the whole schema
+    let docs = doc_comment(
+        &[
+            &json_schema.title,
+            &json_schema.description,
+            &json_schema.comment,
+        ],
+        "\n",
+    );
+    let root = json_schema_to_deval(&json_schema, &mut ctx);
+    let mut references = std::mem::take(&mut ctx.cyclic);
+    if name_definitions {
+        references.extend(ctx.definitions.keys().filter(|r| *r != "#").cloned());
+    }
+    if references.is_empty() {
+        return Ok(docs + &root);
+    }
+
+    // Convert again with each of these emitted once as a named type, which is
+    // the only option for cyclic references since they can't be inlined
+    let mut names: Vec<(String, String)> = vec![];
+    for reference in references {
+        // A reference to nothing but itself accepts anything, and has no type
+        // to name
+        if is_reference_cycle(&reference, &ctx) {
+            ctx.named.insert(reference, "any".to_string());
+            continue;
+        }
+        let base = type_name(&reference);
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while RESERVED_NAMES.contains(&name.as_str()) || names.iter().any(|(n, _)| *n == name) {
+            name = format!("{base}{suffix}");
+            suffix += 1;
+        }
+        names.push((name, reference));
+    }
+    ctx.named.extend(
+        names
+            .iter()
+            .map(|(name, reference)| (reference.clone(), name.clone())),
+    );
+    let root = match ctx.named.get("#") {
+        Some(name) => name.clone(),
+        None => json_schema_to_deval(&json_schema, &mut ctx),
+    };
+    let mut result = docs;
+    for (name, reference) in &names {
+        let target = ctx.definitions[reference];
+        let value = json_schema_to_deval(target, &mut ctx);
+        result += &format!("type {name} = {value};\n\n");
+    }
+    Ok(result + &root)
+}
+
+/// A `///` line for each line of the given docs, each followed by `indent`.
+fn doc_comment(docs: &[&Option<String>], indent: &str) -> String {
+    docs.iter()
+        .copied()
+        .flatten()
+        .flat_map(|text| text.lines())
+        .map(|line| format!("/// {line}{indent}"))
+        .collect()
+}
+
+/// A deval literal accepting only `value`, if it is a scalar deval can write.
+fn literal(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => Some("null".to_owned()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        // String literals only have `\"` and `\\` escapes
+        serde_json::Value::String(s) if !s.contains('\n') => Some(format!(
+            "\"{}\"",
+            s.replace('\\', "\\\\").replace('"', "\\\"")
+        )),
+        _ => None,
+    }
+}
+
+/// Names with a meaning of their own in deval, which a type can't take.
+const RESERVED_NAMES: &[&str] = &[
+    "any",
+    "bool",
+    "false",
+    "format",
+    "ignore_case",
+    "integer",
+    "keys",
+    "null",
+    "number",
+    "string",
+    "true",
+    "type",
+    "unique",
+];
+
+/// Whether `reference` leads back to itself through schemas which are only a
+/// `$ref`, like a definition `A` that is `{"$ref": "#/definitions/A"}`.
+fn is_reference_cycle(reference: &str, ctx: &Context) -> bool {
+    let mut visited = vec![];
+    let mut current = reference;
+    while let Some(next) = ctx
+        .definitions
+        .get(current)
+        .and_then(|schema| schema.reference.as_deref())
+    {
+        if next == reference {
+            return true;
+        }
+        // A cycle further along, which that reference is checked for itself
+        if visited.contains(&next) {
+            return false;
+        }
+        visited.push(next);
+        current = next;
+    }
+    false
+}
+
+/// A deval type name for the target of `reference`, from its last segment.
+fn type_name(reference: &str) -> String {
+    let segment = match reference.rsplit_once('/') {
+        Some((_, segment)) => segment,
+        None => "Root",
+    };
+    let name: String = segment
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.chars().next() {
+        Some(c) if !c.is_ascii_digit() => name,
+        _ => format!("_{name}"),
+    }
+}
+
+fn convert_reference<'a>(reference: &str, ctx: &mut Context<'a>) -> String {
+    let Some(&target) = ctx.definitions.get(reference) else {
+        return "any".to_string();
+    };
+    if let Some(name) = ctx.named.get(reference) {
+        return name.clone();
+    }
+    // Inlining a reference from inside itself would never terminate
+    if ctx.expanding.iter().any(|r| r == reference) {
+        ctx.cyclic.insert(reference.to_string());
+        return "any".to_string();
+    }
+    ctx.expanding.push(reference.to_string());
+    let result = json_schema_to_deval(target, ctx);
+    ctx.expanding.pop();
+    result
 }
 
 fn convert_json_type(type_str: &str) -> String {
@@ -53,30 +302,151 @@ fn convert_json_type(type_str: &str) -> String {
     }
 }
 
-fn convert_number_range(
-    base_type: &str,
-    minimum: Option<f64>,
-    maximum: Option<f64>,
-    exclusive_maximum: bool,
-) -> String {
+fn convert_number_range(base_type: &str, schema: &JsonSchema) -> String {
+    // With both an inclusive and an exclusive bound, the stricter one wins, and
+    // the exclusive one on a tie
+    let (minimum, exclusive_minimum) = match (schema.minimum, &schema.exclusive_minimum) {
+        (Some(min), Some(ExclusiveBound::Value(v))) if min > *v => (Some(min), false),
+        (_, Some(ExclusiveBound::Value(v))) => (Some(*v), true),
+        (min, Some(ExclusiveBound::Flag(flag))) => (min, *flag),
+        (min, None) => (min, false),
+    };
+    let (maximum, exclusive_maximum) = match (schema.maximum, &schema.exclusive_maximum) {
+        (Some(max), Some(ExclusiveBound::Value(v))) if max < *v => (Some(max), false),
+        (_, Some(ExclusiveBound::Value(v))) => (Some(*v), true),
+        (max, Some(ExclusiveBound::Flag(flag))) => (max, *flag),
+        (max, None) => (max, false),
+    };
+    // An exclusive minimum on integers starts at the next integer, and on
+    // numbers is written with `<..`
+    let start = match minimum {
+        Some(min) if exclusive_minimum && base_type == "integer" => format!("{}", min.floor() + 1.),
+        Some(min) if exclusive_minimum => format!("{min}<"),
+        Some(min) => format!("{min}"),
+        None => String::new(),
+    };
+    // TODO: emit `multipleOf` once the schema language has a validator for it.
     match (minimum, maximum, exclusive_maximum) {
         (None, None, _) => base_type.to_string(),
-        (None, Some(max), false) => format!("..={max}"),
-        (None, Some(max), true) => format!("..{max}"),
-        (Some(min), None, _) => format!("{min}.."),
-        (Some(min), Some(max), false) => format!("{min}..={max}"),
-        (Some(min), Some(max), true) => format!("{min}..{max}"),
+        (_, None, _) => format!("{start}.."),
+        (_, Some(max), false) => format!("{start}..={max}"),
+        (_, Some(max), true) => format!("{start}..{max}"),
+    }
+}
+
+fn convert_string(schema: &JsonSchema) -> String {
+    if let Some(pattern) = &schema.pattern {
+        return format!("/{}/", pattern.replace('/', "\\/"));
+    }
+    // Unknown formats are only annotations in JSON Schema, so they are dropped
+    match schema.format.as_deref().and_then(StringFormat::from_name) {
+        Some(string_format) => format!("format({:?})", string_format.name()),
+        None => "string".to_string(),
     }
 }
 
-fn convert_object_properties(schema: &JsonSchema) -> String {
+fn has_number_bounds(schema: &JsonSchema) -> bool {
+    schema.minimum.is_some()
+        || schema.maximum.is_some()
+        || matches!(schema.exclusive_minimum, Some(ExclusiveBound::Value(_)))
+        || matches!(schema.exclusive_maximum, Some(ExclusiveBound::Value(_)))
+}
+
+/// Whether the converted type `deval` is a union at its top level, like
+/// `"a" | "b"`, rather than only containing one, like `{ a: "a" | "b" }`.
+fn is_union(deval: &str) -> bool {
+    let mut depth = 0;
+    let mut chars = deval.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            '|' | '^' if depth == 0 => return true,
+            // Skip string and regex literals, and doc comments, which may hold
+            // any of the above
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => _ = chars.next(),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                chars.find(|&c| c == '\n');
+            }
+            '/' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => _ = chars.next(),
+                        '/' => break,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+fn convert_array<'a>(schema: &JsonSchema, ctx: &mut Context<'a>) -> String {
+    let unique = if schema.unique_items == Some(true) {
+        "unique "
+    } else {
+        ""
+    };
+    let range = |min_items, max_items| match (min_items, max_items) {
+        (None, None) => format!("[{}]", unique.trim_end()),
+        (None, Some(r)) => format!("[{unique}..={r}]"),
+        (Some(l), None) => format!("[{unique}{l}..]"),
+        (Some(l), Some(r)) => format!("[{unique}{l}..={r}]"),
+    };
+    let len_range = range(schema.min_items, schema.max_items);
+    match &schema.items {
+        Some(Items::Single(items)) => {
+            let element = json_schema_to_deval(items, ctx);
+            // `[]` after a union would only apply to its last alternative
+            if is_union(&element) {
+                format!("({element}){len_range}")
+            } else {
+                format!("{element}{len_range}")
+            }
+        }
+        Some(Items::Tuple(items))
+            if matches!(
+                schema.additional_items,
+                Some(AdditionalProperties::Boolean(false))
+            ) =>
+        {
+            // The array may stop before the last listed element, while a tuple
+            // takes all of them
+            let count = items.len() as i32;
+            if schema.min_items.unwrap_or(0) < count {
+                let max_items = schema.max_items.map_or(count, |max| max.min(count));
+                return format!("any{}", range(schema.min_items, Some(max_items)));
+            }
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| json_schema_to_deval(item, ctx))
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+        // Unless `additionalItems` is false, more elements may follow the listed
+        // ones, which a tuple can't express
+        Some(Items::Tuple(_)) | None => format!("any{len_range}"),
+    }
+}
+
+fn convert_object_properties<'a>(schema: &JsonSchema, ctx: &mut Context<'a>) -> String {
     let mut fields = Vec::new();
 
     // Get required fields
     let required: HashSet<&String> = schema.required.iter().collect();
 
     for (key, prop_schema) in &schema.properties {
-        let field_type = json_schema_to_deval(prop_schema);
+        let field_type = json_schema_to_deval(prop_schema, ctx);
 
         // Determine if the field is optional (not in required list)
         let is_optional = !required.contains(key);
@@ -87,23 +457,46 @@ fn convert_object_properties(schema: &JsonSchema) -> String {
         };
 
         // Add documentation if available
-        let doc_comment = if let Some(desc) = &prop_schema.description {
-            format!("/// {}\n    ", desc)
-        } else {
-            String::new()
-        };
+        let doc_comment = doc_comment(&[&prop_schema.description, &prop_schema.comment], "\n    ");
 
         fields.push(format!("{}{}: {}", doc_comment, field_name, field_type));
     }
 
+    // Keys matching a pattern become map records, tried before additionalProperties
+    for (pattern, value_schema) in &schema.pattern_properties {
+        let value_type = json_schema_to_deval(value_schema, ctx);
+        fields.push(format!(
+            "[/{}/]: {}",
+            pattern.replace('/', "\\/"),
+            value_type
+        ));
+    }
+
+    // Every key must match propertyNames. The properties and patterns can't be
+    // checked against it, so it only applies to objects without them
+    let other_keys = match &schema.property_names {
+        Some(names) if schema.properties.is_empty() && schema.pattern_properties.is_empty() => {
+            match json_schema_to_deval(names, ctx) {
+                names if names == "any" => "string".to_owned(),
+                names => names,
+            }
+        }
+        _ => "string".to_owned(),
+    };
+
     // Check if the object allows additional properties
     let allows_additional = match &schema.additional_properties {
         Some(additional) => {
             match additional {
                 // If additionalProperties is explicitly false, no additional properties allowed
                 AdditionalProperties::Boolean(false) => false,
-                // If additionalProperties is true or a schema, additional properties are allowed
-                AdditionalProperties::Boolean(true) | AdditionalProperties::Schema(_) => true,
+                AdditionalProperties::Boolean(true) => true,
+                // A schema constrains the values of every other key
+                AdditionalProperties::Schema(value_schema) => {
+                    let value_type = json_schema_to_deval(value_schema, ctx);
+                    fields.push(format!("[{other_keys}]: {value_type}"));
+                    false
+                }
             }
         }
         // If additionalProperties is not specified, it defaults to true
@@ -111,10 +504,22 @@ fn convert_object_properties(schema: &JsonSchema) -> String {
     };
 
     // Add .. if the object allows additional properties
-    if allows_additional {
+    if allows_additional && other_keys != "string" {
+        fields.push(format!("[{other_keys}]: any"));
+    } else if allows_additional {
         fields.push("..".to_string());
     }
 
+    let key_count = match (schema.min_properties, schema.max_properties) {
+        (None, None) => None,
+        (None, Some(r)) => Some(format!("..={r}")),
+        (Some(l), None) => Some(format!("{l}..")),
+        (Some(l), Some(r)) => Some(format!("{l}..={r}")),
+    };
+    if let Some(key_count) = key_count {
+        fields.push(format!("keys {key_count}"));
+    }
+
     if fields.is_empty() {
         if allows_additional {
             "{\n    ..\n}".to_string()
@@ -126,31 +531,92 @@ fn convert_object_properties(schema: &JsonSchema) -> String {
     }
 }
 
-fn json_schema_to_deval(schema: &JsonSchema) -> String {
+/// Converts a `oneOf` of `cases`, with the other keywords of `schema` applying
+/// to each case.
+fn convert_one_of<'a>(schema: &JsonSchema, cases: &[JsonSchema], ctx: &mut Context<'a>) -> String {
+    let Ok(serde_json::Value::Object(mut siblings)) = serde_json::to_value(schema) else {
+        return "any".to_string();
+    };
+    siblings.remove("oneOf");
+    let mut converted: Vec<String> = vec![];
+    for case in cases {
+        let Some(case) = with_siblings(&siblings, case) else {
+            return "any".to_string();
+        };
+        converted.push(json_schema_to_deval(&case, ctx));
+    }
+    // Cases narrowing the value in ways deval can't write convert to the same
+    // type, which would then match both and fail everything. Only the other
+    // keywords are checked then.
+    let distinguishable = converted
+        .iter()
+        .enumerate()
+        .all(|(i, case)| !converted[..i].contains(case));
+    if distinguishable {
+        return converted.join(" ^ ");
+    }
+    match serde_json::from_value(serde_json::Value::Object(siblings)) {
+        Ok(siblings) => json_schema_to_deval(&siblings, ctx),
+        Err(_) => "any".to_string(),
+    }
+}
+
+/// `case` with the keywords in `siblings` added, and its `properties` and
+/// `required` keys added to theirs.
+fn with_siblings(
+    siblings: &serde_json::Map<String, serde_json::Value>,
+    case: &JsonSchema,
+) -> Option<JsonSchema> {
+    let serde_json::Value::Object(case) = serde_json::to_value(case).ok()? else {
+        return None;
+    };
+    let mut merged = siblings.clone();
+    for (key, value) in case {
+        match (merged.get_mut(&key), value) {
+            (_, serde_json::Value::Null) => {}
+            (Some(serde_json::Value::Object(base)), serde_json::Value::Object(more))
+                if key == "properties" || key == "patternProperties" =>
+            {
+                base.extend(more)
+            }
+            (Some(serde_json::Value::Array(base)), serde_json::Value::Array(more))
+                if key == "required" =>
+            {
+                base.extend(more)
+            }
+            (_, value) => {
+                merged.insert(key, value);
+            }
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(merged)).ok()
+}
+
+fn json_schema_to_deval<'a>(schema: &JsonSchema, ctx: &mut Context<'a>) -> String {
+    if let Some(reference) = &schema.reference {
+        return convert_reference(reference, ctx);
+    }
+    if let Some(literal) = schema.const_value.as_ref().and_then(literal) {
+        return literal;
+    }
+    // An enum of values deval can't write as literals is left unchecked
+    if let Some(values) = &schema.enum_values
+        && let Some(literals) = values.iter().map(literal).collect::<Option<Vec<_>>>()
+        && !literals.is_empty()
+    {
+        return literals.join(" | ");
+    }
+    if let Some(cases) = &schema.one_of {
+        return convert_one_of(schema, cases, ctx);
+    }
     // Check if it's a type specification
     if let Some(type_field) = &schema.type_field {
         match type_field {
             JsonSchemaType::Single(type_str) => match type_str.as_str() {
-                "array" => {
-                    let len_range = match (schema.min_items, schema.max_items) {
-                        (None, None) => format!("[]"),
-                        (None, Some(r)) => format!("[..={r}]"),
-                        (Some(l), None) => format!("[{l}..]"),
-                        (Some(l), Some(r)) => format!("[{l}..={r}]"),
-                    };
-                    if let Some(items) = &schema.items {
-                        format!("{}{len_range}", json_schema_to_deval(items))
-                    } else {
-                        format!("any{len_range}")
-                    }
-                }
-                "object" => convert_object_properties(schema),
-                "number" | "integer" => convert_number_range(
-                    type_str,
-                    schema.minimum,
-                    schema.maximum,
-                    schema.exclusive_maximum,
-                ),
+                "array" => convert_array(schema, ctx),
+                "object" => convert_object_properties(schema, ctx),
+                "number" | "integer" => convert_number_range(type_str, schema),
+                "string" => convert_string(schema),
                 _ => convert_json_type(type_str),
             },
             JsonSchemaType::Multiple(type_array) => {
@@ -158,20 +624,10 @@ fn json_schema_to_deval(schema: &JsonSchema) -> String {
                 let converted_types: Vec<String> = type_array
                     .iter()
                     .map(|type_str| match type_str.as_str() {
-                        "array" => {
-                            if let Some(items) = &schema.items {
-                                format!("{}[]", json_schema_to_deval(items))
-                            } else {
-                                "any[]".to_string()
-                            }
-                        }
-                        "object" => convert_object_properties(schema),
-                        "number" | "integer" => convert_number_range(
-                            type_str,
-                            schema.minimum,
-                            schema.maximum,
-                            schema.exclusive_maximum,
-                        ),
+                        "array" => convert_array(schema, ctx),
+                        "object" => convert_object_properties(schema, ctx),
+                        "number" | "integer" => convert_number_range(type_str, schema),
+                        "string" => convert_string(schema),
                         _ => convert_json_type(type_str),
                     })
                     .collect();
@@ -183,20 +639,22 @@ fn json_schema_to_deval(schema: &JsonSchema) -> String {
                 }
             }
         }
-    } else if schema.minimum.is_some() || schema.maximum.is_some() {
+    } else if has_number_bounds(schema) {
         // Handle number constraints without explicit type
-        convert_number_range(
-            "number",
-            schema.minimum,
-            schema.maximum,
-            schema.exclusive_maximum,
-        )
+        convert_number_range("number", schema)
+    } else if schema.pattern.is_some() {
+        convert_string(schema)
     } else if !schema.properties.is_empty() {
         // Object without explicit type
-        convert_object_properties(schema)
-    } else if schema.additional_properties.is_some() {
+        convert_object_properties(schema, ctx)
+    } else if schema.additional_properties.is_some()
+        || !schema.pattern_properties.is_empty()
+        || schema.property_names.is_some()
+        || schema.min_properties.is_some()
+        || schema.max_properties.is_some()
+    {
         // For objects with additional properties but no defined properties
-        convert_object_properties(schema)
+        convert_object_properties(schema, ctx)
     } else {
         "any".to_string()
     }
@@ -262,6 +720,199 @@ mod tests {
         assert_eq!(result, "number");
     }
 
+    #[test]
+    fn test_exclusive_maximum_draft4() {
+        let json_schema =
+            r#"{"type": "number", "minimum": 0, "maximum": 10, "exclusiveMaximum": true}"#;
+        let result = convert(json_schema);
+        assert_eq!(result, "0..10");
+    }
+
+    #[test]
+    fn test_exclusive_bounds_as_values() {
+        let json_schema = r#"{"type": "integer", "exclusiveMinimum": 0, "exclusiveMaximum": 10}"#;
+        let result = convert(json_schema);
+        assert_eq!(result, "1..10");
+    }
+
+    #[test]
+    fn test_inclusive_and_exclusive_bounds() {
+        let json_schema = r#"{"type": "integer", "minimum": 5, "exclusiveMinimum": 0, "maximum": 8, "exclusiveMaximum": 20}"#;
+        assert_eq!(convert(json_schema), "5..=8");
+
+        let json_schema = r#"{"type": "integer", "minimum": 0, "exclusiveMinimum": 5, "maximum": 20, "exclusiveMaximum": 8}"#;
+        assert_eq!(convert(json_schema), "6..8");
+
+        // On a tie the exclusive bound is the stricter
+        let json_schema = r#"{"type": "integer", "minimum": 5, "exclusiveMinimum": 5, "maximum": 8, "exclusiveMaximum": 8}"#;
+        assert_eq!(convert(json_schema), "6..8");
+    }
+
+    #[test]
+    fn test_exclusive_minimum_draft4_integer() {
+        let json_schema = r#"{"type": "integer", "minimum": 3, "exclusiveMinimum": true}"#;
+        let result = convert(json_schema);
+        assert_eq!(result, "4..");
+    }
+
+    #[test]
+    fn test_multiple_of_is_ignored() {
+        let json_schema = r#"{"type": "integer", "multipleOf": 2, "maximum": 10}"#;
+        let result = convert(json_schema);
+        assert_eq!(result, "..=10");
+    }
+
+    #[test]
+    fn test_string_pattern() {
+        let json_schema = r#"{"type": "string", "pattern": "^a/b$"}"#;
+        let result = convert(json_schema);
+        assert_eq!(result, r"/^a\/b$/");
+    }
+
+    #[test]
+    fn test_const_bool() {
+        let json_schema = r#"{"type": "boolean", "const": false}"#;
+        let result = convert(json_schema);
+        assert_eq!(result, "false");
+    }
+
+    #[test]
+    fn test_ref_to_definition() {
+        let json_schema = r##"{
+            "type": "object",
+            "properties": {
+                "origin": {"$ref": "#/definitions/Point"}
+            },
+            "required": ["origin"],
+            "additionalProperties": false,
+            "definitions": {
+                "Point": {
+                    "type": "object",
+                    "properties": {"x": {"type": "number"}},
+                    "required": ["x"],
+                    "additionalProperties": false
+                }
+            }
+        }"##;
+        let result = convert(json_schema);
+        assert_eq!(result, "{\n    origin: {\n    x: number\n}\n}");
+    }
+
+    #[test]
+    fn test_ref_to_defs() {
+        let json_schema = r##"{
+            "$ref": "#/$defs/Name",
+            "$defs": {"Name": {"type": "string"}}
+        }"##;
+        let result = convert(json_schema);
+        assert_eq!(result, "string");
+    }
+
+    #[test]
+    fn test_self_referential_definition() {
+        let json_schema = r##"{
+            "$ref": "#/definitions/Node",
+            "definitions": {
+                "Node": {
+                    "type": "object",
+                    "properties": {
+                        "children": {
+                            "type": "array",
+                            "items": {"$ref": "#/definitions/Node"}
+                        }
+                    },
+                    "required": ["children"],
+                    "additionalProperties": false
+                }
+            }
+        }"##;
+        let result = convert(json_schema);
+        assert_eq!(result, "type Node = {\n    children: Node[]\n};\n\nNode");
+    }
+
+    #[test]
+    fn test_named_definitions() {
+        let json_schema = r##"{
+            "type": "object",
+            "properties": {
+                "origin": {"$ref": "#/definitions/Point"},
+                "label": {"$ref": "#/$defs/Label"}
+            },
+            "required": ["origin", "label"],
+            "additionalProperties": false,
+            "definitions": {
+                "Point": {
+                    "type": "object",
+                    "properties": {"x": {"type": "number"}},
+                    "required": ["x"],
+                    "additionalProperties": false
+                }
+            },
+            "$defs": {"Label": {"type": "string"}}
+        }"##;
+        let result = convert_named(json_schema);
+        assert_eq!(
+            result.lines().filter(|l| l.starts_with("type ")).count(),
+            2,
+            "{result}"
+        );
+        assert!(result.contains("type Point = {\n    x: number\n}"));
+        assert!(result.contains("type Label = string"));
+        assert!(result.contains("origin: Point"));
+        assert!(result.contains("label: Label"));
+        assert!(deval_schema::compile(&result).is_ok());
+
+        // Without definitions, naming them changes nothing
+        assert_eq!(convert_named(r#"{"type": "string"}"#), "string");
+    }
+
+    #[test]
+    fn test_reference_cycle_is_any() {
+        let json_schema = r##"{
+            "type": "array",
+            "items": {"$ref": "#/definitions/A"},
+            "definitions": {
+                "A": {"$ref": "#/definitions/B"},
+                "B": {"$ref": "#/definitions/A"}
+            }
+        }"##;
+        assert_eq!(convert(json_schema), "any[]");
+        assert_eq!(convert_named(json_schema), "any[]");
+    }
+
+    #[test]
+    fn test_named_definitions_avoid_clashes() {
+        let json_schema = r##"{
+            "type": "object",
+            "properties": {
+                "a": {"$ref": "#/definitions/string"},
+                "b": {"$ref": "#/definitions/Point"},
+                "c": {"$ref": "#/$defs/Point"},
+                "d": {"$ref": "#/definitions/Point1"}
+            },
+            "required": ["a", "b", "c", "d"],
+            "additionalProperties": false,
+            "definitions": {
+                "string": {"type": "integer"},
+                "Point": {"type": "number"},
+                "Point1": {"type": "boolean"}
+            },
+            "$defs": {"Point": {"type": "null"}}
+        }"##;
+        let result = convert_named(json_schema);
+        assert!(result.contains("type string1 = integer;"), "{result}");
+        assert!(result.contains("a: string1"), "{result}");
+        let mut names: Vec<_> = result
+            .lines()
+            .filter_map(|line| line.strip_prefix("type "))
+            .map(|line| line.split(' ').next().unwrap())
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 4, "{result}");
+        assert!(deval_schema::compile(&result).is_ok());
+    }
+
     #[test]
     fn test_max_only_no_type() {
         let json_schema = r#"{"maximum": 3.0}"#;
@@ -387,6 +1038,30 @@ mod tests {
         assert!(result.contains("/// The user's age"));
     }
 
+    #[test]
+    fn test_root_docs() {
+        let json_schema = r#"{
+            "title": "User",
+            "description": "A user of the system.\nLoaded at startup.",
+            "$comment": "Kept in sync with the API",
+            "type": "object",
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "description": "The user's name",
+                    "$comment": "Not unique"
+                }
+            },
+            "required": ["name"]
+        }"#;
+        let result = convert(json_schema);
+        assert!(result.starts_with(
+            "/// User\n/// A user of the system.\n/// Loaded at startup.\n/// Kept in sync with the API\n{"
+        ));
+        assert!(result.contains("/// The user's name\n    /// Not unique\n    name: string"));
+        assert!(deval_schema::compile(&result).is_ok());
+    }
+
     #[test]
     fn test_object_with_no_additional_properties() {
         let json_schema = r#"{
@@ -439,8 +1114,9 @@ mod tests {
             }
         }"#;
         let result = convert(json_schema);
-        // Should contain .. at the end since additionalProperties is a schema
-        assert!(result.contains(".."));
+        // The schema applies to the values of all other keys
+        assert!(!result.contains(".."));
+        assert!(result.contains("[string]: string"));
         assert!(result.contains("name: string"));
         assert!(result.contains("age?: integer"));
     }
@@ -510,6 +1186,126 @@ mod integration_tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_number_bounds_compilation() {
+        let json_schema = r#"{"type": "integer", "minimum": 0, "exclusiveMaximum": 10}"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(deval_schema, "0..10");
+
+        let result = compile(&deval_schema);
+        assert!(result.is_ok());
+    }
+
+    fn check(deval_schema: &str, value: &str) -> Vec<String> {
+        use deval_data_model::Format;
+        let validator = compile(deval_schema).unwrap();
+        let data = deval_format_ron::Ron.parse(value, "test.ron").unwrap();
+        validator
+            .validate(data)
+            .errors
+            .into_iter()
+            .map(|e| e.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_const_number_with_exponent() {
+        let deval_schema = convert(r#"{"const": 1e100}"#);
+        assert_eq!(deval_schema, "1e100");
+        assert!(check(&deval_schema, "1e100").is_empty());
+        assert!(!check(&deval_schema, "1").is_empty());
+    }
+
+    #[test]
+    fn test_exclusive_minimum_number_compilation() {
+        let deval_schema = convert(r#"{"type": "number", "exclusiveMinimum": 0.5}"#);
+        assert_eq!(deval_schema, "0.5<..");
+        assert!(!check(&deval_schema, "0.5").is_empty());
+        assert!(check(&deval_schema, "0.6").is_empty());
+
+        let deval_schema = convert(r#"{"type": "number", "exclusiveMinimum": 0}"#);
+        assert_eq!(deval_schema, "0<..");
+        assert!(!check(&deval_schema, "0").is_empty());
+        assert!(check(&deval_schema, "0.1").is_empty());
+
+        let deval_schema = convert(r#"{"type": "number", "exclusiveMinimum": 0, "maximum": 1}"#);
+        assert_eq!(deval_schema, "0<..=1");
+        assert!(check(&deval_schema, "1").is_empty());
+        assert!(!check(&deval_schema, "1.5").is_empty());
+    }
+
+    #[test]
+    fn test_string_enum_compilation() {
+        let deval_schema = convert(r#"{"enum": ["red", "green", "say \"hi\""]}"#);
+        assert_eq!(deval_schema, r#""red" | "green" | "say \"hi\"""#);
+        assert!(check(&deval_schema, r#""green""#).is_empty());
+        assert!(check(&deval_schema, r#""say \"hi\"""#).is_empty());
+        assert!(!check(&deval_schema, r#""blue""#).is_empty());
+    }
+
+    #[test]
+    fn test_union_items_compilation() {
+        let deval_schema = convert(r#"{"type": "array", "items": {"enum": ["a", "b"]}}"#);
+        assert_eq!(deval_schema, r#"("a" | "b")[]"#);
+        assert!(check(&deval_schema, r#"["a", "b"]"#).is_empty());
+        assert!(!check(&deval_schema, r#"["a", "c"]"#).is_empty());
+        assert!(!check(&deval_schema, r#""a""#).is_empty());
+
+        // A union nested in the element is kept
+        let deval_schema = convert(
+            r#"{
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {"a": {"enum": ["x", "y"]}},
+                    "required": ["a"],
+                    "additionalProperties": false
+                }
+            }"#,
+        );
+        assert_eq!(deval_schema, "{\n    a: \"x\" | \"y\"\n}[]");
+        assert!(!check(&deval_schema, r#"[{"a": "z"}]"#).is_empty());
+        assert!(is_union(r#"/a|b/ | string"#));
+        assert!(!is_union(r#"/a|b/"#));
+        assert!(!is_union("\"|\""));
+    }
+
+    #[test]
+    fn test_mixed_enum_compilation() {
+        let deval_schema = convert(r#"{"enum": ["a", 1, true, null]}"#);
+        assert_eq!(deval_schema, r#""a" | 1 | true | null"#);
+        assert!(check(&deval_schema, "1").is_empty());
+        assert!(check(&deval_schema, "true").is_empty());
+        assert!(!check(&deval_schema, "2").is_empty());
+        assert!(!check(&deval_schema, "false").is_empty());
+    }
+
+    #[test]
+    fn test_pattern_compilation() {
+        let json_schema = r#"{"type": "string", "pattern": "^[a-z0-9-]+$"}"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(deval_schema, "/^[a-z0-9-]+$/");
+
+        let result = compile(&deval_schema);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_recursive_ref_compilation() {
+        let json_schema = r##"{
+            "type": "object",
+            "properties": {"next": {"$ref": "#"}}
+        }"##;
+        let deval_schema = convert(json_schema);
+        assert_eq!(
+            deval_schema,
+            "type Root = {\n    next?: Root,\n    ..\n};\n\nRoot"
+        );
+
+        let result = compile(&deval_schema);
+        assert!(result.is_ok());
+    }
+
     #[test]
     fn test_single_type_compilation() {
         // Test that a schema with a single type can be converted and compiled
@@ -523,4 +1319,186 @@ mod integration_tests {
         let result = compile(&deval_schema);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_one_of_compilation() {
+        let json_schema = r#"{"oneOf": [{"type": "integer"}, {"type": "number", "maximum": 5}]}"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(deval_schema, "integer ^ ..=5");
+
+        let result = compile(&deval_schema);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_one_of_with_siblings_compilation() {
+        let json_schema = r#"{
+            "type": "object",
+            "properties": {"a": {"type": "string"}, "b": {"type": "string"}},
+            "additionalProperties": false,
+            "oneOf": [{"required": ["a"]}, {"required": ["b"]}]
+        }"#;
+        let deval_schema = convert(json_schema);
+        assert!(compile(&deval_schema).is_ok(), "{deval_schema}");
+        assert!(check(&deval_schema, r#"{"a": "x"}"#).is_empty());
+        assert!(check(&deval_schema, r#"{"b": "x"}"#).is_empty());
+        assert!(!check(&deval_schema, r#"{"a": "x", "b": "y"}"#).is_empty());
+        assert!(!check(&deval_schema, r#"{"a": 1}"#).is_empty());
+    }
+
+    #[test]
+    fn test_indistinguishable_one_of() {
+        // Neither `multipleOf` is checked, so the cases would be the same
+        let json_schema = r#"{"type": "integer", "oneOf": [{"multipleOf": 3}, {"multipleOf": 5}]}"#;
+        assert_eq!(convert(json_schema), "integer");
+        let json_schema = r#"{"oneOf": [{"multipleOf": 3}, {"multipleOf": 5}]}"#;
+        assert_eq!(convert(json_schema), "any");
+    }
+
+    #[test]
+    fn test_one_of_items() {
+        let json_schema = r#"{"type": "array", "items": {"oneOf": [{"type": "string"}, {"type": "integer"}]}, "maxItems": 2}"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(deval_schema, "(string ^ integer)[..=2]");
+        assert!(check(&deval_schema, r#"["a", 1]"#).is_empty());
+        assert!(!check(&deval_schema, r#"[true]"#).is_empty());
+        assert!(!check(&deval_schema, r#"[1.5]"#).is_empty());
+        assert!(!check(&deval_schema, r#"["a", 1, 2]"#).is_empty());
+    }
+
+    #[test]
+    fn test_unique_items_compilation() {
+        let json_schema = r#"{"type": "array", "items": {"type": "integer"}, "uniqueItems": true, "minItems": 1}"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(deval_schema, "integer[unique 1..]");
+
+        let result = compile(&deval_schema);
+        assert!(result.is_ok());
+
+        let json_schema = r#"{"type": "array", "uniqueItems": true}"#;
+        assert_eq!(convert(json_schema), "any[unique]");
+    }
+
+    #[test]
+    fn test_tuple_items_compilation() {
+        let json_schema = r#"{
+            "type": "array",
+            "items": [{"type": "string"}, {"type": "number"}, {"type": "boolean"}],
+            "additionalItems": false,
+            "minItems": 3
+        }"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(deval_schema, "[string, number, bool]");
+
+        let result = compile(&deval_schema);
+        assert!(result.is_ok());
+
+        let json_schema = r#"{"type": "array", "items": [{"type": "string"}]}"#;
+        assert_eq!(convert(json_schema), "any[]");
+
+        // Without `minItems`, the listed elements may be cut short
+        let json_schema = r#"{
+            "type": "array",
+            "items": [{"type": "string"}, {"type": "number"}],
+            "additionalItems": false
+        }"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(deval_schema, "any[..=2]");
+        assert!(check(&deval_schema, r#"["a"]"#).is_empty());
+        assert!(!check(&deval_schema, r#"["a", 1, true]"#).is_empty());
+    }
+
+    #[test]
+    fn test_string_format_compilation() {
+        let json_schema = r#"{"type": "string", "format": "date-time"}"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(deval_schema, r#"format("date-time")"#);
+
+        let result = compile(&deval_schema);
+        assert!(result.is_ok());
+
+        let json_schema = r#"{"type": "string", "format": "hostname"}"#;
+        assert_eq!(convert(json_schema), "string");
+    }
+
+    #[test]
+    fn test_property_count_compilation() {
+        let json_schema = r#"{
+            "type": "object",
+            "additionalProperties": { "type": "number" },
+            "minProperties": 1,
+            "maxProperties": 2
+        }"#;
+        let result = convert(json_schema);
+        assert_eq!(result, "{\n    [string]: number,\n    keys 1..=2\n}");
+        assert!(deval_schema::compile(&result).is_ok());
+        assert_eq!(
+            convert(r#"{ "minProperties": 1 }"#),
+            "{\n    ..,\n    keys 1..\n}"
+        );
+    }
+
+    #[test]
+    fn test_map_compilation() {
+        let json_schema = r#"{
+            "type": "object",
+            "patternProperties": {
+                "^x-": {"type": "string"}
+            },
+            "additionalProperties": {"type": "number"}
+        }"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(
+            deval_schema,
+            "{\n    [/^x-/]: string,\n    [string]: number\n}"
+        );
+
+        let result = compile(&deval_schema);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_property_names_compilation() {
+        let json_schema = r#"{
+            "type": "object",
+            "properties": {"id": {"type": "integer"}},
+            "patternProperties": {
+                "^x-": {"type": "string"},
+                "^[0-9]+$": {"type": "boolean"}
+            },
+            "propertyNames": {"pattern": "^[a-z0-9-]+$"}
+        }"#;
+        // The declared keys can't be checked against the names, so any key goes
+        let deval_schema = convert(json_schema);
+        assert_eq!(
+            deval_schema,
+            "{\n    id?: integer,\n    [/^[0-9]+$/]: bool,\n    [/^x-/]: string,\n    ..\n}"
+        );
+        assert!(
+            check(
+                &deval_schema,
+                r#"{"id": 1, "x-a": "b", "1": true, "Other": 2}"#
+            )
+            .is_empty()
+        );
+
+        let json_schema = r#"{"propertyNames": {"pattern": "^[a-z0-9-]+$"}}"#;
+        let deval_schema = convert(json_schema);
+        assert_eq!(deval_schema, "{\n    [/^[a-z0-9-]+$/]: any\n}");
+        assert_eq!(
+            check(&deval_schema, r#"{"Other": 2}"#),
+            [r#"Key "Other" does not match allowed pattern /^[a-z0-9-]+$/"#]
+        );
+
+        let json_schema = r#"{
+            "propertyNames": {"enum": ["a", "b"]},
+            "additionalProperties": {"type": "number"}
+        }"#;
+        assert_eq!(
+            convert(json_schema),
+            r#"{
+    ["a" | "b"]: number
+}"#
+        );
+    }
 }
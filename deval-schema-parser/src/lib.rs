@@ -2,7 +2,7 @@ use chumsky::prelude::*;
 use chumsky::text;
 
 use deval_schema_ast::Spanned;
-use deval_schema_ast::{Expression, RecordMatcher};
+use deval_schema_ast::{Expression, RecordMatcher, Schema, TypeDefinition};
 
 pub type Error<'a> = chumsky::error::Rich<'a, char, SimpleSpan>;
 pub use chumsky::span::SimpleSpan;
@@ -16,90 +16,271 @@ fn spanned<'a, T>(
     })
 }
 
-fn parser<'a>() -> impl Parser<'a, &'a str, Expression, extra::Err<Error<'a>>> {
+/// Whitespace and `//` comments. Doc comments (`///`) are left for the records
+/// they document.
+fn padding<'a>() -> impl Parser<'a, &'a str, (), extra::Err<Error<'a>>> + Clone {
+    let comment = just("//")
+        .then(just('/').not())
+        .then(none_of('\n').repeated());
+    text::whitespace()
+        .then(comment.then(text::whitespace()).repeated())
+        .ignored()
+}
+
+/// An operator written after a type, like `[]` or `?`.
+enum Postfix {
+    Index {
+        unique: bool,
+        index: Option<Spanned<Box<Expression>>>,
+    },
+    /// `?`, standing for `| null`. Holds the span of the `?`.
+    Nullable(std::ops::Range<usize>),
+}
+
+fn expression<'a>() -> impl Parser<'a, &'a str, Expression, extra::Err<Error<'a>>> + Clone {
     recursive(|data| {
         // Parse doc comments (/// lines)
         let doc_comment = just("///")
             .ignore_then(none_of("\n").repeated().collect::<String>())
-            .padded();
+            .padded_by(padding());
+
+        // Parse the names of a record field: the key, then its aliases after `|`
+        let key_names = text::ident()
+            .map(String::from)
+            .separated_by(just('|').padded_by(padding()))
+            .at_least(1)
+            .collect::<Vec<_>>();
+        // `ignore_case` may start the names. A key named `ignore_case` is kept
+        let key_names = text::keyword("ignore_case")
+            .padded_by(padding())
+            .ignore_then(key_names.clone())
+            .map(|names| (true, names))
+            .or(key_names.map(|names| (false, names)));
 
         // Parse a record field: docs + key + colon + data type
         let simple_key_record = doc_comment
             .repeated()
             .collect::<Vec<_>>()
             .map(|docs| docs.join("\n"))
-            .then(text::ident().map(String::from).then(just("?").or_not()))
-            .then_ignore(just(':').padded())
+            .then(key_names.then(just("?").or_not()))
+            .then_ignore(just(':').padded_by(padding()))
             .then(data.clone())
+            .then(
+                just('=')
+                    .padded_by(padding())
+                    .ignore_then(spanned(data.clone()))
+                    .or_not(),
+            )
             .map(
-                |((docs, (key, is_optional)), value)| RecordMatcher::SimpleKey {
-                    key,
-                    optional: is_optional.is_some(),
-                    docs,
-                    value,
+                |(((docs, ((ignore_case, mut names), is_optional)), value), default)| {
+                    RecordMatcher::SimpleKey {
+                        key: names.remove(0),
+                        aliases: names,
+                        ignore_case,
+                        optional: is_optional.is_some(),
+                        docs,
+                        value,
+                        default,
+                    }
                 },
             );
 
-        let any_key_record = just("..").padded().map(|_| RecordMatcher::AnyKey);
-        let record = simple_key_record.or(any_key_record);
+        // Parse a map record: [key type]: value type
+        let map_record = data
+            .clone()
+            .delimited_by(
+                just('[').padded_by(padding()),
+                just(']').padded_by(padding()),
+            )
+            .then_ignore(just(':').padded_by(padding()))
+            .then(data.clone())
+            .map(|(key, value)| RecordMatcher::Map { key, value });
+
+        let any_key_record = just("..")
+            .padded_by(padding())
+            .map(|_| RecordMatcher::AnyKey);
+        // Parse a key count: keys 2..=10. A key named `keys` is taken first
+        let key_count_record = text::keyword("keys")
+            .padded_by(padding())
+            .ignore_then(spanned(data.clone()))
+            .map(RecordMatcher::KeyCount);
+        let record = simple_key_record
+            .or(map_record)
+            .or(any_key_record)
+            .or(key_count_record);
 
         // Parse objects: { ... }
         let object = just('{')
-            .padded()
+            .padded_by(padding())
             .ignore_then(
                 record
-                    .separated_by(just(',').padded())
+                    .separated_by(just(',').padded_by(padding()))
                     .allow_trailing()
                     .collect::<Vec<_>>(),
             )
-            .padded()
-            .then_ignore(just('}').padded())
+            .padded_by(padding())
+            .then_ignore(just('}').padded_by(padding()))
             .map(Expression::Object);
 
+        // Parse boolean literals before identifiers, so `true`/`false` never reach the env
+        let bool_literal = spanned(
+            text::keyword("true")
+                .to(true)
+                .or(text::keyword("false").to(false))
+                .padded_by(padding()),
+        )
+        .map(Expression::BoolLiteral);
+
+        // Parse string literals: "text", with `\"` and `\\` escapes
+        let string = spanned(
+            just('\\')
+                .ignore_then(one_of("\"\\"))
+                .or(none_of("\"\\\n"))
+                .repeated()
+                .collect::<String>()
+                .delimited_by(just('"'), just('"')),
+        )
+        .padded_by(padding());
+        let string_literal = string.clone().map(Expression::StringLiteral);
+
+        // Parse string formats: format("name"), before `format` is taken as an ident
+        let format = text::keyword("format")
+            .padded_by(padding())
+            .ignore_then(string.delimited_by(just('('), just(')')))
+            .padded_by(padding())
+            .map(Expression::Format);
+
         // Parse basic identifiers (string, number, etc.)
-        let ident = spanned(text::ident().padded().map(String::from)).map(Expression::Ident);
+        let ident = spanned(text::ident().map(String::from))
+            .padded_by(padding())
+            .map(Expression::Ident);
+        // Parse numbers: an optional `-`, then digits with an optional fraction
+        // and exponent. The fraction needs a digit after the dot, so `1..5`
+        // stays a range
         let number = spanned(
-            text::digits(10)
-                .collect::<String>()
-                .padded()
-                .map(|x| x.parse().unwrap()),
+            just('-')
+                .or_not()
+                .then(text::digits(10))
+                .then(just('.').then(text::digits(10)).or_not())
+                .then(
+                    one_of("eE")
+                        .then(one_of("+-").or_not())
+                        .then(text::digits(10))
+                        .or_not(),
+                )
+                .to_slice()
+                .padded_by(padding())
+                .map(|x: &str| x.parse().unwrap()),
         )
         .map(Expression::Number);
 
+        // Parse regex literals: /pattern/, with `\/` standing for a literal slash
+        let regex = spanned(
+            just("\\/")
+                .to('/')
+                .or(none_of("/\n"))
+                .repeated()
+                .collect::<String>()
+                .delimited_by(just('/'), just('/')),
+        )
+        .padded_by(padding())
+        .map(Expression::Regex);
+
         let number_or_ident = number.or(ident);
         let range = spanned(number_or_ident.clone().map(Box::new))
+            .then(just('<').or_not())
             .or_not()
             .then_ignore(just(".."))
             .then(just("=").or_not())
             .then(spanned(number_or_ident.clone().map(Box::new)).or_not())
             .map(|x| Expression::Range {
-                start: x.0.0,
+                excludes_start: x.0.0.as_ref().is_some_and(|start| start.1.is_some()),
+                start: x.0.0.map(|start| start.0),
                 end: x.1,
                 is_inclusive: x.0.1.is_some(),
             });
 
+        // Parse tuples: [A, B, C]. Brackets after a type are an array index instead
+        let tuple = just('[')
+            .padded_by(padding())
+            .ignore_then(
+                data.clone()
+                    .separated_by(just(',').padded_by(padding()))
+                    .allow_trailing()
+                    .collect::<Vec<_>>(),
+            )
+            .then_ignore(just(']').padded_by(padding()))
+            .map(Expression::Tuple);
+
+        // Parse groups: (A | B), for applying an operator to a whole union
+        let group = data.clone().delimited_by(
+            just('(').padded_by(padding()),
+            just(')').padded_by(padding()),
+        );
+
+        // The index may start with `unique`, like `string[unique 1..]`
         let array_index = just("[")
-            .padded()
-            .ignore_then(spanned(data.map(Box::new)).or_not())
-            .then_ignore(just("]").padded());
+            .padded_by(padding())
+            .ignore_then(text::keyword("unique").padded_by(padding()).or_not())
+            .then(spanned(data.map(Box::new)).or_not())
+            .then_ignore(just("]").padded_by(padding()))
+            .map(|(unique, index)| Postfix::Index {
+                unique: unique.is_some(),
+                index,
+            });
+        let nullable = spanned(just('?'))
+            .padded_by(padding())
+            .map(|question| Postfix::Nullable(question.span));
 
-        // Parse arrays: type followed by []
-        let arrayable = number_or_ident
-            .or(range)
+        // Parse arrays and nullable types: type followed by [] or ?
+        let arrayable = range
+            .or(bool_literal)
+            .or(string_literal)
+            .or(format)
+            .or(number_or_ident)
+            .or(regex)
             .or(object)
-            .then(array_index.padded().repeated().collect::<Vec<_>>())
-            .map(|(base, brackets)| {
-                brackets
+            .or(tuple)
+            .or(group)
+            .then(
+                array_index
+                    .or(nullable)
+                    .padded_by(padding())
+                    .repeated()
+                    .collect::<Vec<_>>(),
+            )
+            .map(|(base, postfixes)| {
+                postfixes
                     .into_iter()
-                    .fold(base, |inner, index| Expression::Array {
-                        element: Box::new(inner),
-                        index,
+                    .fold(base, |inner, postfix| match postfix {
+                        Postfix::Index { unique, index } => Expression::Array {
+                            element: Box::new(inner),
+                            index,
+                            unique,
+                        },
+                        Postfix::Nullable(span) => Expression::Union(vec![
+                            inner,
+                            Expression::Ident(Spanned {
+                                value: "null".to_owned(),
+                                span,
+                            }),
+                        ]),
                     })
             });
 
+        // Parse negations: !A, binding looser than array brackets
+        let not = just('!')
+            .padded_by(padding())
+            .repeated()
+            .count()
+            .then(arrayable)
+            .map(|(count, inner)| {
+                (0..count).fold(inner, |inner, _| Expression::Not(Box::new(inner)))
+            });
+
         // Parse unions: A | B | C
-        let union = arrayable
-            .separated_by(just('|').padded())
+        let union = not
+            .separated_by(just('|').padded_by(padding()))
             .at_least(1)
             .collect::<Vec<_>>()
             .map(|mut items: Vec<Expression>| {
@@ -110,11 +291,286 @@ fn parser<'a>() -> impl Parser<'a, &'a str, Expression, extra::Err<Error<'a>>> {
                 }
             });
 
+        // Parse exclusive unions: A ^ B, binding looser than `|`
         union
+            .separated_by(just('^').padded_by(padding()))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .map(|mut items: Vec<Expression>| {
+                if items.len() == 1 {
+                    items.remove(0)
+                } else {
+                    Expression::OneOf(items)
+                }
+            })
     })
-    .then_ignore(end())
 }
 
-pub fn parse(source: &str) -> Result<Expression, Vec<Error<'_>>> {
+fn parser<'a>() -> impl Parser<'a, &'a str, Schema, extra::Err<Error<'a>>> {
+    // Parse type definitions: type Name = Expr
+    let definition = text::keyword("type")
+        .padded_by(padding())
+        .ignore_then(spanned(text::ident().map(String::from)))
+        .then_ignore(just('=').padded_by(padding()))
+        .then(expression())
+        .then_ignore(just(';').padded_by(padding()).or_not())
+        .map(|(name, value)| TypeDefinition { name, value });
+
+    let docs = just("///")
+        .ignore_then(none_of("\n").repeated().collect::<String>())
+        .padded_by(padding())
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(|docs| docs.join("\n"));
+
+    docs.then(definition.repeated().collect::<Vec<_>>())
+        .then(expression())
+        .then_ignore(end())
+        .map(|((docs, definitions), root)| Schema {
+            docs,
+            definitions,
+            root,
+        })
+}
+
+pub fn parse(source: &str) -> Result<Schema, Vec<Error<'_>>> {
     parser().parse(source).into_result()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_expression(source: &str) -> Expression {
+        parse(source).unwrap().root
+    }
+
+    #[test]
+    fn test_nullable_type() {
+        let Expression::Union(cases) = parse_expression("string?") else {
+            panic!("Expected a union");
+        };
+        assert!(matches!(&cases[0], Expression::Ident(x) if x.value == "string"));
+        assert!(matches!(&cases[1], Expression::Ident(x) if x.value == "null" && x.span == (6..7)));
+    }
+
+    #[test]
+    fn test_nullable_array_elements() {
+        // `?` applies to what is on its left, like `[]`
+        let Expression::Array { element, .. } = parse_expression("string?[]") else {
+            panic!("Expected an array");
+        };
+        assert!(matches!(*element, Expression::Union(_)));
+        let Expression::Union(cases) = parse_expression("string[]?") else {
+            panic!("Expected a union");
+        };
+        assert!(matches!(cases[0], Expression::Array { .. }));
+    }
+
+    #[test]
+    fn test_groups() {
+        let Expression::Array { element, .. } = parse_expression("(string | null)[]") else {
+            panic!("Expected an array");
+        };
+        assert!(matches!(*element, Expression::Union(ref cases) if cases.len() == 2));
+        let Expression::Not(inner) = parse_expression("!( \"a\" | \"b\" )") else {
+            panic!("Expected a negation");
+        };
+        assert!(matches!(*inner, Expression::Union(_)));
+    }
+
+    #[test]
+    fn test_nullable_value_and_optional_key() {
+        let Expression::Object(records) = parse_expression("{ a?: number, b: string? }") else {
+            panic!("Expected an object");
+        };
+        let [
+            RecordMatcher::SimpleKey {
+                optional: true,
+                value: Expression::Ident(_),
+                ..
+            },
+            RecordMatcher::SimpleKey {
+                optional: false,
+                value: Expression::Union(_),
+                ..
+            },
+        ] = &records[..]
+        else {
+            panic!("Unexpected records {records:?}");
+        };
+    }
+
+    #[test]
+    fn test_key_aliases() {
+        let Expression::Object(records) = parse_expression(
+            "{ user_name | userName?: string, ignore_case id: number, ignore_case: bool }",
+        ) else {
+            panic!("Expected an object");
+        };
+        let [
+            RecordMatcher::SimpleKey {
+                key: first,
+                aliases,
+                ignore_case: false,
+                optional: true,
+                ..
+            },
+            RecordMatcher::SimpleKey {
+                key: second,
+                ignore_case: true,
+                ..
+            },
+            RecordMatcher::SimpleKey {
+                key: third,
+                ignore_case: false,
+                ..
+            },
+        ] = &records[..]
+        else {
+            panic!("Unexpected records {records:?}");
+        };
+        assert_eq!(
+            (first.as_str(), &aliases[..]),
+            ("user_name", &["userName".to_owned()][..])
+        );
+        assert_eq!(second, "id");
+        assert_eq!(third, "ignore_case");
+    }
+
+    #[test]
+    fn test_map_record() {
+        let Expression::Object(records) = parse_expression("{ name: string, [string]: number[] }")
+        else {
+            panic!("Expected an object");
+        };
+        let [
+            RecordMatcher::SimpleKey { .. },
+            RecordMatcher::Map {
+                key: Expression::Ident(_),
+                value: Expression::Array { .. },
+            },
+        ] = &records[..]
+        else {
+            panic!("Unexpected records {records:?}");
+        };
+    }
+
+    #[test]
+    fn test_comments() {
+        let schema = parse(
+            "// The configuration
+            type Level = // how much to log
+                \"debug\" | // everything
+                \"error\" // only errors
+            // The root object
+            {
+                /// The log level
+                // not part of the docs
+                level: Level, // trailing
+            }
+            //",
+        )
+        .unwrap();
+        assert!(
+            matches!(&schema.definitions[0].value, Expression::Union(cases) if cases.len() == 2)
+        );
+        let Expression::Object(records) = schema.root else {
+            panic!("Expected an object");
+        };
+        let [RecordMatcher::SimpleKey { docs, .. }] = &records[..] else {
+            panic!("Unexpected records {records:?}");
+        };
+        assert_eq!(docs, " The log level");
+    }
+
+    #[test]
+    fn test_schema_docs() {
+        let schema = parse(
+            "/// Server settings
+            /// Second line
+            type Port = integer
+            { port: Port }",
+        )
+        .unwrap();
+        assert_eq!(schema.docs, " Server settings\n Second line");
+        assert_eq!(schema.definitions.len(), 1);
+        assert_eq!(parse("{}").unwrap().docs, "");
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn test_numbers() {
+        let number = |source| match parse_expression(source) {
+            Expression::Number(n) => n.value,
+            _ => panic!("Expected a number"),
+        };
+        assert_eq!(number("-2"), -2.);
+        assert_eq!(number("3.14"), 3.14);
+        assert_eq!(number("-0.5"), -0.5);
+        assert_eq!(number("1e3"), 1000.);
+        assert_eq!(number("2.5E-2"), 0.025);
+        assert_eq!(number("-1e+2"), -100.);
+
+        let Expression::Range {
+            start: Some(start),
+            end: Some(end),
+            excludes_start: false,
+            is_inclusive: true,
+        } = parse_expression("-1.0..=1.0")
+        else {
+            panic!("Expected an inclusive range");
+        };
+        assert!(matches!(*start.value, Expression::Number(ref n) if n.value == -1.));
+        assert!(matches!(*end.value, Expression::Number(ref n) if n.value == 1.));
+
+        let Expression::Range { start, end, .. } = parse_expression("1..5") else {
+            panic!("Expected a range");
+        };
+        assert!(matches!(*start.unwrap().value, Expression::Number(ref n) if n.value == 1.));
+        assert!(matches!(*end.unwrap().value, Expression::Number(ref n) if n.value == 5.));
+
+        let Expression::Range {
+            start: Some(start),
+            end: None,
+            ..
+        } = parse_expression("-5..")
+        else {
+            panic!("Expected a range without an end");
+        };
+        assert!(matches!(*start.value, Expression::Number(ref n) if n.value == -5.));
+
+        let Expression::Range {
+            start: None,
+            end: Some(end),
+            excludes_start: false,
+            is_inclusive: true,
+        } = parse_expression("..=-1")
+        else {
+            panic!("Expected an inclusive range without a start");
+        };
+        assert!(matches!(*end.value, Expression::Number(ref n) if n.value == -1.));
+
+        let Expression::Range {
+            start: Some(start),
+            end: Some(end),
+            excludes_start: true,
+            is_inclusive: true,
+        } = parse_expression("0<..=1")
+        else {
+            panic!("Expected a range excluding its start");
+        };
+        assert!(matches!(*start.value, Expression::Number(ref n) if n.value == 0.));
+        assert!(matches!(*end.value, Expression::Number(ref n) if n.value == 1.));
+
+        let Expression::Range {
+            start: Some(_),
+            end: None,
+            excludes_start: true,
+            ..
+        } = parse_expression("0.5<..")
+        else {
+            panic!("Expected a range excluding its start without an end");
+        };
+    }
+}
@@ -0,0 +1,245 @@
+//! Building validators in code, without writing schema text.
+
+use std::ops::{Bound, RangeBounds};
+
+use deval_validator::{
+    AnyValidator, ArrayValidator, BoolLiteralValidator, BoolValidator, IntegerValidator,
+    NullValidator, NumberValidator, ObjectValidator, OrValidator, RecordValidator,
+    StringLiteralValidator, StringValidator, TupleValidator, Validator,
+};
+
+use crate::Value;
+
+/// Constructors for the validators of the schema language, e.g.
+/// `Schema::object().field("x", Schema::number()).optional("y", Schema::string())`.
+pub struct Schema;
+
+impl Schema {
+    pub fn any() -> Box<dyn Validator> {
+        Box::new(AnyValidator)
+    }
+
+    pub fn string() -> Box<dyn Validator> {
+        Box::new(StringValidator)
+    }
+
+    pub fn number() -> Box<dyn Validator> {
+        Box::new(NumberValidator)
+    }
+
+    pub fn integer() -> Box<dyn Validator> {
+        Box::new(IntegerValidator)
+    }
+
+    pub fn bool() -> Box<dyn Validator> {
+        Box::new(BoolValidator)
+    }
+
+    pub fn null() -> Box<dyn Validator> {
+        Box::new(NullValidator)
+    }
+
+    /// A number in `range`, like `0..=10`. An excluded start is written `0<..10`.
+    pub fn range(range: impl RangeBounds<f64>) -> Box<dyn Validator> {
+        let (start, excludes_start) = match range.start_bound() {
+            Bound::Included(s) => (Some(*s), false),
+            Bound::Excluded(s) => (Some(*s), true),
+            Bound::Unbounded => (None, false),
+        };
+        let (end, is_inclusive) = match range.end_bound() {
+            Bound::Included(e) => (Some(*e), true),
+            Bound::Excluded(e) => (Some(*e), false),
+            Bound::Unbounded => (None, false),
+        };
+        Value::Range {
+            start,
+            end,
+            excludes_start,
+            is_inclusive,
+        }
+        .to_validator()
+    }
+
+    pub fn literal_bool(value: bool) -> Box<dyn Validator> {
+        Box::new(BoolLiteralValidator(value))
+    }
+
+    pub fn literal_string(value: impl Into<String>) -> Box<dyn Validator> {
+        Box::new(StringLiteralValidator(value.into()))
+    }
+
+    pub fn array(element: impl Into<Box<dyn Validator>>) -> Box<dyn Validator> {
+        Box::new(ArrayValidator {
+            element: element.into(),
+            min: None,
+            max: None,
+            unique: false,
+        })
+    }
+
+    pub fn tuple(elements: Vec<Box<dyn Validator>>) -> Box<dyn Validator> {
+        Box::new(TupleValidator(elements))
+    }
+
+    pub fn union(cases: Vec<Box<dyn Validator>>) -> Box<dyn Validator> {
+        Box::new(OrValidator::new(cases))
+    }
+
+    pub fn object() -> ObjectBuilder {
+        ObjectBuilder {
+            records: vec![],
+            allow_unknown: false,
+        }
+    }
+}
+
+/// Builds an object validator key by key. It can be passed wherever a validator
+/// is expected, or finished with `build`.
+pub struct ObjectBuilder {
+    records: Vec<RecordValidator>,
+    allow_unknown: bool,
+}
+
+impl ObjectBuilder {
+    fn record(mut self, key: String, value: Box<dyn Validator>, optional: bool) -> Self {
+        self.records.push(RecordValidator::SimpleKey {
+            key,
+            aliases: vec![],
+            ignore_case: false,
+            docs: String::new(),
+            value,
+            optional,
+            default: None,
+        });
+        self
+    }
+
+    /// Adds a required key.
+    pub fn field(self, key: impl Into<String>, value: impl Into<Box<dyn Validator>>) -> Self {
+        self.record(key.into(), value.into(), false)
+    }
+
+    /// Adds an optional key.
+    pub fn optional(self, key: impl Into<String>, value: impl Into<Box<dyn Validator>>) -> Self {
+        self.record(key.into(), value.into(), true)
+    }
+
+    /// Sets the docs of the last added key.
+    pub fn docs(mut self, docs: impl Into<String>) -> Self {
+        if let Some(RecordValidator::SimpleKey { docs: d, .. }) = self.records.last_mut() {
+            *d = docs.into();
+        }
+        self
+    }
+
+    /// Accepts keys other than the added ones, like `..` in a schema.
+    pub fn allow_unknown(mut self) -> Self {
+        self.allow_unknown = true;
+        self
+    }
+
+    pub fn build(self) -> Box<dyn Validator> {
+        Box::new(ObjectValidator {
+            records: self.records,
+            allow_unknown: self.allow_unknown,
+            min_keys: None,
+            max_keys: None,
+        })
+    }
+}
+
+impl From<ObjectBuilder> for Box<dyn Validator> {
+    fn from(builder: ObjectBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile;
+    use deval_data_model::{Span, SpanSet, Spanned, SpannedData};
+
+    fn span() -> SpanSet {
+        SpanSet(vec![Span {
+            filename: "test".to_owned(),
+            start: 0,
+            end: 0,
+        }])
+    }
+
+    fn number(value: f64) -> Spanned<SpannedData> {
+        Spanned {
+            value: SpannedData::Number(Spanned {
+                value: value.into(),
+                annotation: span(),
+            }),
+            annotation: span(),
+        }
+    }
+
+    #[test]
+    fn test_builder_matches_compiled_schema() {
+        let built = Schema::object()
+            .field("name", Schema::string())
+            .docs("The user name")
+            .optional("port", Schema::range(0.0..=65535.0))
+            .field(
+                "address",
+                Schema::union(vec![
+                    Schema::object()
+                        .field("city", Schema::string())
+                        .allow_unknown()
+                        .build(),
+                    Schema::null(),
+                ]),
+            )
+            .field("tags", Schema::array(Schema::literal_string("a")))
+            .build();
+        let compiled = compile(
+            r#"{
+                /// The user name
+                name: string,
+                port?: 0..=65535,
+                address: { city: string, .. } | null,
+                tags: "a"[],
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(built.explain(), compiled.explain());
+        assert_eq!(built.to_ir(), compiled.to_ir());
+    }
+
+    #[test]
+    fn test_builder_validates_data() {
+        let validator = Schema::object()
+            .field("port", Schema::range(0.0..100.0))
+            .build();
+        let data = Spanned {
+            value: SpannedData::Object(vec![(
+                Spanned {
+                    value: "port".to_owned(),
+                    annotation: span(),
+                },
+                number(100.),
+            )]),
+            annotation: span(),
+        };
+        let errors = validator.validate(data).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected 0..100, found 100");
+    }
+
+    #[test]
+    fn test_builder_excluded_start() {
+        let validator = Schema::range((Bound::Excluded(0.), Bound::Unbounded));
+        assert_eq!(validator.validate(number(0.)).errors.len(), 1);
+        assert!(
+            validator
+                .validate(number(f64::MIN_POSITIVE))
+                .errors
+                .is_empty()
+        );
+        assert_eq!(validator.describe(), "0<..");
+    }
+}
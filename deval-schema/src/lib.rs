@@ -1,19 +1,29 @@
-use std::{collections::HashMap, ops::Range};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
 
-use deval_data_model::SpannedData;
-use deval_schema_ast::Expression;
+use deval_data_model::{Span, SpanSet, Spanned, SpannedData};
+use deval_schema_ast::{Expression, Schema};
 pub use deval_schema_parser::Error;
 use deval_schema_parser::SimpleSpan;
 use deval_validator::{
-    ArrayValidator, LambdaValidator, ObjectValidator, OrValidator, RecordValidator, Validator,
+    AnyValidator, ArrayValidator, BoolLiteralValidator, BoolValidator, FormatValidator,
+    IntegerValidator, NotValidator, NullValidator, NumberLiteralValidator, NumberValidator,
+    ObjectValidator, OrValidator, RangeValidator, RecordValidator, RefValidator, RegexValidator,
+    RootValidator, StringFormat, StringLiteralValidator, StringValidator, TupleValidator,
+    Validator, XorValidator, closest_match,
 };
 
+pub mod builder;
+
 #[derive(Clone)]
 enum Value {
     Number(f64),
     Range {
         start: Option<f64>,
         end: Option<f64>,
+        excludes_start: bool,
         is_inclusive: bool,
     },
     Validator(Box<dyn Validator>),
@@ -22,23 +32,18 @@ enum Value {
 impl Value {
     fn to_validator(self) -> Box<dyn Validator> {
         match self {
-            Value::Number(_) => todo!(),
+            Value::Number(n) => Box::new(NumberLiteralValidator(n)),
             Value::Range {
                 start,
                 end,
+                excludes_start,
                 is_inclusive,
-            } => Box::new(LambdaValidator(move |d| {
-                if !matches!(&d.value, SpannedData::Number(n) if start.is_none_or(|s| s <= n.value) && end.is_none_or(|e| n.value < e || is_inclusive && n.value == e))
-                {
-                    // TODO: bad error message
-                    Some(format!(
-                        "Expected Number in range, found {}",
-                        d.value.kind()
-                    ))
-                } else {
-                    None
-                }
-            })),
+            } => Box::new(RangeValidator {
+                start,
+                end,
+                excludes_start,
+                is_inclusive,
+            }),
             Value::Validator(validator) => validator,
         }
     }
@@ -75,38 +80,138 @@ fn eval_as_number(
     }
 }
 
+/// Evaluates bounds on a count, like the length of an array. `what` names the
+/// count in errors, like `array length`.
 fn eval_as_range(
     ast: Expression,
     span: Range<usize>,
+    what: &str,
     env: &HashMap<String, Value>,
 ) -> Result<(Option<usize>, Option<usize>), Error<'static>> {
+    let error = |message: String| {
+        Error::custom(
+            SimpleSpan {
+                start: span.start,
+                end: span.end,
+                context: (),
+            },
+            message,
+        )
+    };
+    let length = |x: f64| {
+        if x < 0. || x.fract() != 0. {
+            let what = what[..1].to_uppercase() + &what[1..];
+            return Err(error(format!("{what}s must be non-negative integers")));
+        }
+        Ok(x as usize)
+    };
     let value = compile_ast(ast, env)?;
     match value {
+        // A single number is an exact length
+        Value::Number(n) => {
+            let n = length(n)?;
+            Ok((Some(n), Some(n)))
+        }
         Value::Range {
             start,
             end,
+            excludes_start,
             is_inclusive,
-        } => Ok((
-            start.map(|x| x as usize),
-            end.map(|x| x as usize + usize::from(is_inclusive) - 1),
-        )),
-        _ => Err(Error::custom(
+        } => {
+            let start = match start.map(length).transpose()? {
+                Some(start) if excludes_start => Some(start + 1),
+                start => start,
+            };
+            let end = match end.map(length).transpose()? {
+                Some(0) if !is_inclusive => {
+                    return Err(error(format!("The {what} range is empty")));
+                }
+                Some(end) if !is_inclusive => Some(end - 1),
+                end => end,
+            };
+            if let (Some(start), Some(end)) = (start, end)
+                && start > end
+            {
+                return Err(error(format!("The {what} range is empty")));
+            }
+            Ok((start, end))
+        }
+        _ => Err(error("Failed to evaluate expression as range".to_owned())),
+    }
+}
+
+/// Evaluates the default value of a key, which must be a literal accepted by
+/// `validator`. The result has no spans, since it doesn't appear in the data.
+fn eval_as_default(
+    ast: deval_schema_ast::Spanned<Expression>,
+    validator: &dyn Validator,
+) -> Result<SpannedData, Error<'static>> {
+    let error = |message: String| {
+        Error::custom(
             SimpleSpan {
-                start: span.start,
-                end: span.end,
+                start: ast.span.start,
+                end: ast.span.end,
                 context: (),
             },
-            "Failed to evaluate expression as range",
-        )),
+            message,
+        )
+    };
+    let literal = |annotation: SpanSet| match &ast.value {
+        Expression::Number(n) => Some(SpannedData::Number(Spanned {
+            value: n.value.into(),
+            annotation,
+        })),
+        Expression::BoolLiteral(b) => Some(SpannedData::Bool(Spanned {
+            value: b.value,
+            annotation,
+        })),
+        Expression::StringLiteral(s) => Some(SpannedData::String(Spanned {
+            value: s.value.clone(),
+            annotation,
+        })),
+        Expression::Ident(i) if i.value == "null" => Some(SpannedData::Null),
+        _ => None,
+    };
+    let span = SpanSet(vec![Span {
+        filename: String::new(),
+        start: ast.span.start,
+        end: ast.span.end,
+    }]);
+    let Some(value) = literal(span.clone()) else {
+        return Err(error("Default value must be a literal".to_owned()));
+    };
+    let errors = validator
+        .validate(Spanned {
+            value,
+            annotation: span,
+        })
+        .errors;
+    if let Some(e) = errors.first() {
+        return Err(error(format!("Invalid default value: {}", e.text)));
     }
+    Ok(literal(SpanSet(vec![])).unwrap())
 }
 
 fn compile_ast(ast: Expression, env: &HashMap<String, Value>) -> Result<Value, Error<'static>> {
     match ast {
         Expression::Number(x) => Ok(Value::Number(x.value)),
+        Expression::BoolLiteral(b) => Ok(Value::from_validator(BoolLiteralValidator(b.value))),
+        Expression::StringLiteral(s) => Ok(Value::from_validator(StringLiteralValidator(s.value))),
+        Expression::Regex(pattern) => match RegexValidator::new(&pattern.value) {
+            Ok(v) => Ok(Value::from_validator(v)),
+            Err(e) => Err(Error::custom(
+                SimpleSpan {
+                    start: pattern.span.start,
+                    end: pattern.span.end,
+                    context: (),
+                },
+                format!("Invalid regex: {e}"),
+            )),
+        },
         Expression::Range {
             start,
             end,
+            excludes_start,
             is_inclusive,
         } => {
             let start = match start {
@@ -120,55 +225,119 @@ fn compile_ast(ast: Expression, env: &HashMap<String, Value>) -> Result<Value, E
             Ok(Value::Range {
                 start,
                 end,
+                excludes_start,
                 is_inclusive,
             })
         }
         Expression::Ident(ident) => Ok(env
             .get(&ident.value)
             .ok_or_else(|| {
+                let suggestion = closest_match(&ident.value, env.keys().map(|name| name.as_str()))
+                    .map(|name| format!(" (did you mean '{name}'?)"))
+                    .unwrap_or_default();
                 Error::custom(
                     SimpleSpan {
                         start: ident.span.start,
                         end: ident.span.end,
                         context: (),
                     },
-                    "Unknown ident",
+                    format!("Unknown type '{}'{suggestion}", ident.value),
                 )
             })?
             .clone()),
-        Expression::Array { element, index } => {
+        Expression::Array {
+            element,
+            index,
+            unique,
+        } => {
             let (start, end) = match index {
-                Some(e) => eval_as_range(*e.value, e.span, env)?,
+                Some(e) => eval_as_range(*e.value, e.span, "array length", env)?,
                 None => (None, None),
             };
-            Ok(Value::from_validator(ArrayValidator(
-                eval_as_validator(*element, env)?,
-                start,
-                end,
-            )))
+            Ok(Value::from_validator(ArrayValidator {
+                element: eval_as_validator(*element, env)?,
+                min: start,
+                max: end,
+                unique,
+            }))
         }
-        Expression::Object(record_matchers) => Ok(Value::from_validator(ObjectValidator(
-            record_matchers
-                .into_iter()
-                .map(|r| {
-                    Ok(match r {
-                        deval_schema_ast::RecordMatcher::SimpleKey {
+        Expression::Object(record_matchers) => {
+            let mut records = vec![];
+            let mut allow_unknown = false;
+            let (mut min_keys, mut max_keys) = (None, None);
+            for r in record_matchers {
+                match r {
+                    deval_schema_ast::RecordMatcher::SimpleKey {
+                        key,
+                        aliases,
+                        ignore_case,
+                        docs,
+                        value,
+                        optional,
+                        default,
+                    } => {
+                        let value = eval_as_validator(value, env)?;
+                        let default = match default {
+                            Some(d) => Some(eval_as_default(d, &*value)?),
+                            None => None,
+                        };
+                        records.push(RecordValidator::SimpleKey {
                             key,
+                            aliases,
+                            ignore_case,
                             docs,
                             value,
                             optional,
-                        } => RecordValidator::SimpleKey {
-                            key,
-                            docs,
+                            default,
+                        })
+                    }
+                    deval_schema_ast::RecordMatcher::Map { key, value } => {
+                        records.push(RecordValidator::Map {
+                            key: eval_as_validator(key, env)?,
                             value: eval_as_validator(value, env)?,
-                            optional,
-                        },
-                        deval_schema_ast::RecordMatcher::AnyKey => RecordValidator::AnyKey,
-                    })
-                })
+                        })
+                    }
+                    deval_schema_ast::RecordMatcher::AnyKey => allow_unknown = true,
+                    deval_schema_ast::RecordMatcher::KeyCount(count) => {
+                        (min_keys, max_keys) =
+                            eval_as_range(count.value, count.span, "key count", env)?;
+                    }
+                }
+            }
+            Ok(Value::from_validator(ObjectValidator {
+                records,
+                allow_unknown,
+                min_keys,
+                max_keys,
+            }))
+        }
+        Expression::Not(inner) => Ok(Value::from_validator(NotValidator(eval_as_validator(
+            *inner, env,
+        )?))),
+        Expression::Tuple(elements) => Ok(Value::from_validator(TupleValidator(
+            elements
+                .into_iter()
+                .map(|x| eval_as_validator(x, env))
+                .collect::<Result<_, _>>()?,
+        ))),
+        Expression::Union(cases) => Ok(Value::from_validator(OrValidator::new(
+            cases
+                .into_iter()
+                .map(|x| eval_as_validator(x, env))
                 .collect::<Result<_, _>>()?,
         ))),
-        Expression::Union(cases) => Ok(Value::from_validator(OrValidator(
+        Expression::Format(name) => match StringFormat::from_name(&name.value) {
+            Some(format) => Ok(Value::from_validator(FormatValidator(format))),
+            None => Err(Error::custom(
+                SimpleSpan {
+                    start: name.span.start,
+                    end: name.span.end,
+                    context: (),
+                },
+                format!("Unknown format '{}'", name.value),
+            )),
+        },
+        Expression::OneOf(cases) => Ok(Value::from_validator(XorValidator(
             cases
                 .into_iter()
                 .map(|x| eval_as_validator(x, env))
@@ -179,65 +348,1164 @@ fn compile_ast(ast: Expression, env: &HashMap<String, Value>) -> Result<Value, E
 
 fn default_env() -> HashMap<String, Value> {
     let key_values: [(String, Value); _] = [
-        (
-            "string".to_owned(),
-            Value::from_validator(LambdaValidator(|d| {
-                if !matches!(d.value, SpannedData::String(_)) {
-                    Some(format!("Expected String, found {}", d.value.kind()))
-                } else {
-                    None
-                }
-            })),
-        ),
-        (
-            "number".to_owned(),
-            Value::from_validator(LambdaValidator(|d| {
-                if !matches!(d.value, SpannedData::Number(_)) {
-                    Some(format!("Expected Number, found {}", d.value.kind()))
-                } else {
-                    None
-                }
-            })),
-        ),
+        ("string".to_owned(), Value::from_validator(StringValidator)),
+        ("number".to_owned(), Value::from_validator(NumberValidator)),
         (
             "integer".to_owned(),
-            Value::from_validator(LambdaValidator(|d| {
-                if !matches!(&d.value, SpannedData::Number(n) if n.value.fract() == 0.) {
-                    Some(format!("Expected Integer, found {}", d.value.kind()))
-                } else {
-                    None
-                }
-            })),
-        ),
-        (
-            "null".to_owned(),
-            Value::from_validator(LambdaValidator(|d| {
-                if !matches!(d.value, SpannedData::Null) {
-                    Some(format!("Expected Null, found {}", d.value.kind()))
-                } else {
-                    None
-                }
-            })),
-        ),
-        (
-            "bool".to_owned(),
-            Value::from_validator(LambdaValidator(|d| {
-                if !matches!(d.value, SpannedData::Bool(_)) {
-                    Some(format!("Expected Bool, found {}", d.value.kind()))
-                } else {
-                    None
-                }
-            })),
-        ),
-        (
-            "any".to_owned(),
-            Value::from_validator(LambdaValidator(|_| None)),
+            Value::from_validator(IntegerValidator),
         ),
+        ("null".to_owned(), Value::from_validator(NullValidator)),
+        ("bool".to_owned(), Value::from_validator(BoolValidator)),
+        ("any".to_owned(), Value::from_validator(AnyValidator)),
     ];
     HashMap::from(key_values)
 }
 
+/// The names `expression` refers to. With `same_value`, only those validating
+/// the same value as `expression`, and not a part of it like an array element.
+fn referenced_names(expression: &Expression, same_value: bool, names: &mut Vec<String>) {
+    match expression {
+        Expression::Ident(name) => names.push(name.value.clone()),
+        Expression::Union(cases) | Expression::OneOf(cases) => {
+            for case in cases {
+                referenced_names(case, same_value, names);
+            }
+        }
+        Expression::Not(inner) => referenced_names(inner, same_value, names),
+        _ if same_value => {}
+        Expression::Array { element, .. } => referenced_names(element, same_value, names),
+        Expression::Tuple(items) => {
+            for item in items {
+                referenced_names(item, same_value, names);
+            }
+        }
+        Expression::Object(records) => {
+            for record in records {
+                match record {
+                    deval_schema_ast::RecordMatcher::SimpleKey { value, .. } => {
+                        referenced_names(value, same_value, names)
+                    }
+                    deval_schema_ast::RecordMatcher::Map { key, value } => {
+                        referenced_names(key, same_value, names);
+                        referenced_names(value, same_value, names);
+                    }
+                    deval_schema_ast::RecordMatcher::AnyKey
+                    | deval_schema_ast::RecordMatcher::KeyCount(_) => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether `to` can be reached from `from` by following at least one reference.
+fn reaches(graph: &HashMap<String, Vec<String>>, from: &str, to: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(name) = stack.pop() {
+        for next in graph.get(name).into_iter().flatten() {
+            if next == to {
+                return true;
+            }
+            if visited.insert(next.as_str()) {
+                stack.push(next);
+            }
+        }
+    }
+    false
+}
+
+fn compile_schema(schema: Schema) -> Result<Box<dyn Validator>, Error<'static>> {
+    let mut env = default_env();
+    // Every name is bound to a reference first, so definitions can refer to each
+    // other regardless of order, and to themselves.
+    let mut references = HashMap::new();
+    for definition in &schema.definitions {
+        let name = &definition.name;
+        if references.contains_key(&name.value) {
+            return Err(Error::custom(
+                SimpleSpan {
+                    start: name.span.start,
+                    end: name.span.end,
+                    context: (),
+                },
+                format!("Duplicate type definition {}", name.value),
+            ));
+        }
+        let reference = RefValidator::new(name.value.clone());
+        references.insert(name.value.clone(), reference.clone());
+        env.insert(name.value.clone(), Value::from_validator(reference));
+    }
+    let graph = |same_value| {
+        let mut graph = HashMap::new();
+        for definition in &schema.definitions {
+            let mut names = vec![];
+            referenced_names(&definition.value, same_value, &mut names);
+            names.retain(|name| references.contains_key(name));
+            graph.insert(definition.name.value.clone(), names);
+        }
+        graph
+    };
+    // A type that is itself without nesting, like `type A = B | string` with
+    // `type B = A`, would never finish validating.
+    let aliases = graph(true);
+    for definition in &schema.definitions {
+        let name = &definition.name;
+        if reaches(&aliases, &name.value, &name.value) {
+            return Err(Error::custom(
+                SimpleSpan {
+                    start: name.span.start,
+                    end: name.span.end,
+                    context: (),
+                },
+                format!("Type {} is defined in terms of itself", name.value),
+            ));
+        }
+    }
+    let graph = graph(false);
+    let mut recursive = vec![];
+    for definition in schema.definitions {
+        let name = definition.name.value;
+        let value = if reaches(&graph, &name, &name) {
+            // References within the cycle are weak, and the root keeps it alive
+            let mut env = env.clone();
+            for (other, reference) in &references {
+                if reaches(&graph, &name, other) && reaches(&graph, other, &name) {
+                    env.insert(other.clone(), Value::from_validator(reference.downgrade()));
+                }
+            }
+            recursive.push(references[&name].clone());
+            compile_ast(definition.value, &env)?
+        } else {
+            compile_ast(definition.value, &env)?
+        };
+        references[&name].set_target(value.clone().to_validator());
+        // Later uses can see the value itself, e.g. a range used as an array length
+        env.insert(name, value);
+    }
+    let root = eval_as_validator(schema.root, &env)?;
+    if recursive.is_empty() && schema.docs.is_empty() {
+        Ok(root)
+    } else {
+        Ok(Box::new(RootValidator {
+            root,
+            definitions: recursive,
+            docs: schema.docs,
+        }))
+    }
+}
+
 pub fn compile(source: &str) -> Result<Box<dyn Validator>, Vec<Error<'_>>> {
-    let ast = deval_schema_parser::parse(source)?;
-    Ok(eval_as_validator(ast, &default_env()).map_err(|e| vec![e])?)
+    let schema = deval_schema_parser::parse(source)?;
+    Ok(compile_schema(schema).map_err(|e| vec![e])?)
+}
+
+/// An error compiling a schema, which unlike `Error` doesn't borrow the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    pub message: String,
+    /// The byte range of the error in the schema source
+    pub span: Range<usize>,
+}
+
+impl From<&Error<'_>> for CompileError {
+    fn from(error: &Error<'_>) -> Self {
+        Self {
+            message: error.to_string(),
+            span: error.span().into_range(),
+        }
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Like `compile`, but with owned errors, which can be kept after the source is
+/// dropped.
+pub fn compile_owned(source: &str) -> Result<Box<dyn Validator>, Vec<CompileError>> {
+    compile(source).map_err(|errors| errors.iter().map(CompileError::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deval_data_model::AnnotatedData;
+
+    fn span() -> SpanSet {
+        SpanSet(vec![Span {
+            filename: "test".to_owned(),
+            start: 0,
+            end: 0,
+        }])
+    }
+
+    fn string(value: &str) -> Spanned<SpannedData> {
+        Spanned {
+            value: SpannedData::String(Spanned {
+                value: value.to_owned(),
+                annotation: span(),
+            }),
+            annotation: span(),
+        }
+    }
+
+    fn bool(value: bool) -> Spanned<SpannedData> {
+        Spanned {
+            value: SpannedData::Bool(Spanned {
+                value,
+                annotation: span(),
+            }),
+            annotation: span(),
+        }
+    }
+
+    fn object(pairs: Vec<(&str, Spanned<SpannedData>)>) -> Spanned<SpannedData> {
+        Spanned {
+            value: SpannedData::Object(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| {
+                        (
+                            Spanned {
+                                value: key.to_owned(),
+                                annotation: span(),
+                            },
+                            value,
+                        )
+                    })
+                    .collect(),
+            ),
+            annotation: span(),
+        }
+    }
+
+    fn number(value: f64) -> Spanned<SpannedData> {
+        Spanned {
+            value: SpannedData::Number(Spanned {
+                value: value.into(),
+                annotation: span(),
+            }),
+            annotation: span(),
+        }
+    }
+
+    fn array(items: Vec<Spanned<SpannedData>>) -> Spanned<SpannedData> {
+        Spanned {
+            value: SpannedData::Array(items),
+            annotation: span(),
+        }
+    }
+
+    /// The messages of the errors in validating `data` against `schema`.
+    fn validation_errors(schema: &str, data: Spanned<SpannedData>) -> Vec<String> {
+        compile(schema)
+            .unwrap()
+            .validate(data)
+            .errors
+            .into_iter()
+            .map(|e| e.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_range_endpoints() {
+        let accepts = |schema: &str, value: f64| {
+            compile(schema)
+                .unwrap()
+                .validate(number(value))
+                .errors
+                .is_empty()
+        };
+        assert!(accepts("1..10", 9.));
+        assert!(!accepts("1..10", 10.));
+        assert!(accepts("1..10", 1.));
+        assert!(!accepts("1..10", 0.5));
+        assert!(accepts("1..=10", 10.));
+        assert!(!accepts("1..=10", 10.5));
+        assert!(accepts("..=5", -100.));
+        assert!(accepts("..=5", 5.));
+        assert!(!accepts("..=5", 5.5));
+        assert!(accepts("5..", 5.));
+        assert!(accepts("5..", 1e9));
+        assert!(!accepts("5..", 4.));
+        assert!(accepts("-10..=10", -10.));
+        assert!(!accepts("-10..=10", -11.));
+        assert!(accepts("..=-1", -1.));
+        assert!(!accepts("..=-1", 0.));
+        assert!(accepts("1e3..", 1000.));
+        assert!(!accepts("1e3..", 999.));
+        assert!(!accepts("0<..1", 0.));
+        assert!(accepts("0<..1", 1e-300));
+        assert!(accepts("0.5<..=1", 1.));
+        assert!(!accepts("0.5<..=1", 0.5));
+        assert!(accepts("..", -1.5));
+        assert!(
+            !compile("..")
+                .unwrap()
+                .validate(string("1"))
+                .errors
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_type_alias() {
+        let validator = compile(
+            "type Name = string
+            { first: Name, last: Name }",
+        )
+        .unwrap();
+        let data = object(vec![("first", string("a")), ("last", bool(true))]);
+        let errors = validator.validate(data).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected String, found Bool");
+    }
+
+    #[test]
+    fn test_type_alias_forward_reference() {
+        let validator = compile(
+            "type Line = { from: Point, to: Point }
+            type Point = { x: number, y: number }
+            Line",
+        )
+        .unwrap();
+        let point = || object(vec![("x", bool(false))]);
+        let errors = validator
+            .validate(object(vec![("from", point()), ("to", point())]))
+            .errors;
+        // Each point has a wrongly typed `x` and is missing `y`
+        assert_eq!(errors.len(), 4);
+    }
+
+    #[test]
+    fn test_recursive_type_alias() {
+        let validator = compile(
+            "type Tree = { name: string, children?: Tree[] }
+            Tree",
+        )
+        .unwrap();
+        let leaf = object(vec![("name", string("leaf"))]);
+        let bad_leaf = object(vec![("name", bool(true))]);
+        let tree = object(vec![
+            ("name", string("root")),
+            ("children", array(vec![leaf, bad_leaf])),
+        ]);
+        let errors = validator.validate(tree).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected String, found Bool");
+    }
+
+    #[test]
+    fn test_duplicate_type_alias() {
+        assert!(compile("type A = string type A = number A").is_err());
+    }
+
+    #[test]
+    fn test_type_defined_as_itself() {
+        for source in [
+            "type T = T T",
+            "type A = B type B = A A",
+            "type A = B | string type B = !A A",
+        ] {
+            let errors = compile(source).unwrap_err();
+            assert!(
+                errors[0]
+                    .to_string()
+                    .ends_with("is defined in terms of itself"),
+                "{source}: {}",
+                errors[0]
+            );
+        }
+    }
+
+    #[test]
+    fn test_forward_alias_to_number() {
+        let validator = compile("type A = B type B = 5 A").unwrap();
+        assert!(validator.validate(number(5.0)).errors.is_empty());
+        assert_eq!(validator.validate(number(6.0)).errors.len(), 1);
+    }
+
+    #[test]
+    fn test_mutually_recursive_types() {
+        let validator = compile(
+            "type A = { b?: B }
+            type B = { a?: A, x?: number }
+            A",
+        )
+        .unwrap();
+        let data = object(vec![(
+            "b",
+            object(vec![(
+                "a",
+                object(vec![("b", object(vec![("x", bool(true))]))]),
+            )]),
+        )]);
+        let errors = validator.validate(data).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected Number, found Bool");
+    }
+
+    #[test]
+    fn test_schema_type_annotation() {
+        let validator = compile("{ port: 0..=65535, tags?: string[] }").unwrap();
+        let result = validator
+            .validate(object(vec![("port", bool(true))]))
+            .result;
+        let AnnotatedData::Object(pairs) = result.value else {
+            panic!("Expected object");
+        };
+        let (key, value) = &pairs[0];
+        assert_eq!(
+            key.annotation.schema_type.as_deref(),
+            Some("port: 0..=65535")
+        );
+        let AnnotatedData::Bool(b) = &value.value else {
+            panic!("Expected bool");
+        };
+        assert_eq!(b.annotation.schema_type.as_deref(), Some("0..=65535"));
+        assert_eq!(validator.describe(), "{ port: 0..=65535, tags?: string[] }");
+    }
+
+    #[test]
+    fn test_regex_match() {
+        let validator = compile(r"/^[a-z0-9-]+$/").unwrap();
+        assert!(validator.validate(string("my-slug-1")).errors.is_empty());
+    }
+
+    #[test]
+    fn test_regex_non_match() {
+        let validator = compile(r"/^[a-z0-9-]+$/").unwrap();
+        let errors = validator.validate(string("Foo!")).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Value 'Foo!' does not match /^[a-z0-9-]+$/");
+    }
+
+    #[test]
+    fn test_regex_escaped_slash() {
+        let validator = compile(r"/^a\/b$/").unwrap();
+        assert!(validator.validate(string("a/b")).errors.is_empty());
+    }
+
+    #[test]
+    fn test_regex_invalid_pattern() {
+        let errors = compile(r"{ slug: /[a-z/ }").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("Invalid regex"));
+    }
+
+    #[test]
+    fn test_bool_literal() {
+        let validator = compile("false").unwrap();
+        assert!(validator.validate(bool(false)).errors.is_empty());
+        let errors = validator.validate(bool(true)).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected false, found true");
+    }
+
+    #[test]
+    fn test_bool_literal_is_not_ident_prefix() {
+        assert!(compile("{ legacy: false, truthy: bool }").is_ok());
+        assert!(compile("falsey").is_err());
+    }
+
+    #[test]
+    fn test_tuple() {
+        let validator = compile("{ pair: [string, bool], pairs?: [string, bool][] }").unwrap();
+        let data = object(vec![("pair", array(vec![string("a"), bool(true)]))]);
+        assert!(validator.validate(data).errors.is_empty());
+        assert_eq!(
+            validator.describe(),
+            "{ pair: [string, bool], pairs?: [string, bool][] }"
+        );
+    }
+
+    #[test]
+    fn test_tuple_wrong_length() {
+        let validator = compile("[string, bool]").unwrap();
+        let errors = validator
+            .validate(array(vec![string("a"), bool(true), bool(false)]))
+            .errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected 2 elements, found 3");
+    }
+
+    #[test]
+    fn test_tuple_element_type() {
+        let validator = compile("[string, bool]").unwrap();
+        let errors = validator
+            .validate(array(vec![bool(true), bool(true)]))
+            .errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "element 0: Expected String, found Bool");
+    }
+
+    #[test]
+    fn test_object_rejects_unknown_keys() {
+        let validator = compile("{ name: string }").unwrap();
+        let errors = validator
+            .validate(object(vec![("name", string("a")), ("extra", bool(true))]))
+            .errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Unexpected key extra");
+    }
+
+    #[test]
+    fn test_object_allows_unknown_keys() {
+        // Known keys are still checked, even when `..` comes first
+        let validator = compile("{ .., name: string }").unwrap();
+        let errors = validator
+            .validate(object(vec![("name", bool(true)), ("extra", bool(true))]))
+            .errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected String, found Bool");
+        assert_eq!(validator.describe(), "{ name: string, .. }");
+    }
+
+    #[test]
+    fn test_map() {
+        let validator = compile("{ [string]: number }").unwrap();
+        let errors = validator
+            .validate(object(vec![("a", number(1.)), ("b", number(2.))]))
+            .errors;
+        assert!(errors.is_empty());
+        let errors = validator
+            .validate(object(vec![("a", number(1.)), ("b", string("x"))]))
+            .errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected Number, found String");
+        assert_eq!(validator.describe(), "{ [string]: number }");
+    }
+
+    #[test]
+    fn test_map_key_type() {
+        // Exact keys are matched first, and keys must match the key type of a map
+        let validator = compile("{ version: string, [/^x-/]: number }").unwrap();
+        let errors = validator
+            .validate(object(vec![
+                ("version", string("1")),
+                ("x-port", number(80.)),
+                ("port", number(80.)),
+            ]))
+            .errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].text,
+            r#"Key "port" does not match allowed pattern /^x-/"#
+        );
+    }
+
+    #[test]
+    fn test_string_literal() {
+        let validator = compile(r#""a \"quoted\" name""#).unwrap();
+        assert!(
+            validator
+                .validate(string(r#"a "quoted" name"#))
+                .errors
+                .is_empty()
+        );
+        let errors = validator.validate(string("other")).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].text,
+            r#"Expected "a \"quoted\" name", found "other""#
+        );
+    }
+
+    #[test]
+    fn test_discriminated_union() {
+        let validator = compile(
+            r#"type Circle = { kind: "circle", radius: number }
+            type Square = { kind: "square", side: number }
+            { shape: Circle | Square }"#,
+        )
+        .unwrap();
+        // Both cases have two errors here, so without the discriminant the circle
+        // case would be reported
+        let square = object(vec![
+            ("kind", string("square")),
+            ("radius", string("1")),
+            ("side", string("big")),
+        ]);
+        let errors = validator.validate(object(vec![("shape", square)])).errors;
+        let texts: Vec<_> = errors.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["Unexpected key radius", "Expected Number, found String"]
+        );
+    }
+
+    #[test]
+    fn test_discriminant_through_forward_alias() {
+        let validator = compile(
+            r#"type Shape = Circle | Square
+            type Circle = { kind: "circle", radius: number }
+            type Square = { kind: "square", side: number }
+            { shape: Shape }"#,
+        )
+        .unwrap();
+        let square = object(vec![
+            ("kind", string("square")),
+            ("radius", string("1")),
+            ("side", string("big")),
+        ]);
+        let errors = validator.validate(object(vec![("shape", square)])).errors;
+        let texts: Vec<_> = errors.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["Unexpected key radius", "Expected Number, found String"]
+        );
+    }
+
+    #[test]
+    fn test_recursive_case_has_no_discriminant() {
+        // A case referring back to the union is only weakly held, so its records
+        // can't be read to infer a discriminant, and the closest case is reported
+        let validator = compile(
+            r#"type Node = { kind: "leaf" } | Branch
+            type Branch = { kind: "branch", children: Node[] }
+            Node"#,
+        )
+        .unwrap();
+        let errors = validator.validate(object(vec![("kind", string("branch"))]));
+        let texts: Vec<_> = errors.errors.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts, [r#"Expected "leaf", found "branch""#]);
+    }
+
+    #[test]
+    fn test_union_without_discriminant() {
+        let validator = compile(r#"{ kind: "a", x: string } | { kind: "a", y: string }"#).unwrap();
+        let errors = validator
+            .validate(object(vec![("kind", string("a")), ("y", bool(true))]))
+            .errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Expected String, found Bool");
+    }
+
+    #[test]
+    fn test_explain() {
+        let validator = compile(
+            "{
+                /// The user name
+                name: string,
+                tags?: string[1..],
+                address: { city: string, .. } | null,
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            validator.explain(),
+            "{
+    /// The user name
+    name: string,
+    tags?: string[1..],
+    address: {
+        city: string,
+        ..,
+    } | null,
+}"
+        );
+    }
+
+    #[test]
+    fn test_explain_schema_docs() {
+        let validator = compile(
+            "/// Server settings
+            /// Second line
+            type Port = integer
+            { port: Port }",
+        )
+        .unwrap();
+        assert_eq!(
+            validator.explain(),
+            "/// Server settings
+/// Second line
+{
+    port: integer,
+}"
+        );
+    }
+
+    #[test]
+    fn test_explain_grouping() {
+        let validator = compile("{ a: string?[], b: !string?, c: string? | number }").unwrap();
+        assert_eq!(
+            validator.explain(),
+            "{
+    a: (string | null)[],
+    b: !(string | null),
+    c: string | null | number,
+}"
+        );
+        // The grouping reads back as the same schema
+        let reparsed = compile(&validator.explain()).unwrap();
+        assert_eq!(reparsed.explain(), validator.explain());
+    }
+
+    #[test]
+    fn test_schema_ir() {
+        use deval_validator::{FieldIr, SchemaIr};
+
+        let ir = compile(r#"{ port?: 0..=65535, tags: "a"[1..], mode: "a" | 1 }"#)
+            .unwrap()
+            .to_ir();
+        let field = |value, optional| FieldIr {
+            value,
+            aliases: vec![],
+            ignore_case: false,
+            optional,
+            default: None,
+        };
+        let expected = SchemaIr::Object {
+            fields: [
+                (
+                    "port".to_owned(),
+                    field(
+                        SchemaIr::Range {
+                            start: Some(0.),
+                            end: Some(65535.),
+                            excludes_start: false,
+                            is_inclusive: true,
+                        },
+                        true,
+                    ),
+                ),
+                (
+                    "tags".to_owned(),
+                    field(
+                        SchemaIr::Array {
+                            element: Box::new(SchemaIr::StringLiteral("a".to_owned())),
+                            min: Some(1),
+                            max: None,
+                            unique: false,
+                        },
+                        false,
+                    ),
+                ),
+                (
+                    "mode".to_owned(),
+                    field(
+                        SchemaIr::Or(vec![
+                            SchemaIr::StringLiteral("a".to_owned()),
+                            SchemaIr::NumberLiteral(1.),
+                        ]),
+                        false,
+                    ),
+                ),
+            ]
+            .into(),
+            maps: vec![],
+            allow_unknown: false,
+            min_keys: None,
+            max_keys: None,
+        };
+        assert_eq!(ir, expected);
+        // Field order and docs don't change the structure, but types do
+        let reordered = compile(
+            r#"{ mode: "a" | 1,
+                /// Tags
+                tags: "a"[1..], port?: 0..=65535 }"#,
+        );
+        assert_eq!(reordered.unwrap().to_ir(), expected);
+        let changed = compile(r#"{ port: 0..=65535, tags: "a"[1..], mode: "a" | 1 }"#);
+        assert_ne!(changed.unwrap().to_ir(), expected);
+    }
+
+    #[test]
+    fn test_schema_ir_of_reordered_definitions() {
+        let ir = |source| compile(source).unwrap().to_ir();
+        let expected = ir("type Point = { x: number, y: number }
+            type Line = { from: Point, to: Point }
+            Line[]");
+        assert_eq!(
+            ir("type Line = { from: Point, to: Point }
+                type Point = { x: number, y: number }
+                Line[]"),
+            expected
+        );
+        let tree = ir("type Tree = { children: Forest }
+            type Forest = Tree[]
+            Tree");
+        assert_eq!(
+            ir("type Forest = Tree[]
+                type Tree = { children: Forest }
+                Tree"),
+            tree
+        );
+    }
+
+    #[test]
+    fn test_compile_owned_errors() {
+        let errors = {
+            let source = String::from("{ a: integer[unknown] }");
+            compile_owned(&source).unwrap_err()
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, 13..20);
+        assert_eq!(
+            errors[0].message,
+            compile("{ a: integer[unknown] }").unwrap_err()[0].to_string()
+        );
+        assert!(compile_owned("{ a: integer }").is_ok());
+    }
+
+    #[test]
+    fn test_key_aliases() {
+        let validator =
+            compile("{ user_name | userName: string, ignore_case Port?: integer }").unwrap();
+        let r = validator.validate(object(vec![
+            ("userName", string("a")),
+            ("PORT", number(80.)),
+        ]));
+        assert!(r.errors.is_empty());
+        let AnnotatedData::Object(pairs) = r.result.value else {
+            panic!("Expected object");
+        };
+        // Keys matched by an alias or case get the name in the schema
+        let keys: Vec<_> = pairs.iter().map(|(k, _)| k.value.as_str()).collect();
+        assert_eq!(keys, ["user_name", "Port"]);
+
+        // Aliases are case sensitive without `ignore_case`, and count as the key
+        let errors = validator
+            .validate(object(vec![("username", string("a"))]))
+            .errors;
+        assert_eq!(errors[0].text, "Unexpected key username");
+        let errors = validator
+            .validate(object(vec![
+                ("user_name", string("a")),
+                ("userName", string("b")),
+            ]))
+            .errors;
+        assert_eq!(errors[0].text, "Duplicate key user_name");
+        assert_eq!(
+            validator.describe(),
+            "{ user_name | userName: string, ignore_case Port?: integer }"
+        );
+    }
+
+    #[test]
+    fn test_default_value() {
+        let validator =
+            compile(r#"{ host: string, port?: integer = 8080, name?: string = "x" }"#).unwrap();
+        let r = validator.validate(object(vec![
+            ("host", string("localhost")),
+            ("name", string("y")),
+        ]));
+        assert!(r.errors.is_empty());
+        let AnnotatedData::Object(pairs) = r.result.value else {
+            panic!("Expected object");
+        };
+        let keys: Vec<_> = pairs.iter().map(|(k, _)| k.value.as_str()).collect();
+        assert_eq!(keys, ["host", "name", "port"]);
+        // A written key overrides its default
+        assert!(matches!(&pairs[1].1.value, AnnotatedData::String(s) if s.value == "y"));
+        let (key, value) = &pairs[2];
+        assert!(key.annotation.span.0.is_empty());
+        assert!(matches!(&value.value, AnnotatedData::Number(n) if n.value == 8080.));
+        assert_eq!(
+            validator.describe(),
+            r#"{ host: string, port?: integer = 8080, name?: string = "x" }"#
+        );
+    }
+
+    #[test]
+    fn test_invalid_default_value() {
+        let errors = compile(r#"{ port?: integer = "x" }"#).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("Invalid default value"));
+        assert!(compile("{ port?: integer = string }").is_err());
+    }
+
+    #[test]
+    fn test_error_path() {
+        let validator = compile("{ servers: { host: string, port: integer }[] }").unwrap();
+        let data = object(vec![(
+            "servers",
+            array(vec![
+                object(vec![("host", string("a")), ("port", number(80.))]),
+                object(vec![("host", string("b")), ("port", string("x"))]),
+                object(vec![("hots", string("c")), ("port", number(80.))]),
+            ]),
+        )]);
+        let errors = validator.validate(data).errors;
+        let paths: Vec<_> = errors.iter().map(|e| (e.pointer(), &*e.text)).collect();
+        assert_eq!(
+            paths,
+            [
+                (
+                    "/servers/1/port".to_owned(),
+                    "Expected Integer, found String"
+                ),
+                ("/servers/2/hots".to_owned(), "Unexpected key hots"),
+                ("/servers/2".to_owned(), "Missing key host"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not() {
+        let validator = compile("!null").unwrap();
+        let five = Spanned {
+            value: SpannedData::Number(Spanned {
+                value: 5.0.into(),
+                annotation: span(),
+            }),
+            annotation: span(),
+        };
+        assert!(validator.validate(five).errors.is_empty());
+        let null = Spanned {
+            value: SpannedData::Null,
+            annotation: span(),
+        };
+        let errors = validator.validate(null).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Value must not match null");
+    }
+
+    #[test]
+    fn test_not_precedence() {
+        let validator = compile("!null | bool | !string[]").unwrap();
+        assert_eq!(validator.describe(), "!null | bool | !string[]");
+        // `!` applies to the whole array type
+        let errors = compile("{ a: !string[] }")
+            .unwrap()
+            .validate(object(vec![("a", array(vec![string("a")]))]))
+            .errors;
+        assert_eq!(errors[0].text, "Value must not match string[]");
+    }
+
+    #[test]
+    fn test_one_of() {
+        let validator = compile(r#"string ^ "a" | null"#).unwrap();
+        assert_eq!(validator.describe(), r#"string ^ "a" | null"#);
+        assert!(validator.validate(string("b")).errors.is_empty());
+        let errors = validator.validate(string("a")).errors;
+        assert_eq!(
+            errors[0].text,
+            "Value matched 2 of the oneOf branches, expected exactly 1"
+        );
+    }
+
+    #[test]
+    fn test_unique_array_numbers() {
+        let validator = compile("number[unique]").unwrap();
+        assert_eq!(validator.describe(), "number[unique]");
+        let data = array(vec![number(1.), number(2.), number(3.)]);
+        assert!(validator.validate(data).errors.is_empty());
+        let data = array(vec![number(1.), number(2.), number(3.), number(2.)]);
+        let errors = validator.validate(data).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Duplicate array item at index 3");
+    }
+
+    #[test]
+    fn test_array_length() {
+        let errors =
+            |schema: &str, len: usize| validation_errors(schema, array(vec![string("a"); len]));
+        assert!(errors("string[3]", 3).is_empty());
+        assert_eq!(
+            errors("string[3]", 4),
+            ["Array has 4 elements, expected exactly 3"]
+        );
+        assert!(errors("string[2..]", 2).is_empty());
+        assert_eq!(
+            errors("string[2..]", 1),
+            ["Array has 1 elements, expected at least 2"]
+        );
+        assert!(errors("string[..=4]", 0).is_empty());
+        assert_eq!(
+            errors("string[..=4]", 5),
+            ["Array has 5 elements, expected at most 4"]
+        );
+        assert_eq!(
+            errors("string[2..=4]", 5),
+            ["Array has 5 elements, expected at most 4"]
+        );
+        assert!(errors("string[2..5]", 4).is_empty());
+        assert_eq!(
+            errors("string[2..5]", 5),
+            ["Array has 5 elements, expected at most 4"]
+        );
+        assert_eq!(
+            errors("string[2<..]", 2),
+            ["Array has 2 elements, expected at least 3"]
+        );
+
+        assert!(compile("string[..0]").is_err());
+        assert!(compile("string[5..=2]").is_err());
+        assert!(compile("string[1..1]").is_err());
+        assert!(compile("string[1..2]").is_ok());
+        assert!(compile("string[1<..2]").is_err());
+        assert!(compile("string[1.5]").is_err());
+        assert!(compile("string[-1..]").is_err());
+    }
+
+    #[test]
+    fn test_key_count() {
+        let schema = "{ name: string, [string]: number, keys 2..=3 }";
+        let errors = |pairs| validation_errors(schema, object(pairs));
+        // Declared keys count toward the total
+        assert!(errors(vec![("name", string("a")), ("x", number(1.))]).is_empty());
+        assert_eq!(
+            errors(vec![("name", string("a"))]),
+            ["Object has 1 property, expected at least 2"]
+        );
+        assert_eq!(
+            errors(vec![
+                ("name", string("a")),
+                ("x", number(1.)),
+                ("y", number(2.)),
+                ("z", number(3.)),
+            ]),
+            ["Object has 4 properties, expected at most 3"]
+        );
+        assert_eq!(compile(schema).unwrap().describe(), schema);
+
+        // `keys` is still usable as a key name
+        assert!(compile("{ keys: string[] }").is_ok());
+        assert_eq!(
+            compile("{ .., keys 1.5 }").err().unwrap()[0].to_string(),
+            "Key counts must be non-negative integers"
+        );
+    }
+
+    #[test]
+    fn test_multi_dimensional_array() {
+        let grid = |rows: &[usize]| {
+            array(
+                rows.iter()
+                    .map(|&len| array(vec![number(0.); len]))
+                    .collect(),
+            )
+        };
+        let errors = |schema: &str, rows: &[usize]| validation_errors(schema, grid(rows));
+
+        let validator = compile("number[3][3]").unwrap();
+        assert_eq!(validator.describe(), "number[3][3]");
+        assert!(errors("number[3][3]", &[3, 3, 3]).is_empty());
+        assert_eq!(
+            errors("number[3][3]", &[3, 3]),
+            ["Array has 2 elements, expected exactly 3"]
+        );
+        assert_eq!(
+            errors("number[3][3]", &[3, 2, 3]),
+            ["Array has 2 elements, expected exactly 3"]
+        );
+
+        // The last brackets constrain the outer array
+        assert!(errors("number[2..=3][1..]", &[2, 3, 3, 2]).is_empty());
+        assert_eq!(
+            errors("number[2..=3][1..]", &[2, 4]),
+            ["Array has 4 elements, expected at most 3"]
+        );
+        assert_eq!(
+            errors("number[2..=3][1..]", &[]),
+            ["Array has 0 elements, expected at least 1"]
+        );
+    }
+
+    #[test]
+    fn test_unique_array_objects() {
+        let validator = compile("{ id: number, tags: string[] }[unique ..=3]").unwrap();
+        assert_eq!(
+            validator.describe(),
+            "{ id: number, tags: string[] }[unique ..=3]"
+        );
+        let item = |id, tag| object(vec![("id", number(id)), ("tags", array(vec![string(tag)]))]);
+        let data = array(vec![item(1., "a"), item(1., "b")]);
+        assert!(validator.validate(data).errors.is_empty());
+        let data = array(vec![item(1., "a"), item(1., "b"), item(1., "a")]);
+        let errors = validator.validate(data).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Duplicate array item at index 2");
+    }
+
+    #[test]
+    fn test_nullable_value() {
+        let validator = compile("{ middle_name: string? }").unwrap();
+        assert_eq!(validator.describe(), "{ middle_name: string | null }");
+        let null = Spanned {
+            value: SpannedData::Null,
+            annotation: span(),
+        };
+        assert!(
+            validator
+                .validate(object(vec![("middle_name", null)]))
+                .errors
+                .is_empty()
+        );
+        assert!(
+            validator
+                .validate(object(vec![("middle_name", string("J"))]))
+                .errors
+                .is_empty()
+        );
+        // The key itself is still required
+        let errors = validator.validate(object(vec![])).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Missing key middle_name");
+    }
+
+    #[test]
+    fn test_optional_key_is_not_nullable() {
+        let validator = compile("{ middle_name?: string }").unwrap();
+        assert!(validator.validate(object(vec![])).errors.is_empty());
+        let null = Spanned {
+            value: SpannedData::Null,
+            annotation: span(),
+        };
+        let errors = validator
+            .validate(object(vec![("middle_name", null)]))
+            .errors;
+        assert_eq!(errors[0].text, "Expected String, found Null");
+    }
+
+    #[test]
+    fn test_unknown_type_suggestion() {
+        let errors = compile("{ name: strng }").unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Unknown type 'strng' (did you mean 'string'?)"
+        );
+        assert_eq!(errors[0].span().into_range(), 8..13);
+
+        let errors = compile("type Address = { city: string }\n{ home: Adress }").unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Unknown type 'Adress' (did you mean 'Address'?)"
+        );
+
+        let errors = compile("{ name: foo }").unwrap_err();
+        assert_eq!(errors[0].to_string(), "Unknown type 'foo'");
+    }
+
+    #[test]
+    fn test_string_format() {
+        let validator = compile(r#"{ email: format("email"), at?: format("date-time") }"#).unwrap();
+        assert_eq!(
+            validator.describe(),
+            r#"{ email: format("email"), at?: format("date-time") }"#
+        );
+        assert!(
+            validator
+                .validate(object(vec![("email", string("a@b.c"))]))
+                .errors
+                .is_empty()
+        );
+        let errors = validator
+            .validate(object(vec![("email", string("foo"))]))
+            .errors;
+        assert_eq!(errors[0].text, r#""foo" is not a valid email"#);
+    }
+
+    #[test]
+    fn test_unknown_string_format() {
+        let errors = compile(r#"format("emial")"#).unwrap_err();
+        assert_eq!(errors[0].to_string(), "Unknown format 'emial'");
+        assert_eq!(errors[0].span().into_range(), 7..14);
+    }
 }
@@ -1,12 +1,25 @@
 use std::fmt::Display;
 
-use deval_data_model::{Annotated, AnnotatedData};
+use deval_data_model::{Annotated, AnnotatedData, Number};
 use serde::{
     Deserialize, Deserializer,
     de::{self, MapAccess, SeqAccess, Visitor},
 };
 
+/// Deserializes `R` from parsed data. Panics if the data doesn't have the shape
+/// of `R`, see `try_deserialize_from_annotated` for reporting that instead.
 pub fn deserialize_from_annotated<'a, R>(data: &'a Annotated<AnnotatedData<()>, ()>) -> R
+where
+    R: Deserialize<'a>,
+{
+    try_deserialize_from_annotated(data).unwrap()
+}
+
+/// Like `deserialize_from_annotated`, but returns an error message if the data
+/// doesn't have the shape of `R`.
+pub fn try_deserialize_from_annotated<'a, R>(
+    data: &'a Annotated<AnnotatedData<()>, ()>,
+) -> Result<R, String>
 where
     R: Deserialize<'a>,
 {
@@ -30,6 +43,9 @@ where
         }
     }
 
+    /// Deserializes an object key. Keys are always text, so a key asked for as
+    /// a number or bool is parsed from it, or visited as a string if it isn't
+    /// one, to fail with the usual type error.
     struct MyStringDeserializer<'b>(&'b Annotated<String, ()>);
 
     impl<'b> Deserializer<'b> for MyStringDeserializer<'b> {
@@ -46,77 +62,110 @@ where
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_bool(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_i8(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_i16(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_i32(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_i64(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_u8(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_u16(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_u32(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_u64(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_f32(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match self.0.value.parse() {
+                Ok(x) => visitor.visit_f64(x),
+                Err(_) => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -306,10 +355,11 @@ where
         }
     }
 
+    /// An externally tagged enum variant: either a bare string naming a unit
+    /// variant, or an object with the variant name as its only key.
     struct MyEnumAccess<'b> {
-        tag: String,
+        tag: &'b str,
         value: Option<&'b Annotated<AnnotatedData<()>, ()>>,
-        variants: std::slice::Iter<'b, (Annotated<String, ()>, Annotated<AnnotatedData<()>, ()>)>,
     }
 
     impl<'b> de::EnumAccess<'b> for MyEnumAccess<'b> {
@@ -320,9 +370,7 @@ where
         where
             V: de::DeserializeSeed<'b>,
         {
-            // For externally tagged enums, we would have already determined the variant
-            // For internally tagged enums, we need to find the tag field
-            let variant_value = seed.deserialize(de::value::StrDeserializer::new(&self.tag))?;
+            let variant_value = seed.deserialize(de::value::StrDeserializer::new(self.tag))?;
             Ok((variant_value, self))
         }
     }
@@ -349,7 +397,10 @@ where
             V: Visitor<'b>,
         {
             match self.value {
-                Some(value) => visitor.visit_seq(MySeqAccess(std::slice::from_ref(value).iter())),
+                Some(value) => match &value.value {
+                    AnnotatedData::Array(items) => visitor.visit_seq(MySeqAccess(items.iter())),
+                    _ => Err(de::Error::custom("expected array for tuple variant")),
+                },
                 None => Err(de::Error::custom("expected value for tuple variant")),
             }
         }
@@ -423,7 +474,27 @@ where
             match &self.0.value {
                 AnnotatedData::Null => visitor.visit_unit(),
                 AnnotatedData::Bool(b) => visitor.visit_bool(b.value),
-                AnnotatedData::Number(annotated) => visitor.visit_f64(annotated.value),
+                AnnotatedData::Number(annotated) => {
+                    // Integers are visited as such, as buffered content, like that of
+                    // an internally tagged enum, only fits integer fields that way.
+                    // Without the raw text, a whole number counts as an integer
+                    let number = &annotated.value;
+                    let integer = match number.raw {
+                        Some(_) => number.raw_integer(),
+                        None => number.to_integer(),
+                    };
+                    match integer {
+                        Some(value) if value >= 0 => match u64::try_from(value) {
+                            Ok(value) => visitor.visit_u64(value),
+                            Err(_) => visitor.visit_f64(number.value),
+                        },
+                        Some(value) => match i64::try_from(value) {
+                            Ok(value) => visitor.visit_i64(value),
+                            Err(_) => visitor.visit_f64(number.value),
+                        },
+                        None => visitor.visit_f64(number.value),
+                    }
+                }
                 AnnotatedData::String(annotated) => visitor.visit_str(&annotated.value),
                 AnnotatedData::Array(items) => visitor.visit_seq(MySeqAccess(items.iter())),
                 AnnotatedData::Object(items) => visitor.visit_map(MyMapAccess(items.iter(), None)),
@@ -445,19 +516,13 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => {
-                    if n.value.fract() == 0.0
-                        && n.value >= i8::MIN as f64
-                        && n.value <= i8::MAX as f64
-                    {
-                        visitor.visit_i8(n.value as i8)
-                    } else {
-                        Err(de::Error::custom(format!(
-                            "cannot convert {} to i8",
-                            n.value
-                        )))
-                    }
-                }
+                AnnotatedData::Number(n) => match n.value.to_integer::<i8>() {
+                    Some(value) => visitor.visit_i8(value),
+                    None => Err(de::Error::custom(format!(
+                        "cannot convert {} to i8",
+                        n.value
+                    ))),
+                },
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -467,19 +532,13 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => {
-                    if n.value.fract() == 0.0
-                        && n.value >= i16::MIN as f64
-                        && n.value <= i16::MAX as f64
-                    {
-                        visitor.visit_i16(n.value as i16)
-                    } else {
-                        Err(de::Error::custom(format!(
-                            "cannot convert {} to i16",
-                            n.value
-                        )))
-                    }
-                }
+                AnnotatedData::Number(n) => match n.value.to_integer::<i16>() {
+                    Some(value) => visitor.visit_i16(value),
+                    None => Err(de::Error::custom(format!(
+                        "cannot convert {} to i16",
+                        n.value
+                    ))),
+                },
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -489,19 +548,13 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => {
-                    if n.value.fract() == 0.0
-                        && n.value >= i32::MIN as f64
-                        && n.value <= i32::MAX as f64
-                    {
-                        visitor.visit_i32(n.value as i32)
-                    } else {
-                        Err(de::Error::custom(format!(
-                            "cannot convert {} to i32",
-                            n.value
-                        )))
-                    }
-                }
+                AnnotatedData::Number(n) => match n.value.to_integer::<i32>() {
+                    Some(value) => visitor.visit_i32(value),
+                    None => Err(de::Error::custom(format!(
+                        "cannot convert {} to i32",
+                        n.value
+                    ))),
+                },
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -511,19 +564,13 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => {
-                    if n.value.fract() == 0.0
-                        && n.value >= i64::MIN as f64
-                        && n.value <= i64::MAX as f64
-                    {
-                        visitor.visit_i64(n.value as i64)
-                    } else {
-                        Err(de::Error::custom(format!(
-                            "cannot convert {} to i64",
-                            n.value
-                        )))
-                    }
-                }
+                AnnotatedData::Number(n) => match n.value.to_integer::<i64>() {
+                    Some(value) => visitor.visit_i64(value),
+                    None => Err(de::Error::custom(format!(
+                        "cannot convert {} to i64",
+                        n.value
+                    ))),
+                },
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -533,16 +580,13 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => {
-                    if n.value.fract() == 0.0 && n.value >= 0.0 && n.value <= u8::MAX as f64 {
-                        visitor.visit_u8(n.value as u8)
-                    } else {
-                        Err(de::Error::custom(format!(
-                            "cannot convert {} to u8",
-                            n.value
-                        )))
-                    }
-                }
+                AnnotatedData::Number(n) => match n.value.to_integer::<u8>() {
+                    Some(value) => visitor.visit_u8(value),
+                    None => Err(de::Error::custom(format!(
+                        "cannot convert {} to u8",
+                        n.value
+                    ))),
+                },
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -552,16 +596,13 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => {
-                    if n.value.fract() == 0.0 && n.value >= 0.0 && n.value <= u16::MAX as f64 {
-                        visitor.visit_u16(n.value as u16)
-                    } else {
-                        Err(de::Error::custom(format!(
-                            "cannot convert {} to u16",
-                            n.value
-                        )))
-                    }
-                }
+                AnnotatedData::Number(n) => match n.value.to_integer::<u16>() {
+                    Some(value) => visitor.visit_u16(value),
+                    None => Err(de::Error::custom(format!(
+                        "cannot convert {} to u16",
+                        n.value
+                    ))),
+                },
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -571,16 +612,13 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => {
-                    if n.value.fract() == 0.0 && n.value >= 0.0 && n.value <= u32::MAX as f64 {
-                        visitor.visit_u32(n.value as u32)
-                    } else {
-                        Err(de::Error::custom(format!(
-                            "cannot convert {} to u32",
-                            n.value
-                        )))
-                    }
-                }
+                AnnotatedData::Number(n) => match n.value.to_integer::<u32>() {
+                    Some(value) => visitor.visit_u32(value),
+                    None => Err(de::Error::custom(format!(
+                        "cannot convert {} to u32",
+                        n.value
+                    ))),
+                },
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -590,16 +628,13 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => {
-                    if n.value.fract() == 0.0 && n.value >= 0.0 && n.value <= u64::MAX as f64 {
-                        visitor.visit_u64(n.value as u64)
-                    } else {
-                        Err(de::Error::custom(format!(
-                            "cannot convert {} to u64",
-                            n.value
-                        )))
-                    }
-                }
+                AnnotatedData::Number(n) => match n.value.to_integer::<u64>() {
+                    Some(value) => visitor.visit_u64(value),
+                    None => Err(de::Error::custom(format!(
+                        "cannot convert {} to u64",
+                        n.value
+                    ))),
+                },
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -609,7 +644,7 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => visitor.visit_f32(n.value as f32),
+                AnnotatedData::Number(n) => visitor.visit_f32(n.value.value as f32),
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -619,7 +654,7 @@ where
             V: Visitor<'b>,
         {
             match &self.0.value {
-                AnnotatedData::Number(n) => visitor.visit_f64(n.value),
+                AnnotatedData::Number(n) => visitor.visit_f64(n.value.value),
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -628,7 +663,19 @@ where
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            match &self.0.value {
+                AnnotatedData::String(s) => {
+                    let mut chars = s.value.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => visitor.visit_char(c),
+                        _ => Err(de::Error::custom(format!(
+                            "expected single character, found {:?}",
+                            s.value
+                        ))),
+                    }
+                }
+                _ => self.deserialize_any(visitor),
+            }
         }
 
         fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -761,23 +808,30 @@ where
 
         fn deserialize_enum<V>(
             self,
-            _name: &'static str,
+            name: &'static str,
             _variants: &'static [&'static str],
             visitor: V,
         ) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'b>,
         {
+            // Internally tagged enums never get here, since serde reads them
+            // through `deserialize_any` and finds the tag field by itself
             match &self.0.value {
-                AnnotatedData::Object(items) => {
-                    // For internally tagged enums, we need to find the tag field
-                    // For simplicity, we'll just visit the map directly
-                    visitor.visit_map(MyMapAccess(items.iter(), None))
-                }
-                AnnotatedData::String(s) => {
-                    // For externally tagged unit variants
-                    visitor.visit_enum(de::value::StrDeserializer::new(&s.value))
-                }
+                AnnotatedData::Object(items) => match &items[..] {
+                    [(tag, value)] => visitor.visit_enum(MyEnumAccess {
+                        tag: &tag.value,
+                        value: Some(value),
+                    }),
+                    _ => Err(de::Error::custom(format!(
+                        "expected an object with a single key for enum {name}, found {} keys",
+                        items.len()
+                    ))),
+                },
+                AnnotatedData::String(s) => visitor.visit_enum(MyEnumAccess {
+                    tag: &s.value,
+                    value: None,
+                }),
                 _ => self.deserialize_any(visitor),
             }
         }
@@ -793,17 +847,57 @@ where
         where
             V: Visitor<'b>,
         {
-            self.deserialize_any(visitor)
+            // The value is already parsed, so skipping it needs no walk
+            visitor.visit_unit()
         }
     }
 
-    R::deserialize(MyDeserializer(data)).unwrap()
+    R::deserialize(MyDeserializer(data)).map_err(|e| e.0)
+}
+
+/// Converts parsed data into a `serde_json::Value`, dropping annotations.
+///
+/// Object keys are inserted in document order, so they keep it when
+/// `serde_json` is built with its `preserve_order` feature. Numbers that are
+/// written as integers stay integers, and NaN and infinities, which JSON can't
+/// represent, become `null`.
+pub fn to_json_value<A>(data: &AnnotatedData<A>) -> serde_json::Value {
+    match data {
+        AnnotatedData::Null => serde_json::Value::Null,
+        AnnotatedData::Bool(b) => serde_json::Value::Bool(b.value),
+        AnnotatedData::Number(n) => json_number(&n.value)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        AnnotatedData::String(s) => serde_json::Value::String(s.value.clone()),
+        AnnotatedData::Array(items) => serde_json::Value::Array(
+            items
+                .iter()
+                .map(|item| to_json_value(&item.value))
+                .collect(),
+        ),
+        AnnotatedData::Object(items) => serde_json::Value::Object(
+            items
+                .iter()
+                .map(|(key, value)| (key.value.clone(), to_json_value(&value.value)))
+                .collect(),
+        ),
+    }
+}
+
+fn json_number(n: &Number) -> Option<serde_json::Number> {
+    if let Some(i) = n.to_integer::<i64>() {
+        return Some(i.into());
+    }
+    if let Some(u) = n.to_integer::<u64>() {
+        return Some(u.into());
+    }
+    serde_json::Number::from_f64(n.value)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use deval_data_model::{Annotated, AnnotatedData, Span, SpanSet};
+    use deval_data_model::{Annotated, AnnotatedData, Number, Span, SpanSet};
     use serde::Deserialize;
 
     fn annotated_string(value: &str) -> Annotated<String, ()> {
@@ -813,9 +907,9 @@ mod tests {
         }
     }
 
-    fn annotated_number(value: f64) -> Annotated<f64, ()> {
+    fn annotated_number(value: f64) -> Annotated<Number, ()> {
         Annotated {
-            value,
+            value: value.into(),
             annotation: (),
         }
     }
@@ -976,6 +1070,141 @@ mod tests {
         );
     }
 
+    /// An object with string values, keyed by `keys`.
+    fn object_with_keys(keys: &[&str]) -> Annotated<AnnotatedData<()>, ()> {
+        Annotated {
+            value: AnnotatedData::Object(
+                keys.iter()
+                    .map(|key| {
+                        (
+                            annotated_string(key),
+                            Annotated {
+                                value: AnnotatedData::String(annotated_string(&key.repeat(2))),
+                                annotation: (),
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+            annotation: (),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_map_with_parsed_keys() {
+        use std::collections::HashMap;
+
+        let result: HashMap<u32, String> =
+            deserialize_from_annotated(&object_with_keys(&["5", "10"]));
+        assert_eq!(
+            result,
+            HashMap::from([(5, "55".to_owned()), (10, "1010".to_owned())])
+        );
+
+        let result: HashMap<i64, String> = deserialize_from_annotated(&object_with_keys(&["-3"]));
+        assert_eq!(result, HashMap::from([(-3, "-3-3".to_owned())]));
+
+        let result: HashMap<bool, String> =
+            deserialize_from_annotated(&object_with_keys(&["true"]));
+        assert_eq!(result, HashMap::from([(true, "truetrue".to_owned())]));
+
+        let result: HashMap<String, String> = deserialize_from_annotated(&object_with_keys(&["5"]));
+        assert_eq!(result, HashMap::from([("5".to_owned(), "55".to_owned())]));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"invalid type: string \"x\", expected u32"#)]
+    fn test_deserialize_map_with_unparsable_key_should_fail() {
+        let _result: std::collections::HashMap<u32, String> =
+            deserialize_from_annotated(&object_with_keys(&["x"]));
+    }
+
+    #[test]
+    fn test_deserialize_ignores_unread_subtrees() {
+        use deval_data_model::Format;
+        use serde::de::{Error, IgnoredAny};
+
+        /// Accepts only a skipped value, failing if its contents are visited
+        #[derive(Debug)]
+        struct Untouched;
+
+        impl<'de> Deserialize<'de> for Untouched {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct UntouchedVisitor;
+
+                impl<'de> Visitor<'de> for UntouchedVisitor {
+                    type Value = Untouched;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str("a skipped value")
+                    }
+
+                    fn visit_unit<E: Error>(self) -> Result<Untouched, E> {
+                        Ok(Untouched)
+                    }
+                }
+
+                deserializer.deserialize_ignored_any(UntouchedVisitor)
+            }
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct Config {
+            name: String,
+            #[allow(unused)]
+            deep: Untouched,
+            #[serde(skip)]
+            cache: Vec<u8>,
+        }
+
+        let deep = (0..20).fold("[1, 2]".to_owned(), |inner, i| {
+            let siblings: Vec<String> = (0..50).map(|j| format!("b{j}: [true, \"x\"]")).collect();
+            format!("(a{i}: {inner}, {})", siblings.join(", "))
+        });
+        let source = format!("(extra: {deep}, deep: {deep}, name: \"app\", more: [{deep}])");
+        let data = deval_format_ron::Ron
+            .parse(&source, "test.ron")
+            .unwrap()
+            .discard_annotation();
+        let config: Config = deserialize_from_annotated(&data);
+        assert_eq!(config.name, "app");
+        assert!(config.cache.is_empty());
+
+        let _: IgnoredAny = deserialize_from_annotated(&data);
+    }
+
+    #[test]
+    fn test_deserialize_schema_defaults() {
+        use deval_data_model::Format;
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Config {
+            host: String,
+            port: u16,
+            name: String,
+        }
+
+        let schema = deval_schema::compile(
+            r#"{ host: string, port?: integer = 8080, name?: string = "x" }"#,
+        )
+        .unwrap();
+        let parse = |source: &str| -> Config {
+            let data = deval_format_ron::Ron.parse(source, "test.ron").unwrap();
+            let validated = schema.validate(data);
+            assert!(validated.errors.is_empty());
+            deserialize_from_annotated(&validated.result.discard_annotation())
+        };
+        // Omitted keys get their defaults, and written keys override them
+        assert_eq!(
+            parse(r#"(host: "localhost", name: "y")"#),
+            Config {
+                host: "localhost".to_owned(),
+                port: 8080,
+                name: "y".to_owned(),
+            }
+        );
+    }
+
     #[test]
     fn test_deserialize_nested_object() {
         let data = Annotated {
@@ -1101,4 +1330,279 @@ mod tests {
 
         let _result: Point = deserialize_from_annotated(&data);
     }
+
+    #[test]
+    fn test_deserialize_char() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Csv {
+            sep: char,
+        }
+
+        let data = Annotated {
+            value: AnnotatedData::Object(vec![(
+                annotated_string("sep"),
+                Annotated {
+                    value: AnnotatedData::String(annotated_string(",")),
+                    annotation: (),
+                },
+            )]),
+            annotation: (),
+        };
+
+        let result: Csv = deserialize_from_annotated(&data);
+        assert_eq!(result, Csv { sep: ',' });
+    }
+
+    #[test]
+    #[should_panic(expected = r#"expected single character, found \"ab\""#)]
+    fn test_deserialize_char_from_long_string_should_fail() {
+        let data = Annotated {
+            value: AnnotatedData::String(annotated_string("ab")),
+            annotation: (),
+        };
+
+        let _result: char = deserialize_from_annotated(&data);
+    }
+
+    #[test]
+    fn test_deserialize_internally_tagged_enum() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(tag = "kind")]
+        enum Shape {
+            Circle { radius: f64 },
+            Rectangle { width: f64, height: f64 },
+            Polygon { sides: u32, id: u64 },
+        }
+
+        let object = |pairs: Vec<(&str, AnnotatedData<()>)>| Annotated {
+            value: AnnotatedData::Object(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| {
+                        (
+                            annotated_string(key),
+                            Annotated {
+                                value,
+                                annotation: (),
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+            annotation: (),
+        };
+
+        let circle = object(vec![
+            ("kind", AnnotatedData::String(annotated_string("Circle"))),
+            ("radius", AnnotatedData::Number(annotated_number(1.5))),
+        ]);
+        let result: Shape = deserialize_from_annotated(&circle);
+        assert_eq!(result, Shape::Circle { radius: 1.5 });
+
+        // The tag does not have to be the first key
+        let rectangle = object(vec![
+            ("width", AnnotatedData::Number(annotated_number(2.))),
+            ("kind", AnnotatedData::String(annotated_string("Rectangle"))),
+            ("height", AnnotatedData::Number(annotated_number(3.))),
+        ]);
+        let result: Shape = deserialize_from_annotated(&rectangle);
+        assert_eq!(
+            result,
+            Shape::Rectangle {
+                width: 2.,
+                height: 3.
+            }
+        );
+
+        // Integer fields, exact beyond 2^53
+        let polygon = object(vec![
+            ("kind", AnnotatedData::String(annotated_string("Polygon"))),
+            ("sides", AnnotatedData::Number(annotated_number(5.))),
+            (
+                "id",
+                AnnotatedData::Number(Annotated {
+                    value: Number::with_raw(9007199254740993., "9007199254740993"),
+                    annotation: (),
+                }),
+            ),
+        ]);
+        let result: Shape = deserialize_from_annotated(&polygon);
+        assert_eq!(
+            result,
+            Shape::Polygon {
+                sides: 5,
+                id: 9007199254740993
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_enum_with_data() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        enum Shape {
+            Point,
+            Circle { radius: f64 },
+            Line(f64, f64),
+        }
+
+        let variant = |name: &str, value: AnnotatedData<()>| Annotated {
+            value: AnnotatedData::Object(vec![(
+                annotated_string(name),
+                Annotated {
+                    value,
+                    annotation: (),
+                },
+            )]),
+            annotation: (),
+        };
+        let number = |value| Annotated {
+            value: AnnotatedData::Number(annotated_number(value)),
+            annotation: (),
+        };
+
+        let circle = variant(
+            "Circle",
+            AnnotatedData::Object(vec![(annotated_string("radius"), number(1.))]),
+        );
+        let result: Shape = deserialize_from_annotated(&circle);
+        assert_eq!(result, Shape::Circle { radius: 1. });
+
+        let line = variant("Line", AnnotatedData::Array(vec![number(2.), number(3.)]));
+        let result: Shape = deserialize_from_annotated(&line);
+        assert_eq!(result, Shape::Line(2., 3.));
+
+        let point = Annotated {
+            value: AnnotatedData::String(annotated_string("Point")),
+            annotation: (),
+        };
+        let result: Shape = deserialize_from_annotated(&point);
+        assert_eq!(result, Shape::Point);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected an object with a single key for enum Shape")]
+    fn test_deserialize_enum_from_empty_object_should_fail() {
+        #[derive(Deserialize, Debug)]
+        #[allow(unused)]
+        enum Shape {
+            Circle { radius: f64 },
+        }
+
+        let data = Annotated {
+            value: AnnotatedData::Object(vec![]),
+            annotation: (),
+        };
+
+        let _result: Shape = deserialize_from_annotated(&data);
+    }
+
+    #[test]
+    fn test_deserialize_large_integers() {
+        let number = |raw: &str| Annotated {
+            value: AnnotatedData::Number(Annotated {
+                value: Number::with_raw(raw.parse().unwrap(), raw),
+                annotation: (),
+            }),
+            annotation: (),
+        };
+        // Above 2^53, the f64 value is rounded to 9007199254740992
+        let result: u64 = deserialize_from_annotated(&number("9007199254740993"));
+        assert_eq!(result, 9007199254740993);
+        let result: i64 = deserialize_from_annotated(&number("-9007199254740993"));
+        assert_eq!(result, -9007199254740993);
+        let result: u64 = deserialize_from_annotated(&number("18446744073709551615"));
+        assert_eq!(result, u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot convert 18446744073709551616 to u64")]
+    fn test_deserialize_u64_overflow_should_fail() {
+        let data = Annotated {
+            value: AnnotatedData::Number(Annotated {
+                value: Number::with_raw(2f64.powi(64), "18446744073709551616"),
+                annotation: (),
+            }),
+            annotation: (),
+        };
+        let _result: u64 = deserialize_from_annotated(&data);
+    }
+
+    #[test]
+    fn test_to_json_value() {
+        let key = annotated_string;
+        let data = AnnotatedData::Object(vec![
+            (
+                key("name"),
+                Annotated {
+                    value: AnnotatedData::String(annotated_string("app")),
+                    annotation: (),
+                },
+            ),
+            (
+                key("server"),
+                Annotated {
+                    value: AnnotatedData::Object(vec![
+                        (
+                            key("port"),
+                            Annotated {
+                                value: AnnotatedData::Number(annotated_number(8080.)),
+                                annotation: (),
+                            },
+                        ),
+                        (
+                            key("ratio"),
+                            Annotated {
+                                value: AnnotatedData::Number(annotated_number(0.5)),
+                                annotation: (),
+                            },
+                        ),
+                        (
+                            key("id"),
+                            Annotated {
+                                value: AnnotatedData::Number(Annotated {
+                                    value: Number::with_raw(
+                                        u64::MAX as f64,
+                                        "18446744073709551615",
+                                    ),
+                                    annotation: (),
+                                }),
+                                annotation: (),
+                            },
+                        ),
+                    ]),
+                    annotation: (),
+                },
+            ),
+            (
+                key("tags"),
+                Annotated {
+                    value: AnnotatedData::Array(vec![
+                        Annotated {
+                            value: AnnotatedData::Bool(annotated_bool(true)),
+                            annotation: (),
+                        },
+                        Annotated {
+                            value: annotated_null(),
+                            annotation: (),
+                        },
+                    ]),
+                    annotation: (),
+                },
+            ),
+        ]);
+
+        let value = to_json_value(&data);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "name": "app",
+                "server": { "port": 8080, "ratio": 0.5, "id": 18446744073709551615u64 },
+                "tags": [true, null],
+            })
+        );
+        assert_eq!(
+            to_json_value(&AnnotatedData::<()>::Number(annotated_number(f64::NAN))),
+            serde_json::Value::Null
+        );
+    }
 }
@@ -0,0 +1,39 @@
+//! Helpers shared by the formats parsed with tree-sitter.
+
+use deval_data_model::{ParseError, Span, SpanSet};
+use tree_sitter::Node;
+
+/// Reports each error and missing node of the tree at its own span, instead of
+/// the whole document.
+pub fn syntax_errors(node: &Node, filename: &str, errors: &mut Vec<ParseError>) {
+    if node.is_error() {
+        errors.push(ParseError {
+            message: "Unexpected token".to_string(),
+            span: make_span(node, filename),
+        });
+    } else if node.is_missing() {
+        errors.push(ParseError {
+            message: format!("Missing `{}`", node.kind()),
+            span: make_span(node, filename),
+        });
+    } else if node.has_error() {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            syntax_errors(&child, filename, errors);
+        }
+    }
+}
+
+/// Creates a `Span` from a `tree_sitter::Node`.
+pub fn make_span(node: &Node, filename: &str) -> Span {
+    Span {
+        filename: filename.to_string(),
+        start: node.start_byte(),
+        end: node.end_byte(),
+    }
+}
+
+/// Creates a `Vec<Span>` from a `tree_sitter::Node`.
+pub fn make_span_vec(node: &Node, filename: &str) -> SpanSet {
+    SpanSet(vec![make_span(node, filename)])
+}
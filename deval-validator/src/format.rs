@@ -0,0 +1,227 @@
+//! Named string formats, like `format("email")` in a schema.
+
+use std::net::Ipv4Addr;
+
+use deval_data_model::{Spanned, SpannedData};
+
+use crate::{SchemaIr, ValidationError, ValidationResult, Validator, kind_mismatch};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum StringFormat {
+    Email,
+    Uri,
+    Uuid,
+    Ipv4,
+    /// A full date like `2024-02-29`
+    Date,
+    /// An RFC 3339 timestamp like `2024-02-29T12:00:00Z`
+    DateTime,
+}
+
+impl StringFormat {
+    pub const ALL: [StringFormat; 6] = [
+        StringFormat::Email,
+        StringFormat::Uri,
+        StringFormat::Uuid,
+        StringFormat::Ipv4,
+        StringFormat::Date,
+        StringFormat::DateTime,
+    ];
+
+    /// The format with the given name, as used by JSON Schema.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StringFormat::Email => "email",
+            StringFormat::Uri => "uri",
+            StringFormat::Uuid => "uuid",
+            StringFormat::Ipv4 => "ipv4",
+            StringFormat::Date => "date",
+            StringFormat::DateTime => "date-time",
+        }
+    }
+
+    pub fn matches(self, text: &str) -> bool {
+        match self {
+            StringFormat::Email => is_email(text),
+            StringFormat::Uri => is_uri(text),
+            StringFormat::Uuid => is_uuid(text),
+            StringFormat::Ipv4 => text.parse::<Ipv4Addr>().is_ok(),
+            StringFormat::Date => is_date(text),
+            StringFormat::DateTime => is_date_time(text),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FormatValidator(pub StringFormat);
+
+impl Validator for FormatValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let SpannedData::String(s) = &data.value else {
+            return kind_mismatch("String", data);
+        };
+        if !self.0.matches(&s.value) {
+            return ValidationResult {
+                errors: vec![ValidationError::spanning(
+                    &data.annotation,
+                    format!("{:?} is not a valid {}", s.value, self.0.name()),
+                )],
+                result: data.into(),
+            };
+        }
+        ValidationResult::ok(data.into())
+    }
+
+    fn describe(&self) -> String {
+        format!("format({:?})", self.0.name())
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Format(self.0)
+    }
+}
+
+fn is_email(text: &str) -> bool {
+    let Some((local, domain)) = text.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !text.contains(char::is_whitespace)
+        && !domain.contains('@')
+        && domain.split('.').count() >= 2
+        && domain.split('.').all(|label| !label.is_empty())
+}
+
+fn is_uri(text: &str) -> bool {
+    let Some((scheme, rest)) = text.split_once(':') else {
+        return false;
+    };
+    let mut scheme = scheme.chars();
+    scheme.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        && !rest.is_empty()
+        && !text.contains(char::is_whitespace)
+}
+
+fn is_uuid(text: &str) -> bool {
+    let groups: Vec<&str> = text.split('-').collect();
+    groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+        && groups
+            .iter()
+            .all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Parses a fixed number of ASCII digits.
+fn digits(text: &str, count: usize) -> Option<u32> {
+    if text.len() != count || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn is_date(text: &str) -> bool {
+    let mut parts = text.split('-');
+    let (Some(year), Some(month), Some(day), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    let (Some(year), Some(month), Some(day)) = (digits(year, 4), digits(month, 2), digits(day, 2))
+    else {
+        return false;
+    };
+    let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days_in_month).contains(&day)
+}
+
+fn is_time(text: &str) -> bool {
+    // Seconds may have a fraction, and the offset is `Z` or `+HH:MM`/`-HH:MM`
+    let (time, offset) = match text.find(['Z', 'z', '+', '-']) {
+        Some(i) => text.split_at(i),
+        None => return false,
+    };
+    let valid_offset = match offset {
+        "Z" | "z" => true,
+        _ => offset[1..].split_once(':').is_some_and(|(h, m)| {
+            digits(h, 2).is_some_and(|h| h < 24) && digits(m, 2).is_some_and(|m| m < 60)
+        }),
+    };
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let mut parts = time.split(':');
+    let (Some(hour), Some(minute), Some(second), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    valid_offset
+        && !fraction.is_empty()
+        && fraction.bytes().all(|b| b.is_ascii_digit())
+        && digits(hour, 2).is_some_and(|h| h < 24)
+        && digits(minute, 2).is_some_and(|m| m < 60)
+        // 60 allows for leap seconds
+        && digits(second, 2).is_some_and(|s| s <= 60)
+}
+
+fn is_date_time(text: &str) -> bool {
+    match text.find(['T', 't', ' ']) {
+        Some(i) => is_date(&text[..i]) && is_time(&text[i + 1..]),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        let cases = [
+            (StringFormat::Email, "user@example.com", "user@localhost"),
+            (StringFormat::Uri, "https://example.com/a?b", "example.com"),
+            (
+                StringFormat::Uuid,
+                "123e4567-e89b-12d3-a456-426614174000",
+                "123e4567-e89b-12d3-a456-42661417400g",
+            ),
+            (StringFormat::Ipv4, "192.168.0.1", "256.0.0.1"),
+            (StringFormat::Date, "2024-02-29", "2023-02-29"),
+            (
+                StringFormat::DateTime,
+                "2024-02-29T12:30:00.5+03:30",
+                "2024-02-29T24:00:00Z",
+            ),
+        ];
+        for (format, valid, invalid) in cases {
+            assert!(
+                format.matches(valid),
+                "{valid} should be a valid {format:?}"
+            );
+            assert!(
+                !format.matches(invalid),
+                "{invalid} should not be a valid {format:?}"
+            );
+        }
+        assert!(StringFormat::DateTime.matches("2024-01-01 00:00:00z"));
+        assert!(!StringFormat::DateTime.matches("2024-01-01T00:00:00"));
+    }
+
+    #[test]
+    fn test_format_names() {
+        for format in StringFormat::ALL {
+            assert_eq!(StringFormat::from_name(format.name()), Some(format));
+        }
+        assert_eq!(StringFormat::from_name("hostname"), None);
+    }
+}
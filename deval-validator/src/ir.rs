@@ -0,0 +1,65 @@
+//! A plain description of a validator's structure, for comparing schemas.
+
+use std::collections::BTreeMap;
+
+use crate::StringFormat;
+
+/// The structure of a validator, as returned by `Validator::to_ir`. Two
+/// validators with equal IRs accept the same values. Object fields are kept by
+/// name, so their order doesn't matter, and docs are left out.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SchemaIr {
+    Any,
+    Number,
+    Integer,
+    String,
+    Bool,
+    Null,
+    BoolLiteral(bool),
+    StringLiteral(String),
+    NumberLiteral(f64),
+    Regex(String),
+    Format(StringFormat),
+    Range {
+        start: Option<f64>,
+        end: Option<f64>,
+        excludes_start: bool,
+        is_inclusive: bool,
+    },
+    Array {
+        element: Box<SchemaIr>,
+        min: Option<usize>,
+        max: Option<usize>,
+        unique: bool,
+    },
+    Tuple(Vec<SchemaIr>),
+    Object {
+        fields: BTreeMap<String, FieldIr>,
+        /// The key and value of each map record, in the order they are tried
+        maps: Vec<(SchemaIr, SchemaIr)>,
+        allow_unknown: bool,
+        min_keys: Option<usize>,
+        max_keys: Option<usize>,
+    },
+    Or(Vec<SchemaIr>),
+    Xor(Vec<SchemaIr>),
+    Not(Box<SchemaIr>),
+    /// A recursive type used within itself, which isn't expanded so the IR
+    /// stays finite. Other named types are replaced by their structure.
+    Ref(String),
+    /// A validator without a known structure, by its description
+    Custom(String),
+}
+
+/// A field of an object in a `SchemaIr`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FieldIr {
+    pub value: SchemaIr,
+    pub aliases: Vec<String>,
+    pub ignore_case: bool,
+    pub optional: bool,
+    /// The default value, as written in the schema
+    pub default: Option<String>,
+}
@@ -1,13 +1,107 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, OnceLock, Weak};
 
 use deval_data_model::{
-    Annotated, AnnotatedData, FullAnnotation, SemanticType, Span, Spanned, SpannedData,
+    Annotated, AnnotatedData, FullAnnotation, SemanticType, Span, SpanSet, Spanned, SpannedData,
 };
 use dyn_clone::DynClone;
 
+mod format;
+mod ir;
+
+pub use format::{FormatValidator, StringFormat};
+pub use ir::{FieldIr, SchemaIr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    /// An issue worth reporting which doesn't make the data invalid
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationErrorKind {
+    /// The data doesn't match the schema
+    Mismatch,
+    /// The data has a key the schema doesn't know about. Lenient checks can
+    /// report these as warnings.
+    UnknownKey,
+    /// The object lacks a mandatory key. `placeholder` is a value the key's
+    /// validator accepts, for filling it in, if it has a simple one.
+    MissingKey {
+        key: String,
+        placeholder: Option<AnnotatedData<()>>,
+    },
+}
+
+impl ValidationErrorKind {
+    /// A stable name of the kind, for tools matching on errors.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationErrorKind::Mismatch => "mismatch",
+            ValidationErrorKind::UnknownKey => "unknown-key",
+            ValidationErrorKind::MissingKey { .. } => "missing-key",
+        }
+    }
+
+    /// Whether lenient checks report errors of this kind as warnings.
+    pub fn is_lenient(&self) -> bool {
+        matches!(self, ValidationErrorKind::UnknownKey)
+    }
+}
+
 pub struct ValidationError {
     pub span: Span,
     pub text: String,
+    pub severity: Severity,
+    pub kind: ValidationErrorKind,
+    /// A suggestion for fixing the error, shown as a note
+    pub help: Option<String>,
+    /// The keys and array indices leading from the validated root to the value
+    /// or key the error is about
+    pub path: Vec<String>,
+    /// Other places the value or key appears, like a TOML table extended by a
+    /// later header
+    pub secondary: Vec<Span>,
+}
+
+impl ValidationError {
+    pub fn new(span: Span, text: String) -> Self {
+        Self {
+            span,
+            text,
+            severity: Severity::Error,
+            kind: ValidationErrorKind::Mismatch,
+            help: None,
+            path: vec![],
+            secondary: vec![],
+        }
+    }
+
+    /// An error at the first of `spans`, also pointing at the others. Data without
+    /// spans gets an error at the default span.
+    pub fn spanning(spans: &SpanSet, text: String) -> Self {
+        let mut error = Self::new(spans.primary().unwrap_or_default(), text);
+        error.secondary = spans
+            .merged()
+            .into_iter()
+            .filter(|span| {
+                span.filename != error.span.filename
+                    || span.end <= error.span.start
+                    || error.span.end <= span.start
+            })
+            .collect();
+        error
+    }
+
+    /// The path of the error as a JSON Pointer, like `/servers/0/port`, or an
+    /// empty string for the root.
+    pub fn pointer(&self) -> String {
+        self.path
+            .iter()
+            .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+            .collect()
+    }
 }
 
 pub struct ValidationResult {
@@ -23,6 +117,15 @@ impl ValidationResult {
         }
     }
 
+    /// Prefixes the paths of the errors with `segment`, for the result of
+    /// validating a nested value.
+    fn at(mut self, segment: impl ToString) -> Self {
+        for error in &mut self.errors {
+            error.path.insert(0, segment.to_string());
+        }
+        self
+    }
+
     fn append_errors_and_return_result(
         self,
         errors: &mut Vec<ValidationError>,
@@ -34,10 +137,77 @@ impl ValidationResult {
 
 pub trait Validator: std::fmt::Debug + DynClone + Send + Sync {
     fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult;
+
+    /// A human readable description of the values this validator accepts, shown to
+    /// users e.g. on hover.
+    fn describe(&self) -> String;
+
+    /// Like `describe`, but spread over multiple lines with object docs included,
+    /// for inspecting a whole schema.
+    fn explain(&self) -> String {
+        self.describe()
+    }
+
+    /// The only string this validator accepts, if it is a string literal.
+    fn string_literal(&self) -> Option<&str> {
+        None
+    }
+
+    /// The records of this validator, if it validates objects.
+    fn object_records(&self) -> Option<&[RecordValidator]> {
+        None
+    }
+
+    /// A simple value this validator accepts, like `""` or `0`, for filling in a
+    /// missing one.
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        None
+    }
+
+    /// The operator joining the alternatives in the description, `|` or `^`, if
+    /// this validator is a union.
+    fn union_operator(&self) -> Option<char> {
+        None
+    }
+
+    /// The structure of this validator, for comparing it with others.
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Custom(self.describe())
+    }
 }
 
 dyn_clone::clone_trait_object!(Validator);
 
+/// The candidate closest to `name` by edit distance, if it is close enough to
+/// be a likely typo.
+pub fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = name.chars().count().div_ceil(3);
+    candidates
+        .into_iter()
+        .map(|candidate| (levenshtein(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct AnyValidator;
 
@@ -45,164 +215,990 @@ impl Validator for AnyValidator {
     fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
         ValidationResult::ok(data.into())
     }
-}
-
-#[derive(Clone)]
-pub struct LambdaValidator<T: Clone + Fn(Spanned<SpannedData>) -> Option<String>>(pub T);
 
-impl<T: Clone + Fn(Spanned<SpannedData>) -> Option<String>> std::fmt::Debug for LambdaValidator<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("LambdaValidator").finish()
+    fn describe(&self) -> String {
+        "any".to_owned()
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        Some(AnnotatedData::Null)
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Any
+    }
+}
+
+/// A validator defined by a function returning the error message, if any. The
+/// first field is its description.
+#[derive(Clone)]
+pub struct LambdaValidator<T: Clone + Fn(Spanned<SpannedData>) -> Option<String>>(
+    pub String,
+    pub T,
+);
+
+impl<T: Clone + Fn(Spanned<SpannedData>) -> Option<String>> std::fmt::Debug for LambdaValidator<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("LambdaValidator").field(&self.0).finish()
+    }
+}
+
+impl<T: Clone + Send + Sync + Fn(Spanned<SpannedData>) -> Option<String>> Validator
+    for LambdaValidator<T>
+{
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let span = data.annotation.primary().unwrap_or_default();
+        if let Some(text) = self.1(data.clone()) {
+            return ValidationResult {
+                errors: vec![ValidationError::new(span, text)],
+                result: data.into(),
+            };
+        } else {
+            ValidationResult::ok(data.into())
+        }
+    }
+
+    fn describe(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// Refers to a validator by name, which may be filled in after the reference is
+/// created. This allows named types to reference themselves or each other.
+#[derive(Clone)]
+pub struct RefValidator {
+    pub name: String,
+    target: RefTarget,
+}
+
+#[derive(Clone)]
+enum RefTarget {
+    Strong(Arc<OnceLock<Box<dyn Validator>>>),
+    /// A reference from inside a recursive type to itself, which must not keep
+    /// the type alive, or it would never be dropped.
+    Weak(Weak<OnceLock<Box<dyn Validator>>>),
+}
+
+impl RefValidator {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            target: RefTarget::Strong(Arc::new(OnceLock::new())),
+        }
+    }
+
+    /// A reference to the same type which doesn't keep it alive, for use inside
+    /// the types it refers to. Something else, like a `RootValidator`, has to
+    /// hold a strong reference for as long as it is used.
+    pub fn downgrade(&self) -> Self {
+        let target = match &self.target {
+            RefTarget::Strong(target) => Arc::downgrade(target),
+            RefTarget::Weak(target) => target.clone(),
+        };
+        Self {
+            name: self.name.clone(),
+            target: RefTarget::Weak(target),
+        }
+    }
+
+    /// Fills in the validator this refers to, unless it was already set.
+    pub fn set_target(&self, validator: Box<dyn Validator>) {
+        let target = match &self.target {
+            RefTarget::Strong(target) => Some(target.clone()),
+            RefTarget::Weak(target) => target.upgrade(),
+        };
+        if let Some(target) = target {
+            let _ = target.set(validator);
+        }
+    }
+
+    /// The target of a strong reference. Weak ones can't lend theirs out.
+    fn strong_target(&self) -> Option<&dyn Validator> {
+        match &self.target {
+            RefTarget::Strong(target) => target.get().map(|v| &**v),
+            RefTarget::Weak(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for RefValidator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The target may contain this reference again, so don't print it
+        f.debug_tuple("RefValidator").field(&self.name).finish()
+    }
+}
+
+impl Validator for RefValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let target = match &self.target {
+            RefTarget::Strong(target) => Some(target.clone()),
+            RefTarget::Weak(target) => target.upgrade(),
+        };
+        match target.as_deref().and_then(OnceLock::get) {
+            Some(validator) => validator.validate(data),
+            None => ValidationResult {
+                errors: vec![ValidationError::spanning(
+                    &data.annotation,
+                    format!("Type {} is not defined", self.name),
+                )],
+                result: data.into(),
+            },
+        }
+    }
+
+    fn describe(&self) -> String {
+        self.name.clone()
+    }
+
+    fn string_literal(&self) -> Option<&str> {
+        self.strong_target()?.string_literal()
+    }
+
+    fn object_records(&self) -> Option<&[RecordValidator]> {
+        self.strong_target()?.object_records()
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        match &self.target {
+            RefTarget::Strong(target) => target.get()?.placeholder(),
+            RefTarget::Weak(target) => target.upgrade()?.get()?.placeholder(),
+        }
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        // Expand the target like a type used after its definition, so the order
+        // of definitions doesn't matter
+        match self.strong_target() {
+            Some(target) => target.to_ir(),
+            None => SchemaIr::Ref(self.name.clone()),
+        }
+    }
+}
+
+/// The root of a schema along with its recursive types, which the references
+/// inside them don't keep alive.
+#[derive(Debug, Clone)]
+pub struct RootValidator {
+    pub root: Box<dyn Validator>,
+    pub definitions: Vec<RefValidator>,
+    /// The `///` comments at the start of the schema, shown by `explain`
+    pub docs: String,
+}
+
+impl Validator for RootValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        self.root.validate(data)
+    }
+
+    fn describe(&self) -> String {
+        self.root.describe()
+    }
+
+    fn explain(&self) -> String {
+        let mut result = String::new();
+        for line in self.docs.lines() {
+            result += &format!("/// {}\n", line.trim());
+        }
+        result + &self.root.explain()
+    }
+
+    fn string_literal(&self) -> Option<&str> {
+        self.root.string_literal()
+    }
+
+    fn object_records(&self) -> Option<&[RecordValidator]> {
+        self.root.object_records()
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        self.root.placeholder()
+    }
+
+    fn union_operator(&self) -> Option<char> {
+        self.root.union_operator()
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        self.root.to_ir()
+    }
+}
+
+/// Wraps `text`, the description of `validator`, in parentheses if it is a
+/// union, for showing it inside a type binding tighter.
+fn grouped(validator: &dyn Validator, text: String) -> String {
+    if validator.union_operator().is_some() {
+        format!("({text})")
+    } else {
+        text
+    }
+}
+
+/// `value` without an annotation, as in a placeholder.
+fn plain<T>(value: T) -> Annotated<T, ()> {
+    Annotated {
+        value,
+        annotation: (),
+    }
+}
+
+/// The result for `data` not being of the `expected` kind.
+fn kind_mismatch(expected: &str, data: Spanned<SpannedData>) -> ValidationResult {
+    ValidationResult {
+        errors: vec![ValidationError::spanning(
+            &data.annotation,
+            format!("Expected {expected}, found {}", data.value.kind()),
+        )],
+        result: data.into(),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NumberValidator;
+
+impl Validator for NumberValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let SpannedData::Number(_n) = &data.value else {
+            return kind_mismatch("Number", data);
+        };
+        ValidationResult::ok(data.into())
+    }
+
+    fn describe(&self) -> String {
+        "number".to_owned()
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Number
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        Some(AnnotatedData::Number(plain(0.0.into())))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IntegerValidator;
+
+impl Validator for IntegerValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        if !matches!(&data.value, SpannedData::Number(n) if n.value.value.fract() == 0.) {
+            return kind_mismatch("Integer", data);
+        }
+        ValidationResult::ok(data.into())
+    }
+
+    fn describe(&self) -> String {
+        "integer".to_owned()
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Integer
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        Some(AnnotatedData::Number(plain(0.0.into())))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StringValidator;
+
+impl Validator for StringValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        if !matches!(data.value, SpannedData::String(_)) {
+            return kind_mismatch("String", data);
+        }
+        ValidationResult::ok(data.into())
+    }
+
+    fn describe(&self) -> String {
+        "string".to_owned()
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::String
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        Some(AnnotatedData::String(plain(String::new())))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoolValidator;
+
+impl Validator for BoolValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        if !matches!(data.value, SpannedData::Bool(_)) {
+            return kind_mismatch("Bool", data);
+        }
+        ValidationResult::ok(data.into())
+    }
+
+    fn describe(&self) -> String {
+        "bool".to_owned()
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Bool
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        Some(AnnotatedData::Bool(plain(false)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NullValidator;
+
+impl Validator for NullValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        if !matches!(data.value, SpannedData::Null) {
+            return kind_mismatch("Null", data);
+        }
+        ValidationResult::ok(data.into())
+    }
+
+    fn describe(&self) -> String {
+        "null".to_owned()
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Null
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        Some(AnnotatedData::Null)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoolLiteralValidator(pub bool);
+
+impl Validator for BoolLiteralValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let found = match &data.value {
+            SpannedData::Bool(b) if b.value == self.0 => {
+                return ValidationResult::ok(data.into());
+            }
+            SpannedData::Bool(b) => b.value.to_string(),
+            _ => data.value.kind().to_owned(),
+        };
+        ValidationResult {
+            errors: vec![ValidationError::spanning(
+                &data.annotation,
+                format!("Expected {}, found {found}", self.0),
+            )],
+            result: data.into(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::BoolLiteral(self.0)
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        Some(AnnotatedData::Bool(plain(self.0)))
+    }
+}
+
+/// Accepts exactly the values the inner validator rejects.
+#[derive(Debug, Clone)]
+pub struct NotValidator(pub Box<dyn Validator>);
+
+impl Validator for NotValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        if !self.0.validate(data.clone()).errors.is_empty() {
+            return ValidationResult::ok(data.into());
+        }
+        ValidationResult {
+            errors: vec![ValidationError::spanning(
+                &data.annotation,
+                format!("Value must not match {}", self.0.describe()),
+            )],
+            result: data.into(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("!{}", grouped(&*self.0, self.0.describe()))
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Not(Box::new(self.0.to_ir()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StringLiteralValidator(pub String);
+
+impl Validator for StringLiteralValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let found = match &data.value {
+            SpannedData::String(s) if s.value == self.0 => {
+                return ValidationResult::ok(data.into());
+            }
+            SpannedData::String(s) => format!("{:?}", s.value),
+            _ => data.value.kind().to_owned(),
+        };
+        ValidationResult {
+            errors: vec![ValidationError::spanning(
+                &data.annotation,
+                format!("Expected {:?}, found {found}", self.0),
+            )],
+            result: data.into(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("{:?}", self.0)
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::StringLiteral(self.0.clone())
+    }
+
+    fn string_literal(&self) -> Option<&str> {
+        Some(&self.0)
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        Some(AnnotatedData::String(plain(self.0.clone())))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NumberLiteralValidator(pub f64);
+
+impl Validator for NumberLiteralValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let found = match &data.value {
+            SpannedData::Number(n) if n.value.value == self.0 => {
+                return ValidationResult::ok(data.into());
+            }
+            SpannedData::Number(n) => n.value.to_string(),
+            _ => data.value.kind().to_owned(),
+        };
+        ValidationResult {
+            errors: vec![ValidationError::spanning(
+                &data.annotation,
+                format!("Expected {}, found {found}", self.0),
+            )],
+            result: data.into(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::NumberLiteral(self.0)
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        Some(AnnotatedData::Number(plain(self.0.into())))
+    }
+}
+
+/// Accepts numbers between `start` and `end`, as written with `0..=10` in the
+/// schema. The start is inclusive unless written with `<..`, like `0<..10`.
+#[derive(Debug, Clone)]
+pub struct RangeValidator {
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub excludes_start: bool,
+    pub is_inclusive: bool,
+}
+
+impl RangeValidator {
+    fn contains(&self, n: f64) -> bool {
+        self.start
+            .is_none_or(|s| s < n || !self.excludes_start && s == n)
+            && self
+                .end
+                .is_none_or(|e| n < e || self.is_inclusive && n == e)
+    }
+}
+
+impl Validator for RangeValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let found = match &data.value {
+            SpannedData::Number(n) if self.contains(n.value.value) => {
+                return ValidationResult::ok(data.into());
+            }
+            SpannedData::Number(n) => n.value.to_string(),
+            _ => data.value.kind().to_owned(),
+        };
+        ValidationResult {
+            errors: vec![ValidationError::spanning(
+                &data.annotation,
+                format!("Expected {}, found {found}", self.describe()),
+            )],
+            result: data.into(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{}{}..{}{}",
+            self.start.map(|s| s.to_string()).unwrap_or_default(),
+            if self.excludes_start { "<" } else { "" },
+            if self.is_inclusive { "=" } else { "" },
+            self.end.map(|e| e.to_string()).unwrap_or_default(),
+        )
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        let value = match (self.start, self.end) {
+            // Step into the range, staying below the end if it is close
+            (Some(start), Some(end)) if self.excludes_start && end - start <= 1.0 => {
+                (start + end) / 2.0
+            }
+            (Some(start), _) if self.excludes_start => start + 1.0,
+            (Some(start), _) => start,
+            (None, Some(end)) if self.is_inclusive => end,
+            (None, Some(end)) => end - 1.0,
+            (None, None) => 0.0,
+        };
+        // An empty range has nothing to suggest
+        self.contains(value)
+            .then(|| AnnotatedData::Number(plain(value.into())))
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Range {
+            start: self.start,
+            end: self.end,
+            excludes_start: self.excludes_start,
+            is_inclusive: self.is_inclusive,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegexValidator(pub regex::Regex);
+
+impl RegexValidator {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        regex::Regex::new(pattern).map(Self)
+    }
+}
+
+impl Validator for RegexValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let SpannedData::String(s) = &data.value else {
+            return kind_mismatch("String", data);
+        };
+        if !self.0.is_match(&s.value) {
+            return ValidationResult {
+                errors: vec![ValidationError::spanning(
+                    &data.annotation,
+                    format!("Value '{}' does not match /{}/", s.value, self.0.as_str()),
+                )],
+                result: data.into(),
+            };
+        }
+        ValidationResult::ok(data.into())
+    }
+
+    fn describe(&self) -> String {
+        format!("/{}/", self.0.as_str())
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Regex(self.0.as_str().to_owned())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArrayValidator {
+    pub element: Box<dyn Validator>,
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+    /// Whether elements must be pairwise distinct, compared with `structural_eq`
+    pub unique: bool,
+}
+
+impl ArrayValidator {
+    /// The part of the description inside the brackets, like `unique 1..=3`.
+    fn brackets(&self) -> String {
+        let len_range = describe_count(self.min, self.max);
+        match (self.unique, len_range.is_empty()) {
+            (false, _) => len_range,
+            (true, true) => "unique".to_owned(),
+            (true, false) => format!("unique {len_range}"),
+        }
+    }
+}
+
+/// A range of counts as written in a schema, like `1..=3`, or the count itself
+/// if the range has one value.
+fn describe_count(min: Option<usize>, max: Option<usize>) -> String {
+    match (min, max) {
+        (None, None) => String::new(),
+        (Some(min), Some(max)) if min == max => min.to_string(),
+        (None, Some(max)) => format!("..={max}"),
+        (Some(min), None) => format!("{min}.."),
+        (Some(min), Some(max)) => format!("{min}..={max}"),
+    }
+}
+
+/// What a count outside of `min..=max` was expected to be, like `at least 2`.
+fn expected_count(count: usize, min: Option<usize>, max: Option<usize>) -> Option<String> {
+    match (min, max) {
+        (Some(min), Some(max)) if min == max && count != min => Some(format!("exactly {min}")),
+        (Some(min), _) if count < min => Some(format!("at least {min}")),
+        (_, Some(max)) if count > max => Some(format!("at most {max}")),
+        _ => None,
+    }
+}
+
+impl Validator for ArrayValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let SpannedData::Array(items) = data.value else {
+            return kind_mismatch("Array", data);
+        };
+        let mut errors = vec![];
+        let items: Vec<Annotated<AnnotatedData>> = items
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let mut item = self
+                    .element
+                    .validate(x)
+                    .at(i)
+                    .append_errors_and_return_result(&mut errors);
+                item.set_schema_type(self.element.describe());
+                item
+            })
+            .collect();
+        if self.unique {
+            for (index, item) in items.iter().enumerate() {
+                if items[..index]
+                    .iter()
+                    .any(|x| x.value.structural_eq(&item.value))
+                {
+                    let mut error = ValidationError::spanning(
+                        &item.annotation.span,
+                        format!("Duplicate array item at index {index}"),
+                    );
+                    error.path = vec![index.to_string()];
+                    errors.push(error);
+                }
+            }
+        }
+        let len = items.len();
+        if let Some(expected) = expected_count(len, self.min, self.max) {
+            // Point at the first excess item of a too long array
+            let span = self
+                .max
+                .and_then(|max| items.get(max))
+                .and_then(|excess| excess.annotation.span.primary())
+                .or_else(|| data.annotation.primary())
+                .unwrap_or_default();
+            errors.push(ValidationError::new(
+                span,
+                format!("Array has {len} elements, expected {expected}"),
+            ));
+        }
+        let result = Annotated {
+            value: AnnotatedData::Array(items),
+            annotation: FullAnnotation {
+                span: data.annotation,
+                docs: String::new(),
+                semantic_type: None,
+                schema_type: None,
+                optional: false,
+            },
+        };
+        ValidationResult { result, errors }
+    }
+
+    fn describe(&self) -> String {
+        let element = grouped(&*self.element, self.element.describe());
+        format!("{element}[{}]", self.brackets())
     }
-}
 
-impl<T: Clone + Send + Sync + Fn(Spanned<SpannedData>) -> Option<String>> Validator
-    for LambdaValidator<T>
-{
-    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
-        let span = data.annotation.primary();
-        if let Some(text) = self.0(data.clone()) {
-            return ValidationResult {
-                errors: vec![ValidationError { span, text }],
-                result: data.into(),
-            };
-        } else {
-            ValidationResult::ok(data.into())
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Array {
+            element: Box::new(self.element.to_ir()),
+            min: self.min,
+            max: self.max,
+            unique: self.unique,
         }
     }
-}
 
-#[derive(Debug, Clone, Copy)]
-pub struct NumberValidator;
+    fn explain(&self) -> String {
+        let element = grouped(&*self.element, self.element.explain());
+        format!("{element}[{}]", self.brackets())
+    }
 
-impl Validator for NumberValidator {
-    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
-        let SpannedData::Number(_n) = &data.value else {
-            return ValidationResult {
-                errors: vec![ValidationError {
-                    span: data.annotation.primary(),
-                    text: format!("Expected Number, found {}", data.value.kind()),
-                }],
-                result: data.into(),
-            };
-        };
-        ValidationResult::ok(data.into())
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        let min = self.min.unwrap_or(0);
+        if min == 0 {
+            return Some(AnnotatedData::Array(vec![]));
+        }
+        // Copies of one element can't fill a unique array
+        if self.unique && min > 1 {
+            return None;
+        }
+        let element = plain(self.element.placeholder()?);
+        Some(AnnotatedData::Array(vec![element; min]))
     }
 }
 
+/// Validates a fixed length array, checking each element against the validator
+/// at the same position.
 #[derive(Debug, Clone)]
-pub struct ArrayValidator(pub Box<dyn Validator>, pub Option<usize>, pub Option<usize>);
+pub struct TupleValidator(pub Vec<Box<dyn Validator>>);
 
-impl Validator for ArrayValidator {
+impl Validator for TupleValidator {
     fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
         let SpannedData::Array(items) = data.value else {
-            return ValidationResult {
-                errors: vec![ValidationError {
-                    span: data.annotation.primary(),
-                    text: format!("Expected Object, found {}", data.value.kind()),
-                }],
-                result: data.into(),
-            };
+            return kind_mismatch("Array", data);
         };
         let mut errors = vec![];
+        if items.len() != self.0.len() {
+            errors.push(ValidationError::spanning(
+                &data.annotation,
+                format!("Expected {} elements, found {}", self.0.len(), items.len()),
+            ));
+        }
         let items: Vec<Annotated<AnnotatedData>> = items
             .into_iter()
-            .map(|x| {
-                self.0
-                    .validate(x)
-                    .append_errors_and_return_result(&mut errors)
+            .enumerate()
+            .map(|(i, x)| match self.0.get(i) {
+                Some(validator) => {
+                    let mut r = validator.validate(x).at(i);
+                    for error in &mut r.errors {
+                        error.text = format!("element {i}: {}", error.text);
+                    }
+                    let mut item = r.append_errors_and_return_result(&mut errors);
+                    item.set_schema_type(validator.describe());
+                    item
+                }
+                None => x.into(),
             })
             .collect();
         let result = Annotated {
-            value: AnnotatedData::Array(items.clone()),
+            value: AnnotatedData::Array(items),
             annotation: FullAnnotation {
                 span: data.annotation,
                 docs: String::new(),
                 semantic_type: None,
+                schema_type: None,
+                optional: false,
             },
         };
-        if let Some(max_items) = self.2 {
-            if let Some(excess_elem) = items.get(max_items) {
-                return ValidationResult {
-                    errors: vec![ValidationError {
-                        span: excess_elem.annotation.span.primary(),
-                        text: format!("Expected at most {max_items} number of elements"),
-                    }],
-                    result,
-                };
-            }
-        }
         ValidationResult { result, errors }
     }
+
+    fn describe(&self) -> String {
+        let elements = self
+            .0
+            .iter()
+            .map(|x| x.describe())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("[{elements}]")
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Tuple(self.0.iter().map(|x| x.to_ir()).collect())
+    }
+
+    fn explain(&self) -> String {
+        let elements = self
+            .0
+            .iter()
+            .map(|x| x.explain())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("[{elements}]")
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        let elements = self
+            .0
+            .iter()
+            .map(|x| Some(plain(x.placeholder()?)))
+            .collect::<Option<_>>()?;
+        Some(AnnotatedData::Array(elements))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum RecordValidator {
     SimpleKey {
         key: String,
+        /// Other names accepted for the key. The validated data has the key under
+        /// its main name.
+        aliases: Vec<String>,
+        /// Whether the key and its aliases match in any case
+        ignore_case: bool,
         docs: String,
         value: Box<dyn Validator>,
         optional: bool,
+        /// A value filled in when the key is absent. It has no spans.
+        default: Option<SpannedData>,
+    },
+    /// Any number of keys matching the `key` validator, as written with
+    /// `[string]: number` in the schema
+    Map {
+        key: Box<dyn Validator>,
+        value: Box<dyn Validator>,
     },
-    AnyKey,
 }
 
 impl RecordValidator {
-    fn matches(&self, input_key: &str) -> bool {
+    fn matches(&self, input_key: &Spanned<String>) -> bool {
         match self {
-            RecordValidator::SimpleKey { key, .. } => key == input_key,
-            RecordValidator::AnyKey => true,
+            RecordValidator::SimpleKey {
+                key,
+                aliases,
+                ignore_case,
+                ..
+            } => std::iter::once(key).chain(aliases).any(|name| {
+                *name == input_key.value
+                    || *ignore_case && name.to_lowercase() == input_key.value.to_lowercase()
+            }),
+            RecordValidator::Map { key, .. } => key
+                .validate(Spanned {
+                    value: SpannedData::String(input_key.clone()),
+                    annotation: input_key.annotation.clone(),
+                })
+                .errors
+                .is_empty(),
         }
     }
 
     fn validator(&self) -> &dyn Validator {
         match self {
-            RecordValidator::SimpleKey { value, .. } => &**value,
-            RecordValidator::AnyKey => &AnyValidator,
+            RecordValidator::SimpleKey { value, .. } | RecordValidator::Map { value, .. } => {
+                &**value
+            }
         }
     }
 
     fn docs(&self) -> String {
         match self {
             RecordValidator::SimpleKey { docs, .. } => docs.clone(),
-            RecordValidator::AnyKey => "".to_owned(),
+            RecordValidator::Map { .. } => String::new(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            RecordValidator::SimpleKey {
+                key,
+                aliases,
+                ignore_case,
+                value,
+                optional,
+                default,
+                ..
+            } => format!(
+                "{}{}: {}{}",
+                describe_names(key, aliases, *ignore_case),
+                if *optional { "?" } else { "" },
+                value.describe(),
+                describe_default(default.as_ref()),
+            ),
+            RecordValidator::Map { key, value } => {
+                format!("[{}]: {}", key.describe(), value.describe())
+            }
         }
     }
 }
 
+/// The names of a key as written in a schema, like `ignore_case a | b`.
+fn describe_names(key: &str, aliases: &[String], ignore_case: bool) -> String {
+    let names = [key]
+        .into_iter()
+        .chain(aliases.iter().map(|a| a.as_str()))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    if ignore_case {
+        format!("ignore_case {names}")
+    } else {
+        names
+    }
+}
+
+fn describe_default(default: Option<&SpannedData>) -> String {
+    let value = match default {
+        None => return String::new(),
+        Some(SpannedData::Null) => "null".to_owned(),
+        Some(SpannedData::Bool(b)) => b.value.to_string(),
+        Some(SpannedData::Number(n)) => n.value.to_string(),
+        Some(SpannedData::String(s)) => format!("{:?}", s.value),
+        Some(SpannedData::Array(_) | SpannedData::Object(_)) => "..".to_owned(),
+    };
+    format!(" = {value}")
+}
+
 #[derive(Debug, Clone)]
-pub struct ObjectValidator(pub Vec<RecordValidator>);
+pub struct ObjectValidator {
+    pub records: Vec<RecordValidator>,
+    /// Whether keys without a matching record are accepted without validation,
+    /// as written with `..` in the schema
+    pub allow_unknown: bool,
+    /// Bounds on the number of keys written in the object, as written with
+    /// `keys 2..=10` in the schema. Every key counts, whether it has a record or
+    /// not, but keys filled in from defaults don't.
+    pub min_keys: Option<usize>,
+    pub max_keys: Option<usize>,
+}
 
 #[derive(Debug, Clone)]
-pub struct OrValidator(pub Vec<Box<dyn Validator>>);
+pub struct OrValidator {
+    pub cases: Vec<Box<dyn Validator>>,
+    /// A key whose string literal value selects the case to validate against. Only
+    /// set when every case is an object with a distinct literal for this key. It is
+    /// inferred on first use, once the types the cases refer to are defined.
+    discriminant: OnceLock<Option<String>>,
+}
 
 impl ObjectValidator {
-    fn mandatory_keys(&self) -> impl Iterator<Item = &str> {
-        self.0.iter().filter_map(|x| match x {
+    fn mandatory_keys(&self) -> impl Iterator<Item = (&str, &dyn Validator)> {
+        self.records.iter().filter_map(|x| match x {
             RecordValidator::SimpleKey {
                 key,
+                value,
                 optional: false,
+                default: None,
                 ..
-            } => Some(&**key),
+            } => Some((&**key, &**value)),
             _ => None,
         })
     }
 
-    fn find_validator(&self, key: &str) -> Option<&RecordValidator> {
-        self.0.iter().find(|x| x.matches(key))
+    /// Finds the record of `key`. Simple keys take precedence over maps, and the
+    /// exact name of a key over an alias or a different case. Maps are tried in
+    /// order.
+    fn find_validator(&self, key: &Spanned<String>) -> Option<&RecordValidator> {
+        let (simple, maps): (Vec<_>, Vec<_>) = self
+            .records
+            .iter()
+            .partition(|x| matches!(x, RecordValidator::SimpleKey { .. }));
+        let exact = simple
+            .iter()
+            .find(|x| matches!(x, RecordValidator::SimpleKey { key: k, .. } if *k == key.value));
+        exact
+            .copied()
+            .or_else(|| simple.into_iter().chain(maps).find(|x| x.matches(key)))
     }
 }
 
 impl Validator for ObjectValidator {
     fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
         let SpannedData::Object(key_values) = data.value else {
-            return ValidationResult {
-                errors: vec![ValidationError {
-                    span: data.annotation.primary(),
-                    text: format!("Expected Object, found {}", data.value.kind()),
-                }],
-                result: data.into(),
-            };
+            return kind_mismatch("Object", data);
         };
         let mut errors = vec![];
         let mut result: Vec<(Annotated<String>, Annotated<AnnotatedData>)> = vec![];
@@ -210,45 +1206,141 @@ impl Validator for ObjectValidator {
         let mut visited_keys = HashSet::new();
 
         for (key, value) in key_values {
-            if !visited_keys.insert(key.value.clone()) {
-                errors.push(ValidationError {
-                    span: key.annotation.primary(),
-                    text: format!("Duplicate key {}", key.value),
-                });
+            let record = self.find_validator(&key);
+            // A key written as an alias or in another case gets its main name
+            let name = match record {
+                Some(RecordValidator::SimpleKey { key, .. }) => key.clone(),
+                _ => key.value.clone(),
+            };
+            if !visited_keys.insert(name.clone()) {
+                let mut error =
+                    ValidationError::spanning(&key.annotation, format!("Duplicate key {name}"));
+                error.path = vec![key.value.clone()];
+                errors.push(error);
             }
 
-            let Some(record_validator) = self.find_validator(&key.value) else {
-                errors.push(ValidationError {
-                    span: key.annotation.primary(),
-                    text: format!("Unexpected key {}", key.value),
-                });
-                continue;
+            let (validator, docs) = match record {
+                Some(record_validator) => (record_validator.validator(), record_validator.docs()),
+                None if self.allow_unknown => (&AnyValidator as &dyn Validator, String::new()),
+                None => {
+                    // With map records, the key is wrong rather than unexpected
+                    let patterns: Vec<String> = self
+                        .records
+                        .iter()
+                        .filter_map(|r| match r {
+                            RecordValidator::Map { key, .. } => Some(key.describe()),
+                            RecordValidator::SimpleKey { .. } => None,
+                        })
+                        .collect();
+                    let text = if patterns.is_empty() {
+                        format!("Unexpected key {}", key.value)
+                    } else {
+                        format!(
+                            "Key {:?} does not match allowed pattern {}",
+                            key.value,
+                            patterns.join(" or ")
+                        )
+                    };
+                    let mut error = ValidationError::spanning(&key.annotation, text);
+                    let known_keys = self.records.iter().filter_map(|r| match r {
+                        RecordValidator::SimpleKey { key, .. } => Some(key.as_str()),
+                        RecordValidator::Map { .. } => None,
+                    });
+                    error.kind = ValidationErrorKind::UnknownKey;
+                    error.path = vec![key.value.clone()];
+                    error.help = closest_match(&key.value, known_keys)
+                        .map(|known| format!("Did you mean `{known}`?"));
+                    errors.push(error);
+                    continue;
+                }
             };
 
-            let r = record_validator.validator().validate(value);
+            let r = validator.validate(value).at(&key.value);
+            let schema_type = validator.describe();
+            // A key with a default may be left out too
+            let optional = matches!(
+                record,
+                Some(
+                    RecordValidator::SimpleKey { optional: true, .. }
+                        | RecordValidator::SimpleKey {
+                            default: Some(_),
+                            ..
+                        }
+                )
+            );
 
             // Apply documentation to the key
             let annotated_key = Annotated {
-                value: key.value,
                 annotation: FullAnnotation {
                     span: key.annotation,
-                    docs: record_validator.docs(),
-                    semantic_type: Some(SemanticType::Variable),
+                    docs,
+                    semantic_type: Some(SemanticType::Key),
+                    schema_type: Some(format!("{name}: {schema_type}")),
+                    optional,
                 },
+                value: name,
             };
 
-            result.push((
-                annotated_key,
-                r.append_errors_and_return_result(&mut errors),
+            let mut value = r.append_errors_and_return_result(&mut errors);
+            value.set_schema_type(schema_type);
+            result.push((annotated_key, value));
+        }
+
+        let count = visited_keys.len();
+        if let Some(expected) = expected_count(count, self.min_keys, self.max_keys) {
+            let properties = if count == 1 { "property" } else { "properties" };
+            errors.push(ValidationError::spanning(
+                &data.annotation,
+                format!("Object has {count} {properties}, expected {expected}"),
             ));
         }
 
-        for mandatory_key in self.mandatory_keys() {
+        for record in &self.records {
+            let RecordValidator::SimpleKey {
+                key,
+                docs,
+                value: validator,
+                default: Some(default),
+                optional,
+                ..
+            } = record
+            else {
+                continue;
+            };
+            if visited_keys.contains(key) {
+                continue;
+            }
+            let schema_type = validator.describe();
+            let annotated_key = Annotated {
+                annotation: FullAnnotation {
+                    span: SpanSet(vec![]),
+                    docs: docs.clone(),
+                    semantic_type: Some(SemanticType::Key),
+                    schema_type: Some(format!("{key}: {schema_type}")),
+                    optional: *optional,
+                },
+                value: key.clone(),
+            };
+            let mut value: Annotated<AnnotatedData> = Spanned {
+                value: default.clone(),
+                annotation: SpanSet(vec![]),
+            }
+            .into();
+            value.set_schema_type(schema_type);
+            result.push((annotated_key, value));
+        }
+
+        for (mandatory_key, validator) in self.mandatory_keys() {
             if !visited_keys.contains(mandatory_key) {
-                errors.push(ValidationError {
-                    span: data.annotation.primary(),
-                    text: format!("Missing key {}", mandatory_key),
-                });
+                let mut error = ValidationError::spanning(
+                    &data.annotation,
+                    format!("Missing key {}", mandatory_key),
+                );
+                error.kind = ValidationErrorKind::MissingKey {
+                    key: mandatory_key.to_owned(),
+                    placeholder: validator.placeholder(),
+                };
+                errors.push(error);
             }
         }
 
@@ -259,19 +1351,691 @@ impl Validator for ObjectValidator {
                     span: data.annotation,
                     docs: String::new(),
                     semantic_type: None,
+                    schema_type: None,
+                    optional: false,
                 },
             },
             errors,
         }
     }
+
+    fn describe(&self) -> String {
+        let mut records = self
+            .records
+            .iter()
+            .map(|r| r.describe())
+            .collect::<Vec<_>>();
+        if self.allow_unknown {
+            records.push("..".to_owned());
+        }
+        if self.min_keys.is_some() || self.max_keys.is_some() {
+            records.push(format!(
+                "keys {}",
+                describe_count(self.min_keys, self.max_keys)
+            ));
+        }
+        format!("{{ {} }}", records.join(", "))
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        let mut fields = BTreeMap::new();
+        let mut maps = vec![];
+        for record in &self.records {
+            match record {
+                RecordValidator::SimpleKey {
+                    key,
+                    aliases,
+                    ignore_case,
+                    value,
+                    optional,
+                    default,
+                    ..
+                } => {
+                    let field = FieldIr {
+                        value: value.to_ir(),
+                        aliases: aliases.clone(),
+                        ignore_case: *ignore_case,
+                        optional: *optional,
+                        default: default.as_ref().map(|d| {
+                            describe_default(Some(d))
+                                .trim_start_matches(" = ")
+                                .to_owned()
+                        }),
+                    };
+                    fields.insert(key.clone(), field);
+                }
+                RecordValidator::Map { key, value } => maps.push((key.to_ir(), value.to_ir())),
+            }
+        }
+        SchemaIr::Object {
+            fields,
+            maps,
+            allow_unknown: self.allow_unknown,
+            min_keys: self.min_keys,
+            max_keys: self.max_keys,
+        }
+    }
+
+    fn explain(&self) -> String {
+        let mut result = "{\n".to_owned();
+        for record in &self.records {
+            match record {
+                RecordValidator::SimpleKey {
+                    key,
+                    aliases,
+                    ignore_case,
+                    docs,
+                    value,
+                    optional,
+                    default,
+                } => {
+                    for line in docs.lines() {
+                        result += &format!("    /// {}\n", line.trim());
+                    }
+                    let names = describe_names(key, aliases, *ignore_case);
+                    let value = value.explain().replace('\n', "\n    ");
+                    let optional = if *optional { "?" } else { "" };
+                    let default = describe_default(default.as_ref());
+                    result += &format!("    {names}{optional}: {value}{default},\n");
+                }
+                RecordValidator::Map { key, value } => {
+                    let value = value.explain().replace('\n', "\n    ");
+                    result += &format!("    [{}]: {value},\n", key.describe());
+                }
+            }
+        }
+        if self.allow_unknown {
+            result += "    ..,\n";
+        }
+        if self.min_keys.is_some() || self.max_keys.is_some() {
+            let count = describe_count(self.min_keys, self.max_keys);
+            result += &format!("    keys {count},\n");
+        }
+        result + "}"
+    }
+
+    fn object_records(&self) -> Option<&[RecordValidator]> {
+        Some(&self.records)
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        let pairs = self
+            .mandatory_keys()
+            .map(|(key, validator)| Some((plain(key.to_owned()), plain(validator.placeholder()?))))
+            .collect::<Option<Vec<_>>>()?;
+        let enough_keys = self.min_keys.is_none_or(|min| pairs.len() >= min);
+        enough_keys.then_some(AnnotatedData::Object(pairs))
+    }
+}
+
+impl OrValidator {
+    /// Creates a union of `cases`.
+    pub fn new(cases: Vec<Box<dyn Validator>>) -> Self {
+        Self {
+            cases,
+            discriminant: OnceLock::new(),
+        }
+    }
+
+    /// Finds the case selected by the discriminant of `data`, if any.
+    fn tagged_case(&self, data: &SpannedData) -> Option<&dyn Validator> {
+        let key = self
+            .discriminant
+            .get_or_init(|| find_discriminant(&self.cases))
+            .as_deref()?;
+        let SpannedData::Object(pairs) = data else {
+            return None;
+        };
+        let (_, value) = pairs.iter().find(|(k, _)| k.value == key)?;
+        let SpannedData::String(tag) = &value.value else {
+            return None;
+        };
+        self.cases
+            .iter()
+            .find(|case| case_tag(&***case, key) == Some(&tag.value))
+            .map(|case| &**case)
+    }
+}
+
+/// The literal value of the mandatory `key` of an object validator.
+fn case_tag<'a>(case: &'a dyn Validator, key: &str) -> Option<&'a str> {
+    case.object_records()?.iter().find_map(|r| match r {
+        RecordValidator::SimpleKey {
+            key: k,
+            value,
+            optional: false,
+            ..
+        } if k == key => value.string_literal(),
+        _ => None,
+    })
+}
+
+fn find_discriminant(cases: &[Box<dyn Validator>]) -> Option<String> {
+    if cases.len() < 2 {
+        return None;
+    }
+    cases[0].object_records()?.iter().find_map(|r| {
+        let RecordValidator::SimpleKey { key, .. } = r else {
+            return None;
+        };
+        let mut tags = HashSet::new();
+        cases
+            .iter()
+            .all(|case| case_tag(&**case, key).is_some_and(|tag| tags.insert(tag)))
+            .then(|| key.clone())
+    })
 }
 
 impl Validator for OrValidator {
     fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
-        self.0
+        if let Some(case) = self.tagged_case(&data.value) {
+            return case.validate(data);
+        }
+        let mut closest: Option<ValidationResult> = None;
+        for case in &self.cases {
+            let result = case.validate(data.clone());
+            if result.errors.is_empty() {
+                return result;
+            }
+            if closest
+                .as_ref()
+                .is_none_or(|c| result.errors.len() < c.errors.len())
+            {
+                closest = Some(result);
+            }
+        }
+        // The result of a failed case is annotated with that case's types, which
+        // would be wrong for a value that matched none of them
+        ValidationResult {
+            errors: closest.map(|c| c.errors).unwrap_or_default(),
+            result: data.into(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        self.cases
+            .iter()
+            .map(|v| group_one_of(&**v, v.describe()))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Or(self.cases.iter().map(|x| x.to_ir()).collect())
+    }
+
+    fn explain(&self) -> String {
+        self.cases
+            .iter()
+            .map(|v| group_one_of(&**v, v.explain()))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    fn placeholder(&self) -> Option<AnnotatedData<()>> {
+        self.cases.iter().find_map(|case| case.placeholder())
+    }
+
+    fn union_operator(&self) -> Option<char> {
+        (self.cases.len() > 1).then_some('|')
+    }
+}
+
+/// Wraps `text` in parentheses if `validator` is a oneOf, which binds looser than
+/// `|`. A union inside a union needs no parentheses.
+fn group_one_of(validator: &dyn Validator, text: String) -> String {
+    if validator.union_operator() == Some('^') {
+        format!("({text})")
+    } else {
+        text
+    }
+}
+
+/// Accepts values matching exactly one of its cases, like `oneOf` in JSON Schema.
+#[derive(Debug, Clone)]
+pub struct XorValidator(pub Vec<Box<dyn Validator>>);
+
+impl Validator for XorValidator {
+    fn validate(&self, data: Spanned<SpannedData>) -> ValidationResult {
+        let mut matched: Vec<_> = self
+            .0
             .iter()
             .map(|v| v.validate(data.clone()))
-            .min_by_key(|x| x.errors.len())
-            .unwrap()
+            .filter(|x| x.errors.is_empty())
+            .collect();
+        if matched.len() == 1 {
+            return matched.remove(0);
+        }
+        ValidationResult {
+            errors: vec![ValidationError::spanning(
+                &data.annotation,
+                format!(
+                    "Value matched {} of the oneOf branches, expected exactly 1",
+                    matched.len()
+                ),
+            )],
+            result: data.into(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        self.0
+            .iter()
+            .map(|v| v.describe())
+            .collect::<Vec<_>>()
+            .join(" ^ ")
+    }
+
+    fn to_ir(&self) -> SchemaIr {
+        SchemaIr::Xor(self.0.iter().map(|x| x.to_ir()).collect())
+    }
+
+    fn explain(&self) -> String {
+        self.0
+            .iter()
+            .map(|v| v.explain())
+            .collect::<Vec<_>>()
+            .join(" ^ ")
+    }
+
+    fn union_operator(&self) -> Option<char> {
+        (self.0.len() > 1).then_some('^')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deval_data_model::SpanSet;
+
+    fn spanned<T>(value: T) -> Spanned<T> {
+        Spanned {
+            value,
+            annotation: SpanSet(vec![Span {
+                filename: "test".to_owned(),
+                start: 0,
+                end: 0,
+            }]),
+        }
+    }
+
+    fn unspanned<T>(value: T) -> Spanned<T> {
+        Spanned {
+            value,
+            annotation: SpanSet(vec![]),
+        }
+    }
+
+    fn errors(validator: impl Validator, data: SpannedData) -> Vec<String> {
+        validator
+            .validate(spanned(data))
+            .errors
+            .into_iter()
+            .map(|e| e.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_string_validator() {
+        assert!(
+            errors(
+                StringValidator,
+                SpannedData::String(spanned("a".to_owned()))
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            errors(StringValidator, SpannedData::Number(spanned(1.0.into()))),
+            ["Expected String, found Number"]
+        );
+    }
+
+    #[test]
+    fn test_number_validator() {
+        assert!(errors(NumberValidator, SpannedData::Number(spanned(1.5.into()))).is_empty());
+        assert_eq!(
+            errors(NumberValidator, SpannedData::Null),
+            ["Expected Number, found Null"]
+        );
+    }
+
+    #[test]
+    fn test_integer_validator() {
+        assert!(errors(IntegerValidator, SpannedData::Number(spanned(2.0.into()))).is_empty());
+        assert_eq!(
+            errors(IntegerValidator, SpannedData::Number(spanned(1.5.into()))),
+            ["Expected Integer, found Number"]
+        );
+        assert_eq!(
+            errors(IntegerValidator, SpannedData::Bool(spanned(true))),
+            ["Expected Integer, found Bool"]
+        );
+    }
+
+    #[test]
+    fn test_bool_validator() {
+        assert!(errors(BoolValidator, SpannedData::Bool(spanned(false))).is_empty());
+        assert_eq!(
+            errors(
+                BoolValidator,
+                SpannedData::String(spanned("true".to_owned()))
+            ),
+            ["Expected Bool, found String"]
+        );
+    }
+
+    #[test]
+    fn test_null_validator() {
+        assert!(errors(NullValidator, SpannedData::Null).is_empty());
+        assert_eq!(
+            errors(NullValidator, SpannedData::Array(vec![])),
+            ["Expected Null, found Array"]
+        );
+    }
+
+    #[test]
+    fn test_regex_validator() {
+        let regex = || RegexValidator(regex::Regex::new("^a+$").unwrap());
+        assert!(errors(regex(), SpannedData::String(spanned("aa".to_owned()))).is_empty());
+        assert_eq!(
+            errors(regex(), SpannedData::String(spanned("b".to_owned()))),
+            ["Value 'b' does not match /^a+$/"]
+        );
+        assert_eq!(
+            errors(regex(), SpannedData::Bool(spanned(true))),
+            ["Expected String, found Bool"]
+        );
+    }
+
+    fn xor() -> XorValidator {
+        XorValidator(vec![Box::new(NumberValidator), Box::new(IntegerValidator)])
+    }
+
+    #[test]
+    fn test_xor_validator_zero_matches() {
+        assert_eq!(
+            errors(xor(), SpannedData::Null),
+            ["Value matched 0 of the oneOf branches, expected exactly 1"]
+        );
+    }
+
+    #[test]
+    fn test_xor_validator_one_match() {
+        assert!(errors(xor(), SpannedData::Number(spanned(1.5.into()))).is_empty());
+    }
+
+    #[test]
+    fn test_xor_validator_two_matches() {
+        assert_eq!(
+            errors(xor(), SpannedData::Number(spanned(2.0.into()))),
+            ["Value matched 2 of the oneOf branches, expected exactly 1"]
+        );
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let keys = ["email", "name", "address"];
+        assert_eq!(closest_match("emial", keys), Some("email"));
+        assert_eq!(closest_match("adress", keys), Some("address"));
+        assert_eq!(closest_match("phone", keys), None);
+    }
+
+    #[test]
+    fn test_unknown_key_suggestion() {
+        let validator = ObjectValidator {
+            records: vec![RecordValidator::SimpleKey {
+                key: "email".to_owned(),
+                aliases: vec![],
+                ignore_case: false,
+                docs: String::new(),
+                value: Box::new(StringValidator),
+                optional: true,
+                default: None,
+            }],
+            allow_unknown: false,
+            min_keys: None,
+            max_keys: None,
+        };
+        let data = SpannedData::Object(vec![(
+            spanned("emial".to_owned()),
+            spanned(SpannedData::String(spanned("a@b.c".to_owned()))),
+        )]);
+        let errors = validator.validate(spanned(data)).errors;
+        assert_eq!(errors[0].text, "Unexpected key emial");
+        assert_eq!(errors[0].help.as_deref(), Some("Did you mean `email`?"));
+        assert_eq!(errors[0].severity, Severity::Error);
+        assert_eq!(errors[0].kind, ValidationErrorKind::UnknownKey);
+    }
+
+    #[test]
+    fn test_missing_key_placeholder() {
+        let record = |key: &str, value: Box<dyn Validator>| RecordValidator::SimpleKey {
+            key: key.to_owned(),
+            aliases: vec![],
+            ignore_case: false,
+            docs: String::new(),
+            value,
+            optional: false,
+            default: None,
+        };
+        let validator = ObjectValidator {
+            records: vec![
+                record("name", Box::new(StringValidator)),
+                record("mode", Box::new(StringLiteralValidator("fast".to_owned()))),
+                record(
+                    "tags",
+                    Box::new(ArrayValidator {
+                        element: Box::new(StringValidator),
+                        min: None,
+                        max: None,
+                        unique: false,
+                    }),
+                ),
+                record("extra", Box::new(AnyValidator)),
+            ],
+            allow_unknown: false,
+            min_keys: None,
+            max_keys: None,
+        };
+        let errors = validator
+            .validate(spanned(SpannedData::Object(vec![])))
+            .errors;
+        let kinds: Vec<_> = errors.iter().map(|e| e.kind.clone()).collect();
+        let missing = |key: &str, placeholder: AnnotatedData<()>| ValidationErrorKind::MissingKey {
+            key: key.to_owned(),
+            placeholder: Some(placeholder),
+        };
+        assert_eq!(
+            kinds,
+            [
+                missing("name", AnnotatedData::String(plain(String::new()))),
+                missing("mode", AnnotatedData::String(plain("fast".to_owned()))),
+                missing("tags", AnnotatedData::Array(vec![])),
+                missing("extra", AnnotatedData::Null),
+            ]
+        );
+        assert_eq!(errors[0].text, "Missing key name");
+        assert_eq!(errors[0].kind.code(), "missing-key");
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let range = |start, end, is_inclusive| RangeValidator {
+            start,
+            end,
+            excludes_start: false,
+            is_inclusive,
+        };
+        let number = |n: f64| AnnotatedData::Number(plain(n.into()));
+        assert_eq!(range(Some(5.), None, false).placeholder(), Some(number(5.)));
+        assert_eq!(
+            range(None, Some(10.), false).placeholder(),
+            Some(number(9.))
+        );
+        assert_eq!(
+            range(None, Some(10.), true).placeholder(),
+            Some(number(10.))
+        );
+        assert_eq!(range(Some(1.), Some(1.), false).placeholder(), None);
+        let excluding = |start, end| RangeValidator {
+            start: Some(start),
+            end,
+            excludes_start: true,
+            is_inclusive: false,
+        };
+        assert_eq!(excluding(0., None).placeholder(), Some(number(1.)));
+        assert_eq!(excluding(0., Some(0.5)).placeholder(), Some(number(0.25)));
+        assert_eq!(excluding(1., Some(1.)).placeholder(), None);
+
+        let tuple = TupleValidator(vec![
+            Box::new(range(Some(1.), None, false)),
+            Box::new(StringValidator),
+        ]);
+        let string = AnnotatedData::String(plain(String::new()));
+        assert_eq!(
+            tuple.placeholder(),
+            Some(AnnotatedData::Array(vec![plain(number(1.)), plain(string)]))
+        );
+        let negated = TupleValidator(vec![Box::new(NotValidator(Box::new(NullValidator)))]);
+        assert_eq!(negated.placeholder(), None);
+    }
+
+    #[test]
+    fn test_unique_array_ignores_spans() {
+        let at = |start, value: f64| Spanned {
+            value: SpannedData::Number(spanned(value.into())),
+            annotation: SpanSet(vec![Span {
+                filename: "test".to_owned(),
+                start,
+                end: start + 1,
+            }]),
+        };
+        let validator = ArrayValidator {
+            element: Box::new(NumberValidator),
+            min: None,
+            max: None,
+            unique: true,
+        };
+        let data = SpannedData::Array(vec![at(1, 1.), at(3, 2.), at(5, 1.)]);
+        let errors = validator.validate(spanned(data)).errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].text, "Duplicate array item at index 2");
+        assert_eq!(errors[0].span.start, 5);
+    }
+
+    #[test]
+    fn test_array_validator_kind_mismatch() {
+        let validator = ArrayValidator {
+            element: Box::new(NumberValidator),
+            min: None,
+            max: None,
+            unique: false,
+        };
+        assert_eq!(
+            errors(validator, SpannedData::Bool(spanned(true))),
+            ["Expected Array, found Bool"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_data_without_spans() {
+        let number = unspanned(SpannedData::Number(unspanned(1.0.into())));
+        let result = StringValidator.validate(number.clone());
+        assert_eq!(result.errors[0].span, Span::default());
+
+        let too_long = ArrayValidator {
+            element: Box::new(NumberValidator),
+            min: None,
+            max: Some(0),
+            unique: false,
+        };
+        let result = too_long.validate(unspanned(SpannedData::Array(vec![number.clone()])));
+        assert_eq!(
+            result.errors[0].text,
+            "Array has 1 elements, expected at most 0"
+        );
+
+        let lambda = LambdaValidator("never".to_owned(), |_| Some("No".to_owned()));
+        assert_eq!(lambda.validate(number).errors[0].text, "No");
+    }
+
+    #[test]
+    fn test_kind_mismatch_points_at_value() {
+        let data = || Spanned {
+            value: SpannedData::String(spanned("x".to_owned())),
+            annotation: SpanSet(vec![Span {
+                filename: "test".to_owned(),
+                start: 4,
+                end: 7,
+            }]),
+        };
+        let array = ArrayValidator {
+            element: Box::new(NumberValidator),
+            min: None,
+            max: None,
+            unique: false,
+        };
+        let object = ObjectValidator {
+            records: vec![],
+            allow_unknown: true,
+            min_keys: None,
+            max_keys: None,
+        };
+        let tuple = TupleValidator(vec![Box::new(NumberValidator)]);
+        let validators: [(&dyn Validator, &str); 3] =
+            [(&array, "Array"), (&object, "Object"), (&tuple, "Array")];
+        for (validator, kind) in validators {
+            let errors = validator.validate(data()).errors;
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].text, format!("Expected {kind}, found String"));
+            assert_eq!((errors[0].span.start, errors[0].span.end), (4, 7));
+        }
+    }
+
+    #[test]
+    fn test_or_validator_failure_keeps_data() {
+        let validator = OrValidator::new(vec![
+            Box::new(ArrayValidator {
+                element: Box::new(NumberValidator),
+                min: None,
+                max: None,
+                unique: false,
+            }),
+            Box::new(StringValidator),
+        ]);
+        let data = SpannedData::Array(vec![spanned(SpannedData::Bool(spanned(true)))]);
+        let result = validator.validate(spanned(data));
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| e.text.as_str())
+                .collect::<Vec<_>>(),
+            ["Expected Number, found Bool"]
+        );
+        let AnnotatedData::Array(items) = &result.result.value else {
+            panic!("Expected the array to stay an array");
+        };
+        assert!(matches!(items[0].value, AnnotatedData::Bool(_)));
+        assert_eq!(items[0].annotation.schema_type, None);
+
+        let result = validator.validate(spanned(SpannedData::String(spanned("a".to_owned()))));
+        assert!(result.errors.is_empty());
+    }
+
+    #[test]
+    fn test_recursive_ref_is_dropped() {
+        let node = RefValidator::new("Node".to_owned());
+        node.set_target(Box::new(ArrayValidator {
+            element: Box::new(node.downgrade()),
+            min: None,
+            max: None,
+            unique: false,
+        }));
+        let array = || SpannedData::Array(vec![]);
+        assert!(errors(node.clone(), array()).is_empty());
+        let weak = node.downgrade();
+        drop(node);
+        assert_eq!(errors(weak, array()), ["Type Node is not defined"]);
     }
 }
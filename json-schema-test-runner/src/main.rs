@@ -1,14 +1,23 @@
 use clap::{Parser, Subcommand};
+use deval_data_model::Format;
+use deval_format_json::Json;
+use deval_validator::{Severity, Validator};
 use serde::Deserialize;
 use std::env;
 use std::fs;
+use std::panic::AssertUnwindSafe;
 use std::process::Command;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Parser)]
 #[clap(name = "json-schema-test-runner", version = "0.1.0")]
 struct Cli {
     #[clap(subcommand)]
     command: Commands,
+    /// Run the deval-cli binary for every conversion and check, instead of
+    /// validating in this process. Slower, but tests the CLI end to end
+    #[clap(long, global = true)]
+    subprocess: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -24,6 +33,10 @@ enum Commands {
         /// Show detailed output for each test
         #[clap(short, long)]
         verbose: bool,
+        /// Run the analysis both in process and through deval-cli, and compare
+        /// their wall times
+        #[clap(long)]
+        compare: bool,
     },
     /// Debug a specific test case
     Debug {
@@ -53,36 +66,101 @@ struct Test {
     valid: bool,
 }
 
+/// How test data is checked against the converted schemas.
+enum Mode<'a> {
+    /// Convert and compile each schema once, and validate its tests in this
+    /// process
+    InProcess,
+    /// Run the deval-cli binary at `cli_path` for each conversion and check,
+    /// passing files through `temp_dir`
+    Subprocess {
+        cli_path: &'a str,
+        temp_dir: &'a str,
+    },
+}
+
+impl Mode<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::InProcess => "in process",
+            Mode::Subprocess { .. } => "subprocess",
+        }
+    }
+}
+
+/// A converted schema, ready to check test data against.
+enum CompiledSchema {
+    InProcess(Box<dyn Validator>),
+    /// The path of the converted schema, compiled by the CLI on each check
+    Subprocess(String),
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
-    // Build the deval-cli binary once
-    println!("Building deval-cli...");
-    let output = Command::new("cargo")
-        .args(&["build", "--bin", "deval-cli"])
-        .current_dir("..")
-        .output()?;
+    let deval_cli_path = "../target/debug/deval-cli";
 
-    if !output.status.success() {
-        eprintln!(
-            "Failed to build deval-cli: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-        return Ok(());
-    }
+    let compare = matches!(cli.command, Commands::Analyze { compare: true, .. });
+    if cli.subprocess || compare {
+        // Build the deval-cli binary once
+        println!("Building deval-cli...");
+        let output = Command::new("cargo")
+            .args(["build", "--bin", "deval-cli"])
+            .current_dir("..")
+            .output()?;
 
-    let deval_cli_path = "../target/debug/deval-cli";
+        if !output.status.success() {
+            eprintln!(
+                "Failed to build deval-cli: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            return Ok(());
+        }
+    }
 
     match cli.command {
         Commands::Analyze {
             files,
             cases,
             verbose,
+            compare,
         } => {
-            run_analysis(deval_cli_path, files, cases, verbose)?;
+            let temp_dir = "/tmp/json-schema-test-runner";
+            let subprocess = Mode::Subprocess {
+                cli_path: deval_cli_path,
+                temp_dir,
+            };
+            let modes = match (compare, cli.subprocess) {
+                (true, _) => vec![Mode::InProcess, subprocess],
+                (false, true) => vec![subprocess],
+                (false, false) => vec![Mode::InProcess],
+            };
+            let mut times = vec![];
+            for mode in &modes {
+                let time = run_analysis(mode, files.clone(), cases.clone(), verbose)?;
+                times.push((mode.name(), time));
+            }
+            if compare {
+                println!("\n=== Wall time ===");
+                for (name, time) in &times {
+                    println!("  {name}: {time:.2?}");
+                }
+                println!(
+                    "  subprocess / in process = {:.1}x",
+                    times[1].1.as_secs_f64() / times[0].1.as_secs_f64()
+                );
+            }
         }
         Commands::Debug { file, case, test } => {
-            run_debug(deval_cli_path, &file, case, test)?;
+            let temp_dir = "/tmp/json-schema-test-runner-debug";
+            let mode = match cli.subprocess {
+                true => Mode::Subprocess {
+                    cli_path: deval_cli_path,
+                    temp_dir,
+                },
+                false => Mode::InProcess,
+            };
+            run_debug(&mode, &file, case, test)?;
         }
     }
 
@@ -90,16 +168,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn run_analysis(
-    deval_cli_path: &str,
+    mode: &Mode,
     files: Option<Vec<String>>,
     cases: Option<Vec<usize>>,
     verbose: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Running comprehensive test suite analysis");
+) -> Result<Duration, Box<dyn std::error::Error>> {
+    println!(
+        "Running comprehensive test suite analysis ({})",
+        mode.name()
+    );
+    let start = Instant::now();
 
     // Create temp directory
-    let temp_dir = "/tmp/json-schema-test-runner";
-    fs::create_dir_all(temp_dir)?;
+    if let Mode::Subprocess { temp_dir, .. } = mode {
+        fs::create_dir_all(temp_dir)?;
+    }
 
     let test_files = if let Some(files) = files {
         files
@@ -127,8 +210,7 @@ fn run_analysis(
     for test_file in test_files {
         println!("\n=== Testing {} ===", test_file);
         test_file_coverage(
-            deval_cli_path,
-            temp_dir,
+            mode,
             &test_file,
             &cases,
             verbose,
@@ -143,16 +225,19 @@ fn run_analysis(
         total_passed,
         total_passed + total_failed,
     );
+    let elapsed = start.elapsed();
+    println!("Total wall time = {:.2?} ({})", elapsed, mode.name());
 
     // Clean up temp directory
-    let _ = fs::remove_dir_all(temp_dir);
+    if let Mode::Subprocess { temp_dir, .. } = mode {
+        let _ = fs::remove_dir_all(temp_dir);
+    }
 
-    Ok(())
+    Ok(elapsed)
 }
 
 fn test_file_coverage(
-    deval_cli_path: &str,
-    temp_dir: &str,
+    mode: &Mode,
     filename: &str,
     cases_filter: &Option<Vec<usize>>,
     verbose: bool,
@@ -178,33 +263,23 @@ fn test_file_coverage(
             }
         }
 
-        // Convert the schema to deval format
-        let schema_json = serde_json::to_string(&test_case.schema)?;
-        let schema_path = format!("{}/temp_schema.json", temp_dir);
-        fs::write(&schema_path, &schema_json)?;
-
-        // Convert using our tool
-        let output = Command::new(deval_cli_path)
-            .args(&["convert-json-schema", &schema_path])
-            .output()?;
-
-        if !output.status.success() {
-            if verbose {
-                println!("  Test case {}: Conversion failed", i);
+        // Convert the schema to deval format, once for all of its tests
+        let schema = match convert_schema(mode, &test_case.schema)? {
+            Ok((_, schema)) => schema,
+            Err(e) => {
+                if verbose {
+                    println!("  Test case {}: Conversion failed: {}", i, e);
+                }
+                total_tests += test_case.tests.len();
+                continue;
             }
-            total_tests += test_case.tests.len();
-            continue;
-        }
-
-        let deval_schema = String::from_utf8(output.stdout)?;
-        let dvl_path = format!("{}/temp_schema.dvl", temp_dir);
-        fs::write(&dvl_path, &deval_schema)?;
+        };
 
         // Run each test in this test case
         for (j, test) in test_case.tests.iter().enumerate() {
             total_tests += 1;
 
-            let result = run_single_test(deval_cli_path, temp_dir, test, &dvl_path)?;
+            let result = run_single_test(mode, &schema, test)?;
 
             // Check if result matches expectation
             if result.success == test.valid {
@@ -243,7 +318,7 @@ fn test_file_coverage(
 }
 
 fn run_debug(
-    deval_cli_path: &str,
+    mode: &Mode,
     filename: &str,
     case_index: usize,
     test_index: usize,
@@ -254,8 +329,14 @@ fn run_debug(
     );
 
     // Create temp directory
-    let temp_dir = "/tmp/json-schema-test-runner-debug";
-    fs::create_dir_all(temp_dir)?;
+    if let Mode::Subprocess { temp_dir, .. } = mode {
+        fs::create_dir_all(temp_dir)?;
+    }
+    let clean_up = || {
+        if let Mode::Subprocess { temp_dir, .. } = mode {
+            let _ = fs::remove_dir_all(temp_dir);
+        }
+    };
 
     let current_dir = env::current_dir()?;
     let filepath = format!(
@@ -283,28 +364,17 @@ fn run_debug(
     );
 
     // Convert the schema to deval format
-    let schema_json = serde_json::to_string(&test_case.schema)?;
-    let schema_path = format!("{}/temp_schema.json", temp_dir);
-    fs::write(&schema_path, &schema_json)?;
-
-    // Convert using our tool
-    let output = Command::new(deval_cli_path)
-        .args(&["convert-json-schema", &schema_path])
-        .output()?;
-
-    if !output.status.success() {
-        println!(
-            "Conversion failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-        let _ = fs::remove_dir_all(temp_dir);
-        return Ok(());
-    }
-
-    let deval_schema = String::from_utf8(output.stdout)?;
-    println!("Converted schema: {}", deval_schema);
-    let dvl_path = format!("{}/temp_schema.dvl", temp_dir);
-    fs::write(&dvl_path, &deval_schema)?;
+    let schema = match convert_schema(mode, &test_case.schema)? {
+        Ok((deval_schema, schema)) => {
+            println!("Converted schema: {}", deval_schema);
+            schema
+        }
+        Err(e) => {
+            println!("Conversion failed: {}", e);
+            clean_up();
+            return Ok(());
+        }
+    };
 
     if test_index >= test_case.tests.len() {
         eprintln!(
@@ -312,7 +382,7 @@ fn run_debug(
             test_index,
             test_case.tests.len() - 1
         );
-        let _ = fs::remove_dir_all(temp_dir);
+        clean_up();
         return Ok(());
     }
 
@@ -322,7 +392,7 @@ fn run_debug(
     println!("Data: {}", serde_json::to_string_pretty(&test.data)?);
     println!("Expected valid: {}", test.valid);
 
-    let result = run_single_test(deval_cli_path, temp_dir, test, &dvl_path)?;
+    let result = run_single_test(mode, &schema, test)?;
 
     println!("Actual valid: {}", result.success);
     if result.success == test.valid {
@@ -339,7 +409,7 @@ fn run_debug(
     }
 
     // Clean up temp directory
-    let _ = fs::remove_dir_all(temp_dir);
+    clean_up();
 
     Ok(())
 }
@@ -350,20 +420,80 @@ struct TestResult {
     stderr: String,
 }
 
+/// Converts a JSON schema to the deval schema language and prepares it for
+/// checking. The inner error describes a failed conversion or compilation.
+#[allow(clippy::type_complexity)]
+fn convert_schema(
+    mode: &Mode,
+    json_schema: &serde_json::Value,
+) -> Result<Result<(String, CompiledSchema), String>, Box<dyn std::error::Error>> {
+    let schema_json = serde_json::to_string(json_schema)?;
+    match mode {
+        Mode::InProcess => {
+            let deval_schema = match deval_schema_from_json_schema::try_convert(&schema_json) {
+                Ok(deval_schema) => deval_schema,
+                Err(e) => return Ok(Err(format!("invalid JSON schema: {e}"))),
+            };
+            // A panic fails only the schema at hand, as in a subprocess
+            let Ok(compiled) = std::panic::catch_unwind(|| deval_schema::compile(&deval_schema))
+            else {
+                return Ok(Err("the compiler panicked".to_string()));
+            };
+            let validator = match compiled {
+                Ok(validator) => validator,
+                Err(errors) => {
+                    let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                    return Ok(Err(errors.join(", ")));
+                }
+            };
+            Ok(Ok((deval_schema, CompiledSchema::InProcess(validator))))
+        }
+        Mode::Subprocess { cli_path, temp_dir } => {
+            let schema_path = format!("{}/temp_schema.json", temp_dir);
+            fs::write(&schema_path, &schema_json)?;
+
+            // Convert using our tool
+            let output = Command::new(cli_path)
+                .args(["convert-json-schema", "--inline", &schema_path])
+                .output()?;
+
+            if !output.status.success() {
+                return Ok(Err(String::from_utf8_lossy(&output.stderr).to_string()));
+            }
+
+            let deval_schema = String::from_utf8(output.stdout)?;
+            let dvl_path = format!("{}/temp_schema.dvl", temp_dir);
+            fs::write(&dvl_path, &deval_schema)?;
+            Ok(Ok((deval_schema, CompiledSchema::Subprocess(dvl_path))))
+        }
+    }
+}
+
 fn run_single_test(
-    deval_cli_path: &str,
-    temp_dir: &str,
+    mode: &Mode,
+    schema: &CompiledSchema,
     test: &Test,
-    dvl_path: &str,
 ) -> Result<TestResult, Box<dyn std::error::Error>> {
-    // Write test data to temporary file
     let test_data = serde_json::to_string(&test.data)?;
+    let (dvl_path, cli_path, temp_dir) = match (schema, mode) {
+        (CompiledSchema::InProcess(validator), _) => {
+            return Ok(validate_in_process(&**validator, &test_data));
+        }
+        (CompiledSchema::Subprocess(dvl_path), Mode::Subprocess { cli_path, temp_dir }) => {
+            (dvl_path, cli_path, temp_dir)
+        }
+        (CompiledSchema::Subprocess(_), Mode::InProcess) => {
+            unreachable!("schemas are compiled in the mode they are run in")
+        }
+    };
+
+    // Write test data to temporary file
     let data_path = format!("{}/temp_data.json", temp_dir);
     fs::write(&data_path, &test_data)?;
 
     // Validate using our tool
-    let output = Command::new(deval_cli_path)
-        .args(&["check", "--schema", dvl_path, "--file", &data_path])
+    let output = Command::new(cli_path)
+        .args(["check", "--schema", dvl_path, "--file", &data_path])
         .output()?;
 
     Ok(TestResult {
@@ -372,3 +502,26 @@ fn run_single_test(
         stderr: String::from_utf8_lossy(&output.stderr).to_string(),
     })
 }
+
+/// Parses and validates `data` like `deval-cli check` does, with the errors
+/// and warnings written to `stderr`. As with the CLI, warnings alone don't fail
+/// the check.
+fn validate_in_process(validator: &dyn Validator, data: &str) -> TestResult {
+    let (success, errors): (bool, Vec<String>) = match Json.parse(data, "temp_data.json") {
+        Ok(parsed) => {
+            match std::panic::catch_unwind(AssertUnwindSafe(|| validator.validate(parsed))) {
+                Ok(result) => (
+                    !result.errors.iter().any(|e| e.severity == Severity::Error),
+                    result.errors.into_iter().map(|e| e.text).collect(),
+                ),
+                Err(_) => (false, vec!["the validator panicked".to_string()]),
+            }
+        }
+        Err(errors) => (false, errors.into_iter().map(|e| e.message).collect()),
+    };
+    TestResult {
+        success,
+        stdout: String::new(),
+        stderr: errors.join("\n"),
+    }
+}